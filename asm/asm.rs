@@ -7,34 +7,156 @@ use common::*;
 
 fn main() {
   let args: Vec<String> = std::env::args().collect();
-  if args.len() != 3 {
-    eprintln!("Asm: Usage: asm <assembly source file> <memory image file>");
+
+  let mut symbol_file: Option<String> = None;
+  let mut listing_file: Option<String> = None;
+  let mut report_file: Option<String> = None;
+  let mut disabled: BTreeSet<Family> = BTreeSet::new();
+  let mut positional_args: Vec<&String> = vec![];
+
+  let mut args_iter = args.iter().skip(1);
+  while let Some(arg) = args_iter.next() {
+    match arg.as_str() {
+      "--symbols" => {
+        symbol_file = Some(args_iter.next().cloned().unwrap_or_else(|| {
+          eprintln!("Asm: Error: Option '--symbols' expects a symbol file");
+          std::process::exit(1);
+        }))
+      }
+      "--listing" => {
+        listing_file = Some(args_iter.next().cloned().unwrap_or_else(|| {
+          eprintln!("Asm: Error: Option '--listing' expects a listing file");
+          std::process::exit(1);
+        }))
+      }
+      "--report" => {
+        report_file = Some(args_iter.next().cloned().unwrap_or_else(|| {
+          eprintln!("Asm: Error: Option '--report' expects a report file");
+          std::process::exit(1);
+        }))
+      }
+      "--no-optimize" => match args_iter.next().map(String::as_str) {
+        Some("all") => disabled.extend(Family::ALL),
+        Some(name) => match Family::ALL.iter().find(|family| family.to_string() == name) {
+          Some(family) => {
+            disabled.insert(*family);
+          }
+          None => {
+            eprintln!("Asm: Error: Unknown optimization family '{}'", name);
+            std::process::exit(1);
+          }
+        },
+        None => {
+          eprintln!("Asm: Error: Option '--no-optimize' expects an optimization family or 'all'");
+          std::process::exit(1);
+        }
+      },
+      _ if arg.starts_with("--") => {
+        eprintln!("Asm: Error: Unknown option '{}'", arg);
+        std::process::exit(1);
+      }
+      _ => positional_args.push(arg),
+    }
+  }
+
+  if positional_args.len() != 2 {
+    eprintln!(
+      "Asm: Usage: asm [--symbols <symbol file>] [--listing <listing file>] [--report <report file>] [--no-optimize <family>]... <assembly source file> <memory image file>"
+    );
     std::process::exit(1);
   }
 
   let mut errors: Vec<(Pos, Error)> = vec![];
-  let memory_image_file = &args[2];
-  let assembly_source_file: File = File(args[1].clone().into());
+  let mut labels: BTreeMap<Label, u8> = BTreeMap::new();
+  let memory_image_file = positional_args[1];
+  let assembly_source_file: File = File(positional_args[0].clone().into());
 
   let preprocessed: Vec<(Pos, String)> = preprocess(assembly_source_file, &mut errors, None);
   let mnemonics: Vec<(Pos, Mnemonic)> = mnemonize(preprocessed, &mut errors);
   let tokens: Vec<(Pos, Token)> = tokenize(mnemonics, &mut errors);
-  let instructions: Vec<(Pos, Result<Instruction, u8>)> = assemble(tokens, &mut errors, "main");
-  let opcodes: Vec<(Pos, u8)> = codegen(instructions, &mut errors);
-  let memory_image: Vec<(Pos, u8)> = opcodes;
+  let mut padding: BTreeSet<usize> = BTreeSet::new();
+  let mut origins: Vec<Origin> = vec![];
+  let mut rewrites: Vec<Rewrite> = vec![];
+  let instructions: Vec<(Origin, Result<Instruction, u8>)> = assemble(
+    tokens,
+    &mut errors,
+    &mut labels,
+    &mut padding,
+    &mut origins,
+    &disabled,
+    &mut rewrites,
+    "main",
+  );
+  let data: BTreeSet<u8> = (0..common::MEM_SIZE)
+    .zip(instructions.iter())
+    .filter(|(address, _)| !padding.contains(address))
+    .filter(|(_, (_, instruction))| instruction.is_err())
+    .map(|(address, _)| address as u8)
+    .collect();
+  let code: BTreeSet<u8> = (0..common::MEM_SIZE)
+    .zip(instructions.iter())
+    .filter(|(_, (_, instruction))| instruction.is_ok())
+    .map(|(address, _)| address as u8)
+    .collect();
+  let display: BTreeSet<u8> = (common::DISPLAY_BUFFER..common::MEM_SIZE)
+    .map(|address| address as u8)
+    .filter(|address| !data.contains(address) && !code.contains(address))
+    .collect();
+  let opcodes: Vec<(Origin, u8)> = codegen(instructions, &mut errors);
+  let memory_image: Vec<(Origin, u8)> = opcodes;
+
+  let write = |file: &String, contents: Vec<u8>| {
+    std::fs::write(file, contents).unwrap_or_else(|_| {
+      eprintln!("Asm: Error: Unable to write file '{}'", file);
+      std::process::exit(1);
+    })
+  };
 
   match errors[..] {
     [] => {
-      std::fs::write::<&String, [u8; common::MEM_SIZE]>(
+      if let Some(symbol_file) = symbol_file {
+        let symbols = Symbols {
+          labels,
+          data,
+          code,
+          display,
+          origins: memory_image
+            .iter()
+            .take(common::MEM_SIZE)
+            .enumerate()
+            .map(|(address, (Origin(pos, macros), _))| {
+              (address as u8, (pos.clone(), macros.clone()))
+            })
+            .collect(),
+          sources: HashMap::new(),
+        };
+
+        write(
+          &symbol_file,
+          format!("# Generated by Asm\n\n{}", symbols).into(),
+        );
+      }
+
+      if let Some(report_file) = report_file {
+        let report = report(rewrites.clone(), &disabled);
+        write(
+          &report_file,
+          format!("# Generated by Asm\n\n{}", report).into(),
+        );
+      }
+
+      if let Some(listing_file) = listing_file {
+        let listing = listing(&memory_image, origins, rewrites, &padding);
+        write(
+          &listing_file,
+          format!("# Generated by Asm\n\n{}", listing).into(),
+        );
+      }
+
+      write(
         memory_image_file,
-        memory_image
-          .into_iter()
-          .map(|(_, b)| b)
-          .collect::<Vec<u8>>()
-          .try_into()
-          .unwrap(),
-      )
-      .unwrap();
+        memory_image.into_iter().map(|(_, b)| b).collect(),
+      );
     }
     _ => {
       let errors = errors
@@ -55,12 +177,56 @@ enum Root {
   Conditional(Node, Node),
   LabelDefs(Vec<Label>),
   Node(Node),
+  Operator(Operator),
   Const,
   Data(Option<Node>),
   Dyn(Option<Instruction>),
   Org(Option<Node>),
+  Bank(Option<Node>),
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Operator {
+  Mul,
+  Div,
+  Mod,
+}
+
+// families of optimizer rewrites, which can be disabled individually from the command line
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+enum Family {
+  Nodes,        // constant folding and identities such as `x00 add`
+  OpTypes,      // removal of values that are pushed then popped
+  Ldos,         // `ldo`s of nodes into nodes, and duplicate nodes into `ldo`s
+  UnaryOps,     // idempotent and involutive instructions such as `swp swp`
+  Labels,       // merging of adjacent label definitions
+  Conditionals, // nodes selected through `iff`
+  Swps,         // swapped nodes
+  Stos,         // `sto`s of nodes
+  Jumps,        // jumps to the following instruction, and through labels that only jump
+}
+
+impl Family {
+  const ALL: [Family; 9] = [
+    Family::Nodes,
+    Family::OpTypes,
+    Family::Ldos,
+    Family::UnaryOps,
+    Family::Labels,
+    Family::Conditionals,
+    Family::Swps,
+    Family::Stos,
+    Family::Jumps,
+  ];
 }
 
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Origin(Pos, Vec<Macro>); // source position and macro expansion chain
+
+type Rewrite = (Origin, Family, Vec<Root>, Vec<Root>); // origin, family, roots before and after
+
+type MacroDefinitions = HashMap<Macro, (Vec<Param>, Vec<(Pos, Token)>)>; // parameters and tokens
+
 #[derive(Clone, Eq, PartialEq, Hash)]
 enum Node {
   LabelRef(Label),
@@ -72,6 +238,9 @@ enum Node {
   And(Box<Node>, Box<Node>),
   Xor(Box<Node>, Box<Node>),
   Xnd(Box<Node>, Box<Node>),
+  Mul(Box<Node>, Box<Node>),
+  Div(Box<Node>, Box<Node>),
+  Mod(Box<Node>, Box<Node>),
   Shl(Box<Node>),
   Shr(Box<Node>),
   Not(Box<Node>),
@@ -123,23 +292,11 @@ fn mnemonize(
 ) -> Vec<(Pos, Mnemonic)> {
   let mnemonics: Vec<(Pos, Mnemonic)> = lines
     .into_iter()
-    .flat_map(|(mut pos, line)| {
-      let mut mnemonic = "".to_string();
-      let mut mnemonics = vec![];
-      for (col, char) in line.chars().enumerate() {
-        if char.is_whitespace() {
-          mnemonics.push((pos.clone(), mnemonic));
-          mnemonic = "".to_string();
-          pos = Pos(pos.0, pos.1, col + 1);
-        } else {
-          mnemonic.push(char);
-        }
-      }
-      mnemonics.push((pos, mnemonic));
-      mnemonics
+    .flat_map(|(pos, line)| {
+      common::split_mnemonics(&line)
+        .into_iter()
+        .map(move |(col, mnemonic)| (Pos(pos.0.clone(), pos.1, pos.2 + col), mnemonic))
     })
-    .filter(|(_, mnemonic)| mnemonic.len() > 0)
-    .map(|(pos, mnemonic)| (pos, Mnemonic(mnemonic)))
     .collect();
 
   mnemonics
@@ -167,19 +324,40 @@ fn tokenize(
   tokens
 }
 
+#[allow(clippy::too_many_arguments)]
 fn assemble(
   tokens: Vec<(Pos, Token)>,
   errors: &mut impl Extend<(Pos, Error)>,
+  labels: &mut impl Extend<(Label, u8)>,
+  padding: &mut impl Extend<usize>,
+  origins: &mut impl Extend<Origin>,
+  disabled: &BTreeSet<Family>,
+  rewrites: &mut impl Extend<Rewrite>,
   entry_point: &str,
-) -> Vec<(Pos, Result<Instruction, u8>)> {
+) -> Vec<(Origin, Result<Instruction, u8>)> {
   // resolve macros recursively from `entry_point` and identify unused labels
 
-  let mut macro_definitions: HashMap<Macro, Vec<(Pos, Token)>> = HashMap::new();
+  let mut macro_definitions: MacroDefinitions = HashMap::new();
   let mut current_macro: Option<Macro> = None;
 
   for (pos, token) in tokens.into_iter() {
     match token {
-      Token::MacroDef(r#macro) => {
+      Token::MacroDef(ref r#macro) | Token::MacroDefParams(ref r#macro, _) => {
+        let params = match token {
+          Token::MacroDefParams(_, ref params) => params.clone(),
+          _ => vec![],
+        };
+        for (index, param) in params.iter().enumerate() {
+          if params[..index].contains(param) {
+            errors.extend([(
+              pos.clone(),
+              Error(format!(
+                "Duplicate parameter `{}` in definition of macro `{}`",
+                param, r#macro
+              )),
+            )]);
+          }
+        }
         current_macro = Some(r#macro.clone());
         macro_definitions
           .entry(r#macro.clone())
@@ -189,14 +367,14 @@ fn assemble(
               Error(format!("Duplicate macro definition `{}`", r#macro)),
             )]);
           })
-          .or_insert(vec![]);
+          .or_insert((params, vec![]));
       }
 
       _ => match current_macro
         .as_ref()
         .and_then(|r#macro| macro_definitions.get_mut(&r#macro))
       {
-        Some(macro_tokens) => macro_tokens.push((pos, token)),
+        Some((_, macro_tokens)) => macro_tokens.push((pos, token)),
         None => errors.extend([(pos, Error(format!("Orphan token `{}` encountered", token)))]),
       },
     }
@@ -204,26 +382,32 @@ fn assemble(
 
   let tokens = expand_macros(
     &vec![(
-      Pos(File("[bootstrap]".into()), 0, 0),
+      Origin(Pos(File("[bootstrap]".into()), 0, 0), vec![]),
       Token::MacroRef(Macro(entry_point.to_string())),
     )],
     &mut 0,
-    &mut vec![],
     &macro_definitions,
     errors,
   );
 
+  // every token carries the macro expansion chain it appears in. tokens substituted in for
+  // parameters carry the chain of the macro reference they were given in rather than that of the
+  // macro they are substituted into, so a macro can be given references to itself as arguments
   fn expand_macros(
-    tokens: &Vec<(Pos, Token)>,
+    tokens: &Vec<(Origin, Token)>,
     scope_uid: &mut usize,
-    parent_macros: &mut Vec<Macro>,
-    macro_definitions: &HashMap<Macro, Vec<(Pos, Token)>>,
+    macro_definitions: &MacroDefinitions,
     errors: &mut impl Extend<(Pos, Error)>,
-  ) -> Vec<(Pos, Token)> {
+  ) -> Vec<(Origin, Token)> {
     tokens
       .into_iter()
-      .flat_map(|(pos, token)| match token {
-        Token::MacroRef(r#macro) => {
+      .flat_map(|(origin @ Origin(pos, parent_macros), token)| match token {
+        Token::MacroRef(r#macro) | Token::MacroRefArgs(r#macro, _) => {
+          let args = match token {
+            Token::MacroRefArgs(_, args) => args.clone(),
+            _ => vec![],
+          };
+
           if parent_macros.contains(&r#macro) {
             errors.extend([(
               pos.clone(),
@@ -240,41 +424,56 @@ fn assemble(
             return vec![];
           }
 
-          let tokens = macro_definitions.get(&r#macro).cloned().unwrap_or_else(|| {
+          let Some((params, tokens)) = macro_definitions.get(&r#macro).cloned() else {
             errors.extend([(
               pos.clone(),
               Error(format!("Reference to undefined macro `{}`", r#macro)),
             )]);
-            vec![]
-          });
+            return vec![];
+          };
+
+          if args.len() != params.len() {
+            errors.extend([(
+              pos.clone(),
+              Error(format!(
+                "Argument count mismatch for macro `{}`: expected {}, found {}",
+                r#macro,
+                params.len(),
+                args.len()
+              )),
+            )]);
+            return vec![];
+          }
 
+          // local labels are scoped to the macro expansion they appear in. arguments are substituted
+          // in after scoping so that local labels within arguments remain scoped to the caller
+          let macros = [parent_macros.clone(), vec![r#macro.clone()]].concat();
           let tokens = tokens
             .into_iter()
-            .map(|(pos, token)| match token {
-              Token::LabelDef(Label::Local(identifier, _)) => (
-                pos,
-                Token::LabelDef(Label::Local(identifier, Some(*scope_uid))),
-              ),
-              Token::LabelRef(Label::Local(identifier, _)) => (
-                pos,
-                Token::LabelRef(Label::Local(identifier, Some(*scope_uid))),
-              ),
-              _ => (pos, token),
+            .map(|(pos, token)| (pos, scope_token(token, *scope_uid)))
+            .flat_map(|(token_pos, token)| {
+              let substituted_origin = match token {
+                Token::ParamRef(_) => origin.clone(),
+                _ => Origin(token_pos.clone(), macros.clone()),
+              };
+              match substitute_params(token, &params, &args) {
+                Ok(tokens) => tokens
+                  .into_iter()
+                  .map(|token| (substituted_origin.clone(), token))
+                  .collect(),
+                Err(param) => {
+                  errors.extend([(
+                    token_pos,
+                    Error(format!("Reference to undefined parameter `{}`", param)),
+                  )]);
+                  vec![]
+                }
+              }
             })
             .collect();
 
           *scope_uid += 1;
-          parent_macros.push(r#macro.clone());
-          let tokens = expand_macros(
-            &tokens,
-            scope_uid,
-            parent_macros,
-            &macro_definitions,
-            errors,
-          );
-          parent_macros.pop();
-
-          tokens
+          expand_macros(&tokens, scope_uid, &macro_definitions, errors)
         }
 
         Token::AtError => {
@@ -284,15 +483,78 @@ fn assemble(
           )]);
           vec![]
         }
-        _ => vec![(pos.clone(), token.clone())],
+        Token::DefinedMacro(r#macro) => vec![(
+          origin.clone(),
+          Token::XXX(match macro_definitions.contains_key(r#macro) {
+            true => 0xFF,
+            false => 0x00,
+          }),
+        )],
+        _ => vec![(origin.clone(), token.clone())],
       })
       .collect()
   }
 
+  fn scope_token(token: Token, scope_uid: usize) -> Token {
+    match token {
+      Token::LabelDef(Label::Local(identifier, _)) => {
+        Token::LabelDef(Label::Local(identifier, Some(scope_uid)))
+      }
+      Token::LabelRef(Label::Local(identifier, _)) => {
+        Token::LabelRef(Label::Local(identifier, Some(scope_uid)))
+      }
+      Token::DefinedLabel(Label::Local(identifier, _)) => {
+        Token::DefinedLabel(Label::Local(identifier, Some(scope_uid)))
+      }
+      Token::MacroRefArgs(r#macro, args) => Token::MacroRefArgs(
+        r#macro,
+        args
+          .into_iter()
+          .map(|arg| {
+            arg
+              .into_iter()
+              .map(|token| scope_token(token, scope_uid))
+              .collect()
+          })
+          .collect(),
+      ),
+      _ => token,
+    }
+  }
+
+  // replaces parameter references with their arguments, including within the arguments of nested
+  // macro references. returns the first parameter that could not be found
+  fn substitute_params(
+    token: Token,
+    params: &Vec<Param>,
+    args: &Vec<Vec<Token>>,
+  ) -> Result<Vec<Token>, Param> {
+    match token {
+      Token::ParamRef(param) => match params.iter().position(|other| *other == param) {
+        Some(index) => Ok(args[index].clone()),
+        None => Err(param),
+      },
+      Token::MacroRefArgs(r#macro, nested_args) => Ok(vec![Token::MacroRefArgs(
+        r#macro,
+        nested_args
+          .into_iter()
+          .map(|arg| {
+            arg
+              .into_iter()
+              .map(|token| substitute_params(token, params, args))
+              .collect::<Result<Vec<Vec<Token>>, Param>>()
+              .map(|tokens| tokens.concat())
+          })
+          .collect::<Result<Vec<Vec<Token>>, Param>>()?,
+      )]),
+      _ => Ok(vec![token]),
+    }
+  }
+
   let label_definitions: HashMap<Label, Pos> = tokens
     .iter()
     .filter_map(|(pos, token)| match token {
-      Token::LabelDef(label) => Some((label.clone(), pos.clone())),
+      Token::LabelDef(label) => Some((label.clone(), pos.0.clone())),
       _ => None,
     })
     .collect();
@@ -300,83 +562,283 @@ fn assemble(
   let label_references: HashSet<Label> = tokens
     .iter()
     .filter_map(|(_pos, token)| match token {
-      Token::LabelRef(label) => Some(label.clone()),
+      Token::LabelRef(label) | Token::DefinedLabel(label) => Some(label.clone()),
       _ => None,
     })
     .collect();
 
-  errors.extend(label_definitions.into_iter().filter_map(|(label, pos)| {
-    (!label_references.contains(&label))
-      .then_some((pos, Error(format!("Unused label definition `{}`", label))))
+  errors.extend(label_definitions.iter().filter_map(|(label, pos)| {
+    (!label_references.contains(label)).then_some((
+      pos.clone(),
+      Error(format!("Unused label definition `{}`", label)),
+    ))
   }));
 
-  // turn assembly tokens into roots, an intermediate representation for optimization. roots correspond to valid instructions
+  // defined-label tests are resolved against every label definition, including those within
+  // branches that conditional assembly later drops. string literals are split into data bytes
 
-  let roots: Vec<(Pos, Root)> = tokens
+  let tokens: Vec<(Origin, Token)> = tokens
     .into_iter()
-    .map(|(pos, token)| {
-      let token = match token {
-        Token::LabelDef(label) => Root::LabelDefs(vec![label]),
-        Token::LabelRef(label) => Root::Node(Node::LabelRef(label)),
-        Token::MacroDef(_) => panic!("Macro definition found in intermediate representation"),
-        Token::MacroRef(_) => panic!("Macro reference found in intermediate representation"),
-        Token::AtError => panic!("Error directive found in intermediate representation"),
-        Token::AtConst => Root::Const,
-        Token::AtData => Root::Data(None),
-        Token::AtDyn => Root::Dyn(None),
-        Token::AtOrg => Root::Org(None),
-        Token::XXX(value) => Root::Node(Node::Value(value)),
-        Token::Add => Root::Instruction(Instruction::Add(Size::assert(0x01))),
-        Token::AdS(size) => Root::Instruction(Instruction::Add(size)),
-        Token::Sub => Root::Instruction(Instruction::Sub(Size::assert(0x01))),
-        Token::SuS(size) => Root::Instruction(Instruction::Sub(size)),
-        Token::Iff => Root::Instruction(Instruction::Iff(Size::assert(0x01))),
-        Token::IfS(size) => Root::Instruction(Instruction::Iff(size)),
-        Token::Swp => Root::Instruction(Instruction::Swp(Size::assert(0x01))),
-        Token::SwS(size) => Root::Instruction(Instruction::Swp(size)),
-        Token::Rot => Root::Instruction(Instruction::Rot(Size::assert(0x01))),
-        Token::RoS(size) => Root::Instruction(Instruction::Rot(size)),
-        Token::Orr => Root::Instruction(Instruction::Orr(Size::assert(0x01))),
-        Token::OrS(size) => Root::Instruction(Instruction::Orr(size)),
-        Token::And => Root::Instruction(Instruction::And(Size::assert(0x01))),
-        Token::AnS(size) => Root::Instruction(Instruction::And(size)),
-        Token::Xor => Root::Instruction(Instruction::Xor(Size::assert(0x01))),
-        Token::XoS(size) => Root::Instruction(Instruction::Xor(size)),
-        Token::Xnd => Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-        Token::XnS(size) => Root::Instruction(Instruction::Xnd(size)),
-        Token::Inc => Root::Instruction(Instruction::Inc),
-        Token::Dec => Root::Instruction(Instruction::Dec),
-        Token::Neg => Root::Instruction(Instruction::Neg),
-        Token::Shl => Root::Instruction(Instruction::Shl),
-        Token::Shr => Root::Instruction(Instruction::Shr),
-        Token::Not => Root::Instruction(Instruction::Not),
-        Token::Buf => Root::Instruction(Instruction::Buf),
-        Token::LdO(ofst) => Root::Instruction(Instruction::Ldo(ofst)),
-        Token::StO(ofst) => Root::Instruction(Instruction::Sto(ofst)),
-        Token::Lda => Root::Instruction(Instruction::Lda),
-        Token::Sta => Root::Instruction(Instruction::Sta),
-        Token::Ldi => Root::Instruction(Instruction::Ldi),
-        Token::Sti => Root::Instruction(Instruction::Sti),
-        Token::Lds => Root::Instruction(Instruction::Lds),
-        Token::Sts => Root::Instruction(Instruction::Sts),
-        Token::Clc => Root::Instruction(Instruction::Clc),
-        Token::Sec => Root::Instruction(Instruction::Sec),
-        Token::Flc => Root::Instruction(Instruction::Flc),
-        Token::Nop => Root::Instruction(Instruction::Nop),
-        Token::Pop => Root::Instruction(Instruction::Pop),
-        Token::AtDD(0xBB) => Root::Instruction(Instruction::Dbg),
-        Token::AtDD(value) => Root::Data(Some(Node::Value(value))),
-      };
-
-      (pos, token)
+    .flat_map(|(pos, token)| match token {
+      Token::DefinedLabel(label) => vec![(
+        pos,
+        Token::XXX(match label_definitions.contains_key(&label) {
+          true => 0xFF,
+          false => 0x00,
+        }),
+      )],
+      Token::AtStr(bytes) => bytes
+        .into_iter()
+        .map(|byte| (pos.clone(), Token::AtDD(byte)))
+        .collect(),
+      _ => vec![(pos, token)],
     })
     .collect();
 
-  let roots = optimize(roots, errors);
+  // resolve conditional assembly and named constants in a single pass, so that constants can be
+  // defined conditionally. the argument to `@if` or `@equ` is the shortest sequence of tokens
+  // preceding it that reduces to a single node, which for `@if` must also be constant. tokens within
+  // branches not taken are dropped and constant references are replaced with their definitions
+
+  let mut resolved_tokens: Vec<(Origin, Token)> = vec![];
+  let mut conditionals: Vec<(Origin, bool, bool)> = vec![]; // (origin, branch taken, `@else` seen)
+  let mut constants: HashMap<Constant, Vec<Token>> = HashMap::new();
+  let mut boundary = 0; // arguments may not span across directives
+
+  // removes and returns the shortest sequence of tokens past `boundary` at the end of `tokens` that
+  // reduces to a single node satisfying `predicate`, along with that node
+  fn take_argument(
+    tokens: &mut Vec<(Origin, Token)>,
+    boundary: usize,
+    predicate: impl Fn(&Node) -> bool,
+  ) -> Option<(Vec<Token>, Node)> {
+    let max_len = tokens[boundary..]
+      .iter()
+      .rev()
+      .take_while(|(_, token)| {
+        matches!(
+          token_to_root(token.clone()),
+          Root::Node(_) | Root::Instruction(_) | Root::Operator(_)
+        )
+      })
+      .count();
+    let (len, node) = (1..=max_len).find_map(|len| {
+      let roots = tokens[tokens.len() - len..]
+        .iter()
+        .cloned()
+        .map(|(origin, token)| (origin, token_to_root(token)))
+        .collect();
+      match &optimize(
+        roots,
+        &BTreeSet::new(),
+        &mut vec![],
+        &mut Vec::<(Pos, Error)>::new(),
+      )[..]
+      {
+        [(_, Root::Node(node))] if predicate(node) => Some((len, node.clone())),
+        _ => None,
+      }
+    })?;
+    let argument = tokens.split_off(tokens.len() - len);
+    Some((argument.into_iter().map(|(_, token)| token).collect(), node))
+  }
+
+  let mut tokens = tokens.into_iter().peekable();
+  while let Some((origin, token)) = tokens.next() {
+    let active = conditionals.iter().all(|(_, taken, _)| *taken);
+    match token {
+      Token::AtIf => {
+        let taken = active
+          && match take_argument(&mut resolved_tokens, boundary, |node| {
+            resolve_node_value(node, &HashMap::new()).is_ok()
+          }) {
+            Some((_, node)) => resolve_node_value(&node, &HashMap::new()) != Ok(0x00),
+            None => {
+              errors.extend([(
+                origin.0.clone(),
+                Error(format!(
+                  "`{}` argument could not be reduced to a constant expression",
+                  token
+                )),
+              )]);
+              false
+            }
+          };
+        conditionals.push((origin, taken, false));
+      }
+      Token::AtElse => match conditionals.last_mut() {
+        Some((_, _, true)) => errors.extend([(
+          origin.0,
+          Error(format!(
+            "Duplicate `{}` for the same `{}`",
+            token,
+            Token::AtIf
+          )),
+        )]),
+        Some((_, taken, else_seen)) => (*taken, *else_seen) = (!*taken, true),
+        None => errors.extend([(
+          origin.0,
+          Error(format!("`{}` without matching `{}`", token, Token::AtIf)),
+        )]),
+      },
+      Token::AtEnd => match conditionals.pop() {
+        Some(_) => {}
+        None => errors.extend([(
+          origin.0,
+          Error(format!("`{}` without matching `{}`", token, Token::AtIf)),
+        )]),
+      },
+      Token::AtEqu => {
+        let constant = tokens.next_if(|(_, token)| matches!(token, Token::ConstantRef(_)));
+        if !active {
+          continue;
+        }
+        match (
+          constant,
+          take_argument(&mut resolved_tokens, boundary, |_| true),
+        ) {
+          (None, _) => errors.extend([(
+            origin.0,
+            Error(format!("`{}` must be followed by a constant name", token)),
+          )]),
+          (_, None) => errors.extend([(
+            origin.0,
+            Error(format!(
+              "`{}` argument could not be reduced to an expression",
+              token
+            )),
+          )]),
+          (Some((_, Token::ConstantRef(constant))), Some((argument, _))) => {
+            if constants.contains_key(&constant) {
+              errors.extend([(
+                origin.0,
+                Error(format!("Duplicate constant definition `{}`", constant)),
+              )]);
+            }
+            constants.insert(constant, argument);
+          }
+          (Some(_), Some(_)) => unreachable!(),
+        }
+      }
+      Token::ConstantRef(constant) if active => {
+        match constants.get(&constant) {
+          Some(argument) => {
+            resolved_tokens.extend(argument.iter().map(|token| (origin.clone(), token.clone())))
+          }
+          None => errors.extend([(
+            origin.0,
+            Error(format!("Reference to undefined constant `{}`", constant)),
+          )]),
+        }
+        continue;
+      }
+      _ => {
+        if active {
+          resolved_tokens.push((origin, token));
+        }
+        continue;
+      }
+    }
+    boundary = resolved_tokens.len();
+  }
+
+  errors.extend(conditionals.into_iter().map(|(origin, _, _)| {
+    (
+      origin.0,
+      Error(format!(
+        "`{}` without matching `{}`",
+        Token::AtIf,
+        Token::AtEnd
+      )),
+    )
+  }));
+
+  fn token_to_root(token: Token) -> Root {
+    match token {
+      Token::LabelDef(label) => Root::LabelDefs(vec![label]),
+      Token::LabelRef(label) => Root::Node(Node::LabelRef(label)),
+      Token::MacroDef(_) => panic!("Macro definition found in intermediate representation"),
+      Token::MacroRef(_) => panic!("Macro reference found in intermediate representation"),
+      Token::MacroDefParams(..) => {
+        panic!("Macro definition found in intermediate representation")
+      }
+      Token::MacroRefArgs(..) => panic!("Macro reference found in intermediate representation"),
+      Token::ParamRef(_) => panic!("Parameter reference found in intermediate representation"),
+      Token::DefinedLabel(_) | Token::DefinedMacro(_) => {
+        panic!("Definition test found in intermediate representation")
+      }
+      Token::AtError => panic!("Error directive found in intermediate representation"),
+      Token::AtIf | Token::AtElse | Token::AtEnd => {
+        panic!("Conditional directive found in intermediate representation")
+      }
+      Token::ConstantRef(_) => panic!("Constant reference found in intermediate representation"),
+      Token::AtEqu => panic!("Constant definition found in intermediate representation"),
+      Token::AtStr(_) => panic!("String literal found in intermediate representation"),
+      Token::AtConst => Root::Const,
+      Token::AtData => Root::Data(None),
+      Token::AtDyn => Root::Dyn(None),
+      Token::AtOrg => Root::Org(None),
+      Token::AtBank => Root::Bank(None),
+      Token::XXX(value) => Root::Node(Node::Value(value)),
+      Token::Mul => Root::Operator(Operator::Mul),
+      Token::Div => Root::Operator(Operator::Div),
+      Token::Mod => Root::Operator(Operator::Mod),
+      Token::Add => Root::Instruction(Instruction::Add(Size::assert(0x01))),
+      Token::AdS(size) => Root::Instruction(Instruction::Add(size)),
+      Token::Sub => Root::Instruction(Instruction::Sub(Size::assert(0x01))),
+      Token::SuS(size) => Root::Instruction(Instruction::Sub(size)),
+      Token::Iff => Root::Instruction(Instruction::Iff(Size::assert(0x01))),
+      Token::IfS(size) => Root::Instruction(Instruction::Iff(size)),
+      Token::Swp => Root::Instruction(Instruction::Swp(Size::assert(0x01))),
+      Token::SwS(size) => Root::Instruction(Instruction::Swp(size)),
+      Token::Rot => Root::Instruction(Instruction::Rot(Size::assert(0x01))),
+      Token::RoS(size) => Root::Instruction(Instruction::Rot(size)),
+      Token::Orr => Root::Instruction(Instruction::Orr(Size::assert(0x01))),
+      Token::OrS(size) => Root::Instruction(Instruction::Orr(size)),
+      Token::And => Root::Instruction(Instruction::And(Size::assert(0x01))),
+      Token::AnS(size) => Root::Instruction(Instruction::And(size)),
+      Token::Xor => Root::Instruction(Instruction::Xor(Size::assert(0x01))),
+      Token::XoS(size) => Root::Instruction(Instruction::Xor(size)),
+      Token::Xnd => Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+      Token::XnS(size) => Root::Instruction(Instruction::Xnd(size)),
+      Token::Inc => Root::Instruction(Instruction::Inc),
+      Token::Dec => Root::Instruction(Instruction::Dec),
+      Token::Neg => Root::Instruction(Instruction::Neg),
+      Token::Shl => Root::Instruction(Instruction::Shl),
+      Token::Shr => Root::Instruction(Instruction::Shr),
+      Token::Not => Root::Instruction(Instruction::Not),
+      Token::Buf => Root::Instruction(Instruction::Buf),
+      Token::LdO(ofst) => Root::Instruction(Instruction::Ldo(ofst)),
+      Token::StO(ofst) => Root::Instruction(Instruction::Sto(ofst)),
+      Token::Lda => Root::Instruction(Instruction::Lda),
+      Token::Sta => Root::Instruction(Instruction::Sta),
+      Token::Ldi => Root::Instruction(Instruction::Ldi),
+      Token::Sti => Root::Instruction(Instruction::Sti),
+      Token::Lds => Root::Instruction(Instruction::Lds),
+      Token::Sts => Root::Instruction(Instruction::Sts),
+      Token::Clc => Root::Instruction(Instruction::Clc),
+      Token::Sec => Root::Instruction(Instruction::Sec),
+      Token::Flc => Root::Instruction(Instruction::Flc),
+      Token::Nop => Root::Instruction(Instruction::Nop),
+      Token::Pop => Root::Instruction(Instruction::Pop),
+      Token::AtDD(0xBB) => Root::Instruction(Instruction::Dbg),
+      Token::AtDD(value) => Root::Data(Some(Node::Value(value))),
+    }
+  }
+
+  // turn assembly tokens into roots, an intermediate representation for optimization. roots correspond to valid instructions
+
+  let roots: Vec<(Origin, Root)> = resolved_tokens
+    .into_iter()
+    .map(|(pos, token)| (pos, token_to_root(token)))
+    .collect();
+
+  origins.extend(roots.iter().map(|(origin, _)| origin.clone()));
+  let roots = optimize(roots, disabled, rewrites, errors);
 
   // assemble roots into instructions by computing the value of every node and resolving labels
 
-  fn codegen_push_immediate(value: u8, pos: &Pos) -> Vec<(Pos, Instruction)> {
+  fn codegen_push_immediate(value: u8, pos: &Origin) -> Vec<(Origin, Instruction)> {
     // the `Psh` instruction allows us to push arbitrary 7-bit immediates onto the stack.
     // we then optionally use `Neg` and `Inc` to get the ability to push arbitrary 8-bit
     // values. we also use `Phn` as a shorthand when possible.
@@ -405,7 +867,9 @@ fn assemble(
   // out to require more than one byte, iteratively `'bruteforce` allocation sizes until we
   // find one that works. repeat for every node.
 
-  let mut instructions: Vec<(Pos, Result<Instruction, u8>)>;
+  let mut instructions: Vec<(Origin, Result<Instruction, u8>)>;
+  let mut label_definitions: HashMap<Label, u8>;
+  let mut padding_indices: Vec<usize>;
   let mut allocation_sizes: HashMap<Node, usize> = HashMap::new();
   let mut bruteforce_errors: Vec<(Pos, Error)> = vec![];
 
@@ -417,9 +881,15 @@ fn assemble(
 
   'bruteforce: loop {
     let mut location_counter: usize = 0;
-    let mut label_definitions: HashMap<Label, u8> = HashMap::new();
-    let mut unevaluated_nodes: BTreeMap<u8, (Pos, Node)> = BTreeMap::new();
-    let mut unevaluated_datas: BTreeMap<u8, (Pos, Node)> = BTreeMap::new();
+    let mut bank: usize = 0;
+    // `instructions` is laid out like the memory image, so indices into it differ from the location
+    // counter past the first `@bank`. sections are the base image and banks, by start index and size
+    let mut index: usize = 0;
+    let mut sections: Vec<(usize, usize, usize)> = vec![(0, 0, common::MEM_SIZE)];
+    label_definitions = HashMap::new();
+    padding_indices = vec![];
+    let mut unevaluated_nodes: BTreeMap<usize, (Origin, Node)> = BTreeMap::new();
+    let mut unevaluated_datas: BTreeMap<usize, (Origin, Node)> = BTreeMap::new();
 
     instructions = roots
       .iter()
@@ -459,11 +929,8 @@ fn assemble(
                 Ok(Instruction::Iff(Size::assert(0x01))),
               )]);
             }
-            unevaluated_nodes.insert(location_counter as u8, (pos.clone(), node1.clone()));
-            unevaluated_nodes.insert(
-              (location_counter + allocation_size!(&node1)) as u8,
-              (pos.clone(), node2),
-            );
+            unevaluated_nodes.insert(index, (pos.clone(), node1.clone()));
+            unevaluated_nodes.insert(index + allocation_size!(&node1), (pos.clone(), node2));
             instructions
           }
 
@@ -473,7 +940,7 @@ fn assemble(
 
               if label_definitions.contains_key(&label) {
                 bruteforce_errors.extend([(
-                  pos.clone(),
+                  pos.0.clone(),
                   Error(format!("Duplicate label definition `{}`", label)),
                 )]);
               }
@@ -488,14 +955,29 @@ fn assemble(
               .map(|(pos, instruction)| (pos, Ok(instruction)))
               .collect::<Vec<_>>(),
             Err(_) => {
-              unevaluated_nodes.insert(location_counter as u8, (pos.clone(), node.clone()));
+              unevaluated_nodes.insert(index, (pos.clone(), node.clone()));
               vec![(pos.clone(), Ok(Instruction::Nop)); allocation_size!(&node)]
             }
           },
 
+          Root::Operator(operator) => {
+            bruteforce_errors.extend([(
+              pos.0.clone(),
+              Error(format!(
+                "Operator `{}` could not be applied to constant operands",
+                match operator {
+                  Operator::Mul => Token::Mul,
+                  Operator::Div => Token::Div,
+                  Operator::Mod => Token::Mod,
+                }
+              )),
+            )]);
+            vec![]
+          }
+
           Root::Const => {
             bruteforce_errors.extend([(
-              pos.clone(),
+              pos.0.clone(),
               Error(format!(
                 "`{}` argument could not be reduced to a constant expression",
                 Token::AtConst,
@@ -505,13 +987,13 @@ fn assemble(
           }
 
           Root::Data(Some(node)) => {
-            unevaluated_datas.insert(location_counter as u8, (pos.clone(), node.clone()));
+            unevaluated_datas.insert(index, (pos.clone(), node.clone()));
             vec![(pos.clone(), Err(0x00))]
           }
 
           Root::Data(None) => {
             bruteforce_errors.extend([(
-              pos.clone(),
+              pos.0.clone(),
               Error(format!(
                 "`{}` argument could not be reduced to a constant expression",
                 Token::AtData,
@@ -522,7 +1004,7 @@ fn assemble(
 
           Root::Dyn(None) => {
             bruteforce_errors.extend([(
-              pos.clone(),
+              pos.0.clone(),
               Error(format!(
                 "`{}` argument could not be reduced to an instruction",
                 Token::AtDyn,
@@ -534,11 +1016,12 @@ fn assemble(
           Root::Org(Some(node)) => match resolve_node_value(&node, &label_definitions) {
             Ok(value) => match (value as usize).checked_sub(location_counter) {
               Some(padding) => {
+                padding_indices.extend(index..index + padding);
                 vec![(pos.clone(), Err(0x00)); padding]
               }
               None => {
                 bruteforce_errors.extend([(
-                  pos.clone(),
+                  pos.0.clone(),
                   Error(format!(
                     "`{}` cannot move location counter backward from {:02X} to {:02X}",
                     Token::AtOrg,
@@ -551,7 +1034,7 @@ fn assemble(
             },
             Err(label) => {
               bruteforce_errors.extend([(
-                pos.clone(),
+                pos.0.clone(),
                 Error(format!(
                   "`{}` argument references currently unresolved label `{}`",
                   Token::AtOrg,
@@ -564,7 +1047,7 @@ fn assemble(
 
           Root::Org(None) => {
             bruteforce_errors.extend([(
-              pos.clone(),
+              pos.0.clone(),
               Error(format!(
                 "`{}` argument could not be reduced to a constant expression",
                 Token::AtOrg,
@@ -572,18 +1055,114 @@ fn assemble(
             )]);
             vec![]
           }
+
+          Root::Bank(Some(node)) => match resolve_node_value(node, &label_definitions) {
+            Ok(value) if value as usize > bank => {
+              // pad the current section if it fits, then fill any skipped banks with zeros. the
+              // location counter is moved to the bank window rather than advanced past the padding
+              let (_, start, size) = sections[sections.len() - 1];
+              let mut instructions =
+                vec![(pos.clone(), Err(0x00)); (start + size).saturating_sub(index)];
+              for skipped in bank + 1..value as usize {
+                sections.push((skipped, index + instructions.len(), common::BANK_WINDOW_LEN));
+                instructions.extend(vec![(pos.clone(), Err(0x00)); common::BANK_WINDOW_LEN]);
+              }
+              padding_indices.extend(index..index + instructions.len());
+              sections.push((
+                value as usize,
+                index + instructions.len(),
+                common::BANK_WINDOW_LEN,
+              ));
+              bank = value as usize;
+              location_counter = common::BANK_WINDOW;
+              index += instructions.len();
+              return instructions;
+            }
+            Ok(value) => {
+              bruteforce_errors.extend([(
+                pos.0.clone(),
+                Error(format!(
+                  "`{}` cannot move backward from bank {:02X} to bank {:02X}",
+                  Token::AtBank,
+                  bank,
+                  value
+                )),
+              )]);
+              vec![]
+            }
+            Err(label) => {
+              bruteforce_errors.extend([(
+                pos.0.clone(),
+                Error(format!(
+                  "`{}` argument references currently unresolved label `{}`",
+                  Token::AtBank,
+                  label
+                )),
+              )]);
+              vec![]
+            }
+          },
+
+          Root::Bank(None) => {
+            bruteforce_errors.extend([(
+              pos.0.clone(),
+              Error(format!(
+                "`{}` argument could not be reduced to a constant expression",
+                Token::AtBank,
+              )),
+            )]);
+            vec![]
+          }
         };
         location_counter += instructions.len();
+        index += instructions.len();
         instructions
       })
       .collect();
 
+    // pad the last bank to its full size, then ensure no section exceeds its size. the base image
+    // is padded by `codegen` instead, so as not to mark its trailing bytes as data
+    let (bank, start, size) = sections[sections.len() - 1];
+    if let (1.., Some(padding)) = (bank, (start + size).checked_sub(instructions.len())) {
+      instructions.extend(vec![
+        (
+          Origin(Pos(File("[codegen]".into()), 0, 0), vec![]),
+          Err(0x00)
+        );
+        padding
+      ]);
+    }
+    for (section, &(bank, start, size)) in sections.iter().enumerate() {
+      let end = match sections.get(section + 1) {
+        Some(&(_, next_start, _)) => next_start,
+        None => instructions.len(),
+      };
+      if end - start > size {
+        bruteforce_errors.extend([(
+          instructions[start + size].0 .0.clone(),
+          Error(match bank {
+            0 => format!(
+              "Program size {:02X} exceeds available memory of size {:02X}",
+              end - start,
+              size
+            ),
+            _ => format!(
+              "Bank {:02X} size {:02X} exceeds bank window of size {:02X}",
+              bank,
+              end - start,
+              size
+            ),
+          }),
+        )]);
+      }
+    }
+
     // poke into `instructions` and evaluate `@data`s now that all labels have been resolved
-    for (location_counter, (pos, node)) in unevaluated_datas.into_iter() {
+    for (index, (pos, node)) in unevaluated_datas.into_iter() {
       match resolve_node_value(&node, &label_definitions) {
-        Ok(value) => instructions[location_counter as usize] = (pos, Err(value)),
+        Ok(value) => instructions[index] = (pos, Err(value)),
         Err(label) => bruteforce_errors.extend([(
-          pos,
+          pos.0,
           Error(format!("Reference to undefined label `{}`", label)),
         )]),
       };
@@ -591,7 +1170,7 @@ fn assemble(
 
     // poke into `instructions` and evaluate the nodes that couldn't be evaluated before
     'poke: {
-      for (location_counter, (pos, node)) in unevaluated_nodes.into_iter() {
+      for (index, (pos, node)) in unevaluated_nodes.into_iter() {
         match resolve_node_value(&node, &label_definitions) {
           Ok(value) => {
             // if the evaluated node doesn't fit in the allocated memory, note down the right amount of
@@ -603,12 +1182,12 @@ fn assemble(
               break 'poke;
             }
 
-            for (index, (pos, instruction)) in push_instructions.into_iter().enumerate() {
-              instructions[location_counter as usize + index] = (pos, Ok(instruction));
+            for (offset, (pos, instruction)) in push_instructions.into_iter().enumerate() {
+              instructions[index + offset] = (pos, Ok(instruction));
             }
           }
           Err(label) => bruteforce_errors.extend([(
-            pos,
+            pos.0,
             Error(format!("Reference to undefined label `{}`", label)),
           )]),
         };
@@ -625,76 +1204,313 @@ fn assemble(
   }
 
   errors.extend(bruteforce_errors);
+  labels.extend(label_definitions);
+  padding.extend(padding_indices);
 
   instructions
 }
 
 fn codegen(
-  instructions: Vec<(Pos, Result<Instruction, u8>)>,
-  errors: &mut impl Extend<(Pos, Error)>,
-) -> Vec<(Pos, u8)> {
-  // codegen instructions into opcodes
+  instructions: Vec<(Origin, Result<Instruction, u8>)>,
+  _errors: &mut impl Extend<(Pos, Error)>,
+) -> Vec<(Origin, u8)> {
+  // codegen instructions into opcodes. programs exceeding the available memory have already been
+  // reported by `assemble`
 
-  let opcodes: Vec<(Pos, u8)> = instructions
+  let opcodes: Vec<(Origin, u8)> = instructions
     .into_iter()
     .map(|(pos, instruction)| (pos, common::instruction_to_opcode(instruction)))
     .collect();
 
   let mut opcodes = opcodes;
 
-  match common::MEM_SIZE.checked_sub(opcodes.len()) {
-    Some(padding) => opcodes.extend(vec![(Pos(File("[codegen]".into()), 0, 0), 0x00); padding]),
-    None => {
-      errors.extend([(
-        opcodes[common::MEM_SIZE].0.clone(),
-        Error(format!(
-          "Program size {:02X} exceeds available memory of size {:02X}",
-          opcodes.len(),
-          common::MEM_SIZE
-        )),
-      )]);
-    }
+  if let Some(padding) = common::MEM_SIZE.checked_sub(opcodes.len()) {
+    opcodes.extend(vec![
+      (
+        Origin(Pos(File("[codegen]".into()), 0, 0), vec![]),
+        0x00
+      );
+      padding
+    ]);
   }
 
   opcodes
 }
 
-fn optimize(roots: Vec<(Pos, Root)>, _errors: &mut impl Extend<(Pos, Error)>) -> Vec<(Pos, Root)> {
+fn listing(
+  memory_image: &[(Origin, u8)],
+  origins: Vec<Origin>,
+  rewrites: Vec<Rewrite>,
+  padding: &BTreeSet<usize>,
+) -> String {
+  // every line of the listing holds the bytes emitted for one source line within one macro
+  // expansion, followed by the rewrites the optimizer made to the tokens on that line. roots are
+  // grouped into lines in program order, then bytes are matched to the nearest upcoming line they
+  // originate from. bytes that cannot be matched, such as those the optimizer moved backward, get
+  // a line of their own
+
+  let mut lines: Vec<(Vec<Origin>, Vec<usize>)> = vec![];
+  for origin in origins {
+    match lines.last_mut() {
+      Some((line_origins, _))
+        if line_origins[0].0 .0 == origin.0 .0
+          && line_origins[0].0 .1 == origin.0 .1
+          && line_origins[0].1 == origin.1 =>
+      {
+        line_origins.push(origin)
+      }
+      _ => lines.push((vec![origin], vec![])),
+    }
+  }
+
+  let mut strays: Vec<(Vec<Origin>, Vec<usize>)> = vec![];
+  let mut current = 0;
+  for (index, (origin, _)) in memory_image.iter().enumerate() {
+    match lines[current..]
+      .iter()
+      .position(|(line_origins, _)| line_origins.contains(origin))
+    {
+      Some(offset) => {
+        current += offset;
+        lines[current].1.push(index);
+      }
+      None => match strays.last_mut() {
+        Some((stray_origins, indices))
+          if stray_origins[0] == *origin && indices.last() == Some(&(index - 1)) =>
+        {
+          indices.push(index)
+        }
+        _ => strays.push((vec![origin.clone()], vec![index])),
+      },
+    }
+  }
+
+  let mut strays = strays.into_iter().peekable();
+  let mut merged: Vec<(Vec<Origin>, Vec<usize>)> = vec![];
+  for line in lines {
+    if let Some(&first) = line.1.first() {
+      while let Some(stray) = strays.next_if(|(_, indices)| indices[0] < first) {
+        merged.push(stray);
+      }
+    }
+    merged.push(line);
+  }
+  merged.extend(strays);
+
+  let mut rewritten: HashMap<Origin, Vec<String>> = HashMap::new();
+  for (origin, family, before, after) in rewrites {
+    let Some(rewrite) = describe_rewrite(&before, &after) else {
+      continue;
+    };
+    let rewrite = format!("[{}] {}", family, rewrite);
+    let rewrites = rewritten.entry(origin).or_default();
+    if !rewrites.contains(&rewrite) {
+      rewrites.push(rewrite);
+    }
+  }
+
+  let address = |index: usize| match index.checked_sub(common::MEM_SIZE) {
+    None => format!("{:02X}", index),
+    Some(offset) => format!(
+      "{:X}:{:02X}",
+      offset / common::BANK_WINDOW_LEN + 1,
+      common::BANK_WINDOW + offset % common::BANK_WINDOW_LEN
+    ),
+  };
+
+  let mut sources: HashMap<std::path::PathBuf, Option<Vec<String>>> = HashMap::new();
+  let mut listing = vec![];
+  let mut location = 0; // index of the byte following the last one listed
+  for (line_origins, indices) in merged {
+    let Origin(pos @ Pos(File(path), row, _), macros) = &line_origins[0];
+    let source = sources
+      .entry(path.clone())
+      .or_insert_with_key(|path| {
+        std::fs::read_to_string(path)
+          .ok()
+          .map(|source| source.lines().map(str::to_string).collect())
+      })
+      .as_ref()
+      .and_then(|lines| lines.get(*row))
+      .map(|line| line.trim())
+      .unwrap_or("");
+    let origin = Origin(pos.clone(), macros.clone()).to_string();
+
+    // padding is collapsed into a single line, other bytes are listed four per line
+    let is_padding = |index: &usize| padding.contains(index) || path.starts_with("[codegen]");
+    let mut chunks: Vec<(usize, String)> = vec![];
+    for chunk in indices
+      .chunk_by(|index1, index2| index1 + 1 == *index2 && is_padding(index1) == is_padding(index2))
+    {
+      match is_padding(&chunk[0]) {
+        true => chunks.push((chunk[0], format!("({:02X} bytes)", chunk.len()))),
+        false => chunks.extend(chunk.chunks(4).map(|bytes| {
+          (
+            bytes[0],
+            bytes
+              .iter()
+              .map(|index| format!("{:02X}", memory_image[*index].1))
+              .collect::<Vec<String>>()
+              .join(" "),
+          )
+        })),
+      }
+    }
+
+    match &chunks[..] {
+      [] => listing.push(format!(
+        "{:<5} {:<11}  {:<40} {}",
+        address(location),
+        "",
+        source,
+        origin
+      )),
+      [(index, bytes), rest @ ..] => {
+        listing.push(format!(
+          "{:<5} {:<11}  {:<40} {}",
+          address(*index),
+          bytes,
+          source,
+          origin
+        ));
+        listing.extend(
+          rest
+            .iter()
+            .map(|(index, bytes)| format!("{:<5} {}", address(*index), bytes)),
+        );
+      }
+    }
+
+    location = indices.last().map_or(location, |index| index + 1);
+
+    let mut listed: Vec<&String> = vec![];
+    for rewrite in line_origins
+      .iter()
+      .flat_map(|origin| rewritten.get(origin))
+      .flatten()
+    {
+      if !listed.contains(&rewrite) {
+        listing.push(format!("{:<5} {:<11}  # {}", "", "", rewrite));
+        listed.push(rewrite);
+      }
+    }
+  }
+
+  listing.join("\n") + "\n"
+}
+
+fn report(rewrites: Vec<Rewrite>, disabled: &BTreeSet<Family>) -> String {
+  // a summary of how many rewrites every family made, followed by every rewrite in the order
+  // the optimizer made them. rewrites that leave the rendered roots unchanged, such as reordering
+  // nested nodes, are omitted
+
+  let rewrites: Vec<(Origin, Family, String)> = rewrites
+    .into_iter()
+    .filter_map(|(origin, family, before, after)| {
+      Some((origin, family, describe_rewrite(&before, &after)?))
+    })
+    .collect();
+
+  let summary = Family::ALL
+    .iter()
+    .map(|family| match disabled.contains(family) {
+      true => format!("# {:<12} disabled", family),
+      false => format!(
+        "# {:<12} {}",
+        family,
+        rewrites.iter().filter(|(_, f, _)| f == family).count()
+      ),
+    });
+
+  let mut report: Vec<String> = vec![format!("# {:<12} {}", "family", "rewrites")];
+  report.extend(summary);
+  report.push("".to_string());
+  report.extend(
+    rewrites
+      .iter()
+      .map(|(origin, family, rewrite)| format!("{:<12} {:<40} {}", family, origin, rewrite)),
+  );
+
+  report.join("\n") + "\n"
+}
+
+fn describe_rewrite(before: &[Root], after: &[Root]) -> Option<String> {
+  let render = |roots: &[Root]| {
+    roots
+      .iter()
+      .map(|root| root.to_string())
+      .collect::<Vec<String>>()
+      .join(" ")
+  };
+  let (before, after) = (render(before), render(after));
+  match after.as_str() {
+    _ if before == after => None,
+    "" => Some(format!("removed `{}`", before)),
+    _ => Some(format!("rewrote `{}` into `{}`", before, after)),
+  }
+}
+
+fn optimize(
+  roots: Vec<(Origin, Root)>,
+  disabled: &BTreeSet<Family>,
+  rewrites: &mut impl Extend<Rewrite>,
+  _errors: &mut impl Extend<(Pos, Error)>,
+) -> Vec<(Origin, Root)> {
   // build a tree of nodes representing everything we can compute at compile time
   // this removes redundant instructions and makes macros usable
 
-  // a convenience function to replace slice patterns within a vector
+  // a convenience function to replace slice patterns within a vector. replacers are tried in order
+  // and belong to a family of rewrites, or to none if they bind directives rather than optimize.
+  // rewrites from disabled families are skipped unless they apply an `Operator`, which has no
+  // instruction equivalent, and are never recorded. other rewrites that change the window are
+  // recorded into `rewrites` along with the origin of the window
+  type Replacer<'a, const N: usize> = (
+    Option<Family>,
+    &'a mut dyn FnMut(&[Root; N]) -> Option<Vec<Root>>,
+  );
   fn match_replace<const N: usize>(
-    roots: &Vec<(Pos, Root)>,
-    mut replacer: impl FnMut(&[Root; N]) -> Option<Vec<Root>>,
-  ) -> Vec<(Pos, Root)> {
+    roots: &Vec<(Origin, Root)>,
+    rewrites: &mut Vec<Rewrite>,
+    disabled: &BTreeSet<Family>,
+    replacers: &mut [Replacer<N>],
+  ) -> Vec<(Origin, Root)> {
     if roots.len() < N {
       return roots.clone();
     }
 
-    let mut output: Vec<(Pos, Root)> = vec![];
+    let mut output: Vec<(Origin, Root)> = vec![];
 
     let mut skip_next_n_roots = 0;
     for window in roots.windows(N) {
       if skip_next_n_roots > 0 {
         skip_next_n_roots -= 1;
       } else {
-        match replacer(
-          window
-            .iter()
-            .cloned()
-            .map(|(_, root)| root)
-            .collect::<Vec<Root>>()
-            .as_slice()
-            .try_into()
-            .unwrap(),
-        ) {
-          Some(roots) => {
+        let window_roots: Vec<Root> = window.iter().cloned().map(|(_, root)| root).collect();
+        let replacement = replacers.iter_mut().find_map(|(family, replacer)| {
+          let roots = replacer(window_roots.as_slice().try_into().unwrap())?;
+          match family {
+            Some(family)
+              if disabled.contains(family)
+                && !window_roots
+                  .iter()
+                  .any(|root| matches!(root, Root::Operator(_))) =>
+            {
+              None
+            }
+            _ => Some((*family, roots)),
+          }
+        });
+        match replacement {
+          Some((family, roots)) => {
+            let family = family.filter(|family| !disabled.contains(family));
+            if let (Some(family), true) = (family, roots != window_roots) {
+              rewrites.push((window[0].0.clone(), family, window_roots, roots.clone()));
+            }
             output.extend(
               roots
                 .into_iter()
                 .map(|root| (window[0].0.clone(), root))
-                .collect::<Vec<(Pos, Root)>>(),
+                .collect::<Vec<(Origin, Root)>>(),
             );
             skip_next_n_roots = N - 1;
           }
@@ -774,14 +1590,98 @@ fn optimize(roots: Vec<(Pos, Root)>, _errors: &mut impl Extend<(Pos, Error)>) ->
       Root::Conditional(_, _) => OpType::PushOp,
       Root::LabelDefs(_) => OpType::Impure,
       Root::Node(_) => OpType::PushOp,
+      Root::Operator(_) => OpType::BinaryOp,
       Root::Const => OpType::Impure,
       Root::Data(_) => OpType::Impure,
       Root::Dyn(_) => OpType::Impure,
       Root::Org(_) => OpType::Impure,
+      Root::Bank(_) => OpType::Impure,
     }
   }
 
-  let mut roots = roots;
+  // directive arguments are folded into nodes up front with every family enabled, as arguments to
+  // `@if` and `@equ` are, so that disabling a family never leaves a directive without a constant.
+  // only the rewrites this makes on behalf of enabled families are recorded. node folding alone is
+  // not enough, as arguments such as `swp ld6 ld2 ld2 xo4` in `stopwatch.asm` only reduce to a
+  // node through `ldos`, `swps` and `op-types` rewrites, so candidate arguments go through the
+  // full optimizer. this only runs when a family is disabled, and an argument that already is a
+  // node is taken as is. otherwise, suffixes of the straight-line run before the directive are
+  // tried shortest first, which is quadratic in the length of that run in the worst case
+  fn fold_directive_arguments(
+    roots: Vec<(Origin, Root)>,
+    disabled: &BTreeSet<Family>,
+    rewrites: &mut impl Extend<Rewrite>,
+  ) -> Vec<(Origin, Root)> {
+    let mut output: Vec<(Origin, Root)> = vec![];
+
+    for (origin, root) in roots.into_iter() {
+      if let Root::Const | Root::Data(None) | Root::Org(None) | Root::Bank(None) = root {
+        // label definitions may sit between a directive and its argument, as in `!pad`
+        let label_defs_len = output
+          .iter()
+          .rev()
+          .take_while(|(_, root)| matches!(root, Root::LabelDefs(_)))
+          .count();
+        let end = output.len() - label_defs_len;
+        let max_len = output[..end]
+          .iter()
+          .rev()
+          .take_while(|(_, root)| {
+            matches!(
+              root,
+              Root::Node(_) | Root::Instruction(_) | Root::Operator(_)
+            )
+          })
+          .count();
+        // the shortest sequence of roots that reduces to roots ending in a node, as arguments
+        // such as `ld6 ld2 ld2 xo4` leave other roots below the node they reduce to
+        if let Some((_, Root::Node(_))) = output[..end].last() {
+          if root != Root::Const {
+            output.push((origin, root));
+          }
+          continue;
+        }
+        let argument = (1..=max_len).find_map(|len| {
+          let mut folded: Vec<Rewrite> = vec![];
+          let roots = optimize(
+            output[end - len..end].to_vec(),
+            &BTreeSet::new(),
+            &mut folded,
+            &mut Vec::<(Pos, Error)>::new(),
+          );
+          match roots.last() {
+            Some((_, Root::Node(_))) => Some((len, roots, folded)),
+            _ => None,
+          }
+        });
+
+        if let Some((len, roots, folded)) = argument {
+          rewrites.extend(
+            folded
+              .into_iter()
+              .filter(|(_, family, _, _)| !disabled.contains(family)),
+          );
+          let label_defs = output.split_off(end);
+          output.truncate(end - len);
+          output.extend(roots);
+          output.extend(label_defs);
+          // bind `@const` right away so its node can be part of a later directive argument
+          if root == Root::Const {
+            continue;
+          }
+        }
+      }
+      output.push((origin, root));
+    }
+
+    output
+  }
+
+  let mut roots = match disabled.is_empty() {
+    true => roots,
+    false => fold_directive_arguments(roots, disabled, rewrites),
+  };
+  let mut rewritten = vec![];
 
   // optimize as much as possible into `Node`s for assembly-time evaluation
 
@@ -790,38 +1690,51 @@ fn optimize(roots: Vec<(Pos, Root)>, _errors: &mut impl Extend<(Pos, Error)>) ->
     last_roots = roots.clone();
     // println!("roots: {:?}\nlen: {}", roots, roots.len());
 
-    // higher priority for directives
-    roots = match_replace(&roots, |window| match window {
-      [node @ Root::Node(_), Root::Const] => Some(vec![node.clone()]),
-      [Root::Instruction(instruction), Root::Dyn(None)] => {
-        Some(vec![Root::Dyn(Some(instruction.clone()))])
-      }
-      [r#dyn @ Root::Dyn(Some(_)), Root::Dyn(None)] => Some(vec![r#dyn.clone()]),
-      [Root::Node(Node::Value(value)), Root::Dyn(None)] => {
-        match common::opcode_to_instruction(*value) {
-          Ok(instruction @ Instruction::Psh(_)) => Some(vec![Root::Dyn(Some(instruction))]),
-          Ok(instruction @ Instruction::Phn(_)) => Some(vec![Root::Dyn(Some(instruction))]),
-          _ => None,
+    // higher priority for directives. binding directives to their arguments is not a rewrite
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(None, &mut |window| match window {
+        [node @ Root::Node(_), Root::Const] => Some(vec![node.clone()]),
+        [Root::Instruction(instruction), Root::Dyn(None)] => {
+          Some(vec![Root::Dyn(Some(instruction.clone()))])
+        }
+        [r#dyn @ Root::Dyn(Some(_)), Root::Dyn(None)] => Some(vec![r#dyn.clone()]),
+        [Root::Node(Node::Value(value)), Root::Dyn(None)] => {
+          match common::opcode_to_instruction(*value) {
+            Ok(instruction @ Instruction::Psh(_)) => Some(vec![Root::Dyn(Some(instruction))]),
+            Ok(instruction @ Instruction::Phn(_)) => Some(vec![Root::Dyn(Some(instruction))]),
+            _ => None,
+          }
         }
-      }
-      [Root::Node(node), Root::Data(None)] => Some(vec![Root::Data(Some(node.clone()))]),
-      [Root::Node(node), Root::Org(None)] => Some(vec![Root::Org(Some(node.clone()))]),
-      _ => None,
-    });
+        [Root::Node(node), Root::Data(None)] => Some(vec![Root::Data(Some(node.clone()))]),
+        [Root::Node(node), Root::Org(None)] => Some(vec![Root::Org(Some(node.clone()))]),
+        [Root::Node(node), Root::Bank(None)] => Some(vec![Root::Bank(Some(node.clone()))]),
+
+        _ => None,
+      })],
+    );
 
     // for `!pad` macro
-    roots = match_replace(&roots, |window| match window {
-      [node @ Root::Node(_), label_defs @ Root::LabelDefs(_), r#const @ Root::Const] => {
-        Some(vec![node.clone(), r#const.clone(), label_defs.clone()])
-      }
-      [node @ Root::Node(_), label_defs @ Root::LabelDefs(_), data @ Root::Data(None)] => {
-        Some(vec![node.clone(), data.clone(), label_defs.clone()])
-      }
-      [node @ Root::Node(_), label_defs @ Root::LabelDefs(_), org @ Root::Org(None)] => {
-        Some(vec![label_defs.clone(), node.clone(), org.clone()])
-      }
-      _ => None,
-    });
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(None, &mut |window| match window {
+        [node @ Root::Node(_), label_defs @ Root::LabelDefs(_), r#const @ Root::Const] => {
+          Some(vec![node.clone(), r#const.clone(), label_defs.clone()])
+        }
+        [node @ Root::Node(_), label_defs @ Root::LabelDefs(_), data @ Root::Data(None)] => {
+          Some(vec![node.clone(), data.clone(), label_defs.clone()])
+        }
+        [node @ Root::Node(_), label_defs @ Root::LabelDefs(_), org @ Root::Org(None)] => {
+          Some(vec![label_defs.clone(), node.clone(), org.clone()])
+        }
+
+        _ => None,
+      })],
+    );
 
     // for patterns such as `:label1 !bcs :label2 !jmp`
     let labels = roots.iter().flat_map(|(_, root)| match root {
@@ -830,919 +1743,1028 @@ fn optimize(roots: Vec<(Pos, Root)>, _errors: &mut impl Extend<(Pos, Error)>) ->
     });
     let mut label_aliases: BTreeMap<Label, BTreeSet<Label>> =
       labels.map(|label| (label, BTreeSet::new())).collect();
-    roots = match_replace(&roots, |window| match window {
-      [Root::LabelDefs(diff_labels), Root::Node(Node::LabelRef(diff_label)), Root::Instruction(Instruction::Sti)]
-        if !diff_labels.contains(&diff_label) =>
-      {
-        label_aliases
-          .entry(diff_label.clone())
-          .or_default()
-          .extend(diff_labels.clone());
-        Some(vec![])
-      }
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(Some(Family::Jumps), &mut |window| match window {
+        [Root::LabelDefs(diff_labels), Root::Node(Node::LabelRef(diff_label)), Root::Instruction(Instruction::Sti)]
+          if !diff_labels.contains(&diff_label) =>
+        {
+          label_aliases
+            .entry(diff_label.clone())
+            .or_default()
+            .extend(diff_labels.clone());
+          Some(vec![])
+        }
 
-      _ => None,
-    });
+        _ => None,
+      })],
+    );
     // if A has alias B and B has alias C then ensure A has alias C,
     // for all A, B, C. ensure A has alias A, for all A.
     common::reflexive_transitive_closure(&mut label_aliases);
-    roots = match_replace(&roots, |window| match window {
-      [Root::LabelDefs(labels)] => Some(vec![Root::LabelDefs(
-        labels
-          .iter()
-          .flat_map(|label| label_aliases.get(&label).cloned().unwrap_or_default())
-          .collect(),
-      )]),
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(Some(Family::Jumps), &mut |window| match window {
+        [Root::LabelDefs(labels)] => Some(vec![Root::LabelDefs(
+          labels
+            .iter()
+            .flat_map(|label| label_aliases.get(&label).cloned().unwrap_or_default())
+            .collect(),
+        )]),
 
-      _ => None,
-    });
+        _ => None,
+      })],
+    );
 
     // length 1
-    roots = match_replace(&roots, |window| match window {
-      // `OpType`s
-      [no_op] if op_type(no_op) == OpType::NoOp => Some(vec![]),
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(Some(Family::OpTypes), &mut |window| match window {
+        // `OpType`s
+        [no_op] if op_type(no_op) == OpType::NoOp => Some(vec![]),
 
-      _ => None,
-    });
+        _ => None,
+      })],
+    );
 
     // length 2
-    roots = match_replace(&roots, |window| match window {
-      // `Node`s
-      [Root::Node(x00), Root::Instruction(Instruction::Add(_size))]
-        if resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
-      {
-        Some(vec![])
-      }
-      [Root::Node(x01), Root::Instruction(Instruction::Add(ad1))]
-        if resolve_node_value(&x01, &HashMap::new()) == Ok(0x01) && ad1.get() == 0x01 =>
-      {
-        Some(vec![Root::Instruction(Instruction::Inc)])
-      }
-      [Root::Node(x00), Root::Instruction(Instruction::Sub(_size))]
-        if resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
-      {
-        Some(vec![])
-      }
-      [Root::Node(x01), Root::Instruction(Instruction::Sub(su1))]
-        if resolve_node_value(&x01, &HashMap::new()) == Ok(0x01) && su1.get() == 0x01 =>
-      {
-        Some(vec![Root::Instruction(Instruction::Dec)])
-      }
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [
+        (Some(Family::Nodes), &mut |window| match window {
+          // `Node`s
+          [Root::Node(x00), Root::Instruction(Instruction::Add(_size))]
+            if resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
+          {
+            Some(vec![])
+          }
+          [Root::Node(x01), Root::Instruction(Instruction::Add(ad1))]
+            if resolve_node_value(&x01, &HashMap::new()) == Ok(0x01) && ad1.get() == 0x01 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Inc)])
+          }
+          [Root::Node(x00), Root::Instruction(Instruction::Sub(_size))]
+            if resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
+          {
+            Some(vec![])
+          }
+          [Root::Node(x01), Root::Instruction(Instruction::Sub(su1))]
+            if resolve_node_value(&x01, &HashMap::new()) == Ok(0x01) && su1.get() == 0x01 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Dec)])
+          }
 
-      [Root::Node(div_by_eight), Root::Instruction(Instruction::Rot(_size))]
-        if resolve_node_value(&div_by_eight, &HashMap::new()).map(|value| value % 8)
-          == Ok(0x00) =>
-      {
-        Some(vec![])
-      }
-      [Root::Node(x00), Root::Instruction(Instruction::Orr(_size))]
-        if resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
-      {
-        Some(vec![])
-      }
-      [Root::Node(xff), Root::Instruction(Instruction::And(_size))]
-        if resolve_node_value(&xff, &HashMap::new()) == Ok(0xFF) =>
-      {
-        Some(vec![])
-      }
-      [Root::Node(x00), Root::Instruction(Instruction::Xor(_size))]
-        if resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
-      {
-        Some(vec![])
-      }
-      [Root::Node(node), Root::Instruction(Instruction::Inc)] => Some(vec![Root::Node(Node::Add(
-        Box::new(Node::Value(0x01)),
-        Box::new(node.clone()),
-      ))]),
-
-      [Root::Node(node), Root::Instruction(Instruction::Dec)] => Some(vec![Root::Node(Node::Sub(
-        Box::new(Node::Value(0x01)),
-        Box::new(node.clone()),
-      ))]),
-
-      [Root::Node(node), Root::Instruction(Instruction::Neg)] => Some(vec![Root::Node(Node::Sub(
-        Box::new(node.clone()),
-        Box::new(Node::Value(0x00)),
-      ))]),
-      [Root::Instruction(Instruction::Neg), Root::Instruction(Instruction::Neg)] => Some(vec![]),
-      [Root::Node(node), Root::Instruction(Instruction::Shl)] => {
-        Some(vec![Root::Node(Node::Shl(Box::new(node.clone())))])
-      }
-      [Root::Node(node), Root::Instruction(Instruction::Shr)] => {
-        Some(vec![Root::Node(Node::Shr(Box::new(node.clone())))])
-      }
-      [Root::Node(node), Root::Instruction(Instruction::Not)] => {
-        Some(vec![Root::Node(Node::Not(Box::new(node.clone())))])
-      }
-      [Root::Instruction(Instruction::Not), Root::Instruction(Instruction::Not)] => {
-        Some(vec![Root::Instruction(Instruction::Buf)])
-      }
+          [Root::Node(div_by_eight), Root::Instruction(Instruction::Rot(_size))]
+            if resolve_node_value(&div_by_eight, &HashMap::new()).map(|value| value % 8)
+              == Ok(0x00) =>
+          {
+            Some(vec![])
+          }
+          [Root::Node(x00), Root::Instruction(Instruction::Orr(_size))]
+            if resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
+          {
+            Some(vec![])
+          }
+          [Root::Node(xff), Root::Instruction(Instruction::And(_size))]
+            if resolve_node_value(&xff, &HashMap::new()) == Ok(0xFF) =>
+          {
+            Some(vec![])
+          }
+          [Root::Node(x00), Root::Instruction(Instruction::Xor(_size))]
+            if resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
+          {
+            Some(vec![])
+          }
+          [Root::Node(node), Root::Instruction(Instruction::Inc)] => Some(vec![Root::Node(
+            Node::Add(Box::new(Node::Value(0x01)), Box::new(node.clone())),
+          )]),
 
-      // `Ldo`s
-      [node @ Root::Node(_), Root::Instruction(Instruction::Ldo(ld0))] if ld0.get() == 0x00 => {
-        Some(vec![node.clone(), node.clone()])
-      }
-      [Root::Instruction(Instruction::Ldo(same_ofst1)), Root::Instruction(Instruction::Sto(same_ofst2))]
-        if same_ofst1 == same_ofst2 =>
-      {
-        Some(vec![])
-      }
+          [Root::Node(node), Root::Instruction(Instruction::Dec)] => Some(vec![Root::Node(
+            Node::Sub(Box::new(Node::Value(0x01)), Box::new(node.clone())),
+          )]),
 
-      // idempotent and involutive `UnaryOp`s
-      [Root::Instruction(Instruction::Swp(same_size1)), Root::Instruction(Instruction::Swp(same_size2))]
-        if same_size1 == same_size2 =>
-      {
-        Some(vec![])
-      }
-      [clc @ Root::Instruction(Instruction::Clc), Root::Instruction(Instruction::Clc)] => {
-        Some(vec![clc.clone()])
-      }
-      [sec @ Root::Instruction(Instruction::Sec), Root::Instruction(Instruction::Sec)] => {
-        Some(vec![sec.clone()])
-      }
-      [Root::Instruction(Instruction::Flc), Root::Instruction(Instruction::Flc)] => Some(vec![]),
+          [Root::Node(node), Root::Instruction(Instruction::Neg)] => Some(vec![Root::Node(
+            Node::Sub(Box::new(node.clone()), Box::new(Node::Value(0x00))),
+          )]),
+          [Root::Instruction(Instruction::Neg), Root::Instruction(Instruction::Neg)] => {
+            Some(vec![])
+          }
+          [Root::Node(node), Root::Instruction(Instruction::Shl)] => {
+            Some(vec![Root::Node(Node::Shl(Box::new(node.clone())))])
+          }
+          [Root::Node(node), Root::Instruction(Instruction::Shr)] => {
+            Some(vec![Root::Node(Node::Shr(Box::new(node.clone())))])
+          }
+          [Root::Node(node), Root::Instruction(Instruction::Not)] => {
+            Some(vec![Root::Node(Node::Not(Box::new(node.clone())))])
+          }
+          [Root::Instruction(Instruction::Not), Root::Instruction(Instruction::Not)] => {
+            Some(vec![Root::Instruction(Instruction::Buf)])
+          }
 
-      //  `Label`s
-      [Root::LabelDefs(labels1), Root::LabelDefs(labels2)] => Some(vec![Root::LabelDefs(
-        labels1.iter().chain(labels2.iter()).cloned().collect(),
-      )]),
+          _ => None,
+        }),
+        (Some(Family::Ldos), &mut |window| match window {
+          // `Ldo`s
+          [node @ Root::Node(_), Root::Instruction(Instruction::Ldo(ld0))] if ld0.get() == 0x00 => {
+            Some(vec![node.clone(), node.clone()])
+          }
+          [Root::Instruction(Instruction::Ldo(same_ofst1)), Root::Instruction(Instruction::Sto(same_ofst2))]
+            if same_ofst1 == same_ofst2 =>
+          {
+            Some(vec![])
+          }
 
-      // `OpType`s
-      [push_op, pop_op]
-        if op_type(push_op) == OpType::PushOp && op_type(pop_op) == OpType::PopOp =>
-      {
-        Some(vec![])
-      }
-      [unary_op, pop_op]
-        if op_type(unary_op) == OpType::UnaryOp && op_type(pop_op) == OpType::PopOp =>
-      {
-        Some(vec![pop_op.clone()])
-      }
+          _ => None,
+        }),
+        (Some(Family::UnaryOps), &mut |window| match window {
+          // idempotent and involutive `UnaryOp`s
+          [Root::Instruction(Instruction::Swp(same_size1)), Root::Instruction(Instruction::Swp(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![])
+          }
+          [clc @ Root::Instruction(Instruction::Clc), Root::Instruction(Instruction::Clc)] => {
+            Some(vec![clc.clone()])
+          }
+          [sec @ Root::Instruction(Instruction::Sec), Root::Instruction(Instruction::Sec)] => {
+            Some(vec![sec.clone()])
+          }
+          [Root::Instruction(Instruction::Flc), Root::Instruction(Instruction::Flc)] => {
+            Some(vec![])
+          }
 
-      _ => None,
-    });
+          _ => None,
+        }),
+        (Some(Family::Labels), &mut |window| match window {
+          //  `Label`s
+          [Root::LabelDefs(labels1), Root::LabelDefs(labels2)] => Some(vec![Root::LabelDefs(
+            labels1.iter().chain(labels2.iter()).cloned().collect(),
+          )]),
 
-    // length 3
-    roots = match_replace(&roots, |window| {
-      match window {
-        // `Conditional`s
-        [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Iff(if1))]
-          if if1.get() == 0x01 =>
-        {
-          Some(vec![Root::Conditional(node1.clone(), node2.clone())])
-        }
+          _ => None,
+        }),
+        (Some(Family::OpTypes), &mut |window| match window {
+          // `OpType`s
+          [push_op, pop_op]
+            if op_type(push_op) == OpType::PushOp && op_type(pop_op) == OpType::PopOp =>
+          {
+            Some(vec![])
+          }
+          [unary_op, pop_op]
+            if op_type(unary_op) == OpType::UnaryOp && op_type(pop_op) == OpType::PopOp =>
+          {
+            Some(vec![pop_op.clone()])
+          }
 
-        // `Node`s
-        [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Add(ad1))]
-          if ad1.get() == 0x01 =>
-        {
-          Some(vec![Root::Node(Node::Add(
-            Box::new(node2.clone()),
-            Box::new(node1.clone()),
-          ))])
-        }
-        [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Sub(su1))]
-          if su1.get() == 0x01 =>
-        {
-          Some(vec![Root::Node(Node::Sub(
-            Box::new(node2.clone()),
-            Box::new(node1.clone()),
-          ))])
-        }
-        [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Rot(ro1))]
-          if ro1.get() == 0x01 =>
-        {
-          Some(vec![Root::Node(Node::Rot(
-            Box::new(node2.clone()),
-            Box::new(node1.clone()),
-          ))])
-        }
-        [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Orr(or1))]
-          if or1.get() == 0x01 =>
-        {
-          Some(vec![Root::Node(Node::Orr(
-            Box::new(node2.clone()),
-            Box::new(node1.clone()),
-          ))])
-        }
-        [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::And(an1))]
-          if an1.get() == 0x01 =>
-        {
-          Some(vec![Root::Node(Node::And(
-            Box::new(node2.clone()),
-            Box::new(node1.clone()),
-          ))])
-        }
-        [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Xor(xo1))]
-          if xo1.get() == 0x01 =>
-        {
-          Some(vec![Root::Node(Node::Xor(
-            Box::new(node2.clone()),
-            Box::new(node1.clone()),
-          ))])
-        }
-        [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Xnd(xn1))]
-          if xn1.get() == 0x01 =>
-        {
-          Some(vec![Root::Node(Node::Xnd(
-            Box::new(node2.clone()),
-            Box::new(node1.clone()),
-          ))])
-        }
+          _ => None,
+        }),
+      ],
+    );
 
-        // `Swp`s
-        [Root::Instruction(Instruction::Swp(sw1)), Root::Instruction(Instruction::Inc), Root::Instruction(Instruction::Swp(sw1_))]
-          if sw1.get() == 0x01 && sw1_.get() == 0x01 =>
-        {
-          Some(vec![
-            Root::Node(Node::Value(0x01)),
-            Root::Instruction(Instruction::Add(Size::assert(0x02))),
-          ])
-        }
-        [Root::Instruction(Instruction::Swp(sw1)), Root::Instruction(Instruction::Dec), Root::Instruction(Instruction::Swp(sw1_))]
-          if sw1.get() == 0x01 && sw1_.get() == 0x01 =>
-        {
-          Some(vec![
-            Root::Node(Node::Value(0x01)),
-            Root::Instruction(Instruction::Sub(Size::assert(0x02))),
-          ])
-        }
-        [node1 @ Root::Node(_), node2 @ Root::Node(_), Root::Instruction(Instruction::Swp(sw1))]
-          if sw1.get() == 0x01 =>
-        {
-          Some(vec![node2.clone(), node1.clone()])
-        }
+    // length 3
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [
+        (Some(Family::Nodes), &mut |window| match window {
+          // `Operator`s
+          [Root::Node(node1), Root::Node(node2), Root::Operator(operator)] => {
+            let (node2, node1) = (Box::new(node2.clone()), Box::new(node1.clone()));
+            Some(vec![Root::Node(match operator {
+              Operator::Mul => Node::Mul(node2, node1),
+              Operator::Div => Node::Div(node2, node1),
+              Operator::Mod => Node::Mod(node2, node1),
+            })])
+          }
 
-        [Root::Instruction(Instruction::Ldo(ofst)), node @ Root::Node(_), Root::Instruction(Instruction::Swp(sw1))]
-          if ofst.get().checked_add(1).and_then(Ofst::new).is_some() && sw1.get() == 0x01 =>
-        {
-          Some(vec![
-            node.clone(),
-            Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() + 1))),
-          ])
-        }
+          _ => None,
+        }),
+        (Some(Family::Conditionals), &mut |window| match window {
+          // `Conditional`s
+          [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Iff(if1))]
+            if if1.get() == 0x01 =>
+          {
+            Some(vec![Root::Conditional(node1.clone(), node2.clone())])
+          }
 
-        [node @ Root::Node(_), Root::Instruction(Instruction::Ldo(ofst)), Root::Instruction(Instruction::Swp(sw1))]
-          if ofst.get().checked_sub(1).and_then(Ofst::new).is_some() && sw1.get() == 0x01 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() - 1))),
-            node.clone(),
-          ])
-        }
-        [Root::Instruction(Instruction::Ldo(ofst1)), Root::Instruction(Instruction::Ldo(ofst2)), Root::Instruction(Instruction::Swp(sw1))]
-          if ofst1.get().checked_add(1).and_then(Ofst::new).is_some()
-            && ofst2.get().checked_sub(1).and_then(Ofst::new).is_some()
-            && sw1.get() == 0x01 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Ldo(Ofst::assert(ofst2.get() - 1))),
-            Root::Instruction(Instruction::Ldo(Ofst::assert(ofst1.get() + 1))),
-          ])
-        }
+          _ => None,
+        }),
+        (Some(Family::Nodes), &mut |window| match window {
+          // `Node`s
+          [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Add(ad1))]
+            if ad1.get() == 0x01 =>
+          {
+            Some(vec![Root::Node(Node::Add(
+              Box::new(node2.clone()),
+              Box::new(node1.clone()),
+            ))])
+          }
+          [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Sub(su1))]
+            if su1.get() == 0x01 =>
+          {
+            Some(vec![Root::Node(Node::Sub(
+              Box::new(node2.clone()),
+              Box::new(node1.clone()),
+            ))])
+          }
+          [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Rot(ro1))]
+            if ro1.get() == 0x01 =>
+          {
+            Some(vec![Root::Node(Node::Rot(
+              Box::new(node2.clone()),
+              Box::new(node1.clone()),
+            ))])
+          }
+          [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Orr(or1))]
+            if or1.get() == 0x01 =>
+          {
+            Some(vec![Root::Node(Node::Orr(
+              Box::new(node2.clone()),
+              Box::new(node1.clone()),
+            ))])
+          }
+          [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::And(an1))]
+            if an1.get() == 0x01 =>
+          {
+            Some(vec![Root::Node(Node::And(
+              Box::new(node2.clone()),
+              Box::new(node1.clone()),
+            ))])
+          }
+          [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Xor(xo1))]
+            if xo1.get() == 0x01 =>
+          {
+            Some(vec![Root::Node(Node::Xor(
+              Box::new(node2.clone()),
+              Box::new(node1.clone()),
+            ))])
+          }
+          [Root::Node(node1), Root::Node(node2), Root::Instruction(Instruction::Xnd(xn1))]
+            if xn1.get() == 0x01 =>
+          {
+            Some(vec![Root::Node(Node::Xnd(
+              Box::new(node2.clone()),
+              Box::new(node1.clone()),
+            ))])
+          }
 
-        // `Ldo`s
-        [node @ Root::Node(_), push_op, Root::Instruction(Instruction::Ldo(ld1))]
-          if op_type(push_op) == OpType::PushOp && ld1.get() == 0x01 =>
-        {
-          Some(vec![node.clone(), push_op.clone(), node.clone()])
-        }
+          _ => None,
+        }),
+        (Some(Family::Swps), &mut |window| match window {
+          // `Swp`s
+          [Root::Instruction(Instruction::Swp(sw1)), Root::Instruction(Instruction::Inc), Root::Instruction(Instruction::Swp(sw1_))]
+            if sw1.get() == 0x01 && sw1_.get() == 0x01 =>
+          {
+            Some(vec![
+              Root::Node(Node::Value(0x01)),
+              Root::Instruction(Instruction::Add(Size::assert(0x02))),
+            ])
+          }
+          [Root::Instruction(Instruction::Swp(sw1)), Root::Instruction(Instruction::Dec), Root::Instruction(Instruction::Swp(sw1_))]
+            if sw1.get() == 0x01 && sw1_.get() == 0x01 =>
+          {
+            Some(vec![
+              Root::Node(Node::Value(0x01)),
+              Root::Instruction(Instruction::Sub(Size::assert(0x02))),
+            ])
+          }
+          [node1 @ Root::Node(_), node2 @ Root::Node(_), Root::Instruction(Instruction::Swp(sw1))]
+            if sw1.get() == 0x01 =>
+          {
+            Some(vec![node2.clone(), node1.clone()])
+          }
 
-        // `Sto`s
-        [pop_op, Root::Node(x00), Root::Instruction(Instruction::Sto(st7))]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
-            && st7.get() == 0x07 =>
-        {
-          Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
-            0x08,
-          )))])
-        }
-        [Root::Node(x00), Root::Instruction(Instruction::Sto(st8)), pop_op]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
-            && st8.get() == 0x08 =>
-        {
-          Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
-            0x08,
-          )))])
-        }
-        [pop_op, Root::Node(x00), Root::Instruction(Instruction::Sto(st3))]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
-            && st3.get() == 0x03 =>
-        {
-          Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
-            0x04,
-          )))])
-        }
-        [Root::Node(x00), Root::Instruction(Instruction::Sto(st4)), pop_op]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
-            && st4.get() == 0x04 =>
-        {
-          Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
-            0x04,
-          )))])
-        }
-        [pop_op, Root::Node(x00), Root::Instruction(Instruction::Sto(st1))]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
-            && st1.get() == 0x01 =>
-        {
-          Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
-            0x02,
-          )))])
-        }
-        [Root::Node(x00), Root::Instruction(Instruction::Sto(st2)), pop_op]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
-            && st2.get() == 0x02 =>
-        {
-          Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
-            0x02,
-          )))])
-        }
-        [pop_op1, pop_op2, Root::Node(x00)]
-          if op_type(pop_op1) == OpType::PopOp
-            && op_type(pop_op2) == OpType::PopOp
-            && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
-        {
-          Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
-            0x01,
-          )))])
-        }
-        [pop_op, Root::Node(x00), Root::Instruction(Instruction::Sto(st0))]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
-            && st0.get() == 0x00 =>
-        {
-          Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
-            0x01,
-          )))])
-        }
-        [Root::Node(x00), Root::Instruction(Instruction::Sto(st1)), pop_op]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
-            && st1.get() == 0x01 =>
-        {
-          Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
-            0x01,
-          )))])
-        }
-        [pop_op1, pop_op2, Root::Node(x01)]
-          if op_type(pop_op1) == OpType::PopOp
-            && op_type(pop_op2) == OpType::PopOp
-            && resolve_node_value(&x01, &HashMap::new()) == Ok(0x01) =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-            Root::Instruction(Instruction::Shl),
-          ])
-        }
-        [pop_op, Root::Node(x01), Root::Instruction(Instruction::Sto(st0))]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x01, &HashMap::new()) == Ok(0x01)
-            && st0.get() == 0x00 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-            Root::Instruction(Instruction::Shl),
-          ])
-        }
-        [Root::Node(x01), Root::Instruction(Instruction::Sto(st1)), pop_op]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x01, &HashMap::new()) == Ok(0x01)
-            && st1.get() == 0x01 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-            Root::Instruction(Instruction::Shl),
-          ])
-        }
-        [pop_op1, pop_op2, Root::Node(x80)]
-          if op_type(pop_op1) == OpType::PopOp
-            && op_type(pop_op2) == OpType::PopOp
-            && resolve_node_value(&x80, &HashMap::new()) == Ok(0x80) =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-            Root::Instruction(Instruction::Shr),
-          ])
-        }
-        [pop_op, Root::Node(x80), Root::Instruction(Instruction::Sto(st0))]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x80, &HashMap::new()) == Ok(0x80)
-            && st0.get() == 0x00 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-            Root::Instruction(Instruction::Shr),
-          ])
-        }
-        [Root::Node(x80), Root::Instruction(Instruction::Sto(st1)), pop_op]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&x80, &HashMap::new()) == Ok(0x80)
-            && st1.get() == 0x01 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-            Root::Instruction(Instruction::Shr),
-          ])
-        }
-        [pop_op1, pop_op2, Root::Node(xff)]
-          if op_type(pop_op1) == OpType::PopOp
-            && op_type(pop_op2) == OpType::PopOp
-            && resolve_node_value(&xff, &HashMap::new()) == Ok(0xFF) =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-            Root::Instruction(Instruction::Not),
-          ])
-        }
-        [pop_op, Root::Node(xff), Root::Instruction(Instruction::Sto(st0))]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&xff, &HashMap::new()) == Ok(0xFF)
-            && st0.get() == 0x00 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-            Root::Instruction(Instruction::Not),
-          ])
-        }
-        [Root::Node(xff), Root::Instruction(Instruction::Sto(st1)), pop_op]
-          if op_type(pop_op) == OpType::PopOp
-            && resolve_node_value(&xff, &HashMap::new()) == Ok(0xFF)
-            && st1.get() == 0x01 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
-            Root::Instruction(Instruction::Not),
-          ])
-        }
+          [Root::Instruction(Instruction::Ldo(ofst)), node @ Root::Node(_), Root::Instruction(Instruction::Swp(sw1))]
+            if ofst.get().checked_add(1).and_then(Ofst::new).is_some() && sw1.get() == 0x01 =>
+          {
+            Some(vec![
+              node.clone(),
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() + 1))),
+            ])
+          }
 
-        // for `cc` macro return and if statement codegen
-        [Root::Node(Node::LabelRef(same_label)), Root::Instruction(Instruction::Sti), Root::LabelDefs(same_labels)]
-          if same_labels.contains(&same_label) =>
-        {
-          Some(vec![Root::LabelDefs(same_labels.clone())])
-        }
+          [node @ Root::Node(_), Root::Instruction(Instruction::Ldo(ofst)), Root::Instruction(Instruction::Swp(sw1))]
+            if ofst.get().checked_sub(1).and_then(Ofst::new).is_some() && sw1.get() == 0x01 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() - 1))),
+              node.clone(),
+            ])
+          }
+          [Root::Instruction(Instruction::Ldo(ofst1)), Root::Instruction(Instruction::Ldo(ofst2)), Root::Instruction(Instruction::Swp(sw1))]
+            if ofst1.get().checked_add(1).and_then(Ofst::new).is_some()
+              && ofst2.get().checked_sub(1).and_then(Ofst::new).is_some()
+              && sw1.get() == 0x01 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst2.get() - 1))),
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst1.get() + 1))),
+            ])
+          }
 
-        // `OpType`s
-        [push_op, binary_op, pop_op]
-          if op_type(push_op) == OpType::PushOp
-            && op_type(binary_op) == OpType::BinaryOp
-            && op_type(pop_op) == OpType::PopOp =>
-        {
-          Some(vec![pop_op.clone()])
-        }
-        [dual_op, pop_op1, pop_op2]
-          if op_type(dual_op) == OpType::DualOp
-            && op_type(pop_op1) == OpType::PopOp
-            && op_type(pop_op2) == OpType::PopOp =>
-        {
-          Some(vec![pop_op1.clone(), pop_op2.clone()])
-        }
+          _ => None,
+        }),
+        (Some(Family::Ldos), &mut |window| match window {
+          // `Ldo`s
+          [node @ Root::Node(_), push_op, Root::Instruction(Instruction::Ldo(ld1))]
+            if op_type(push_op) == OpType::PushOp && ld1.get() == 0x01 =>
+          {
+            Some(vec![node.clone(), push_op.clone(), node.clone()])
+          }
 
-        _ => None,
-      }
-    });
+          _ => None,
+        }),
+        (Some(Family::Stos), &mut |window| match window {
+          // `Sto`s
+          [pop_op, Root::Node(x00), Root::Instruction(Instruction::Sto(st7))]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
+              && st7.get() == 0x07 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
+              0x08,
+            )))])
+          }
+          [Root::Node(x00), Root::Instruction(Instruction::Sto(st8)), pop_op]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
+              && st8.get() == 0x08 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
+              0x08,
+            )))])
+          }
+          [pop_op, Root::Node(x00), Root::Instruction(Instruction::Sto(st3))]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
+              && st3.get() == 0x03 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
+              0x04,
+            )))])
+          }
+          [Root::Node(x00), Root::Instruction(Instruction::Sto(st4)), pop_op]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
+              && st4.get() == 0x04 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
+              0x04,
+            )))])
+          }
+          [pop_op, Root::Node(x00), Root::Instruction(Instruction::Sto(st1))]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
+              && st1.get() == 0x01 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
+              0x02,
+            )))])
+          }
+          [Root::Node(x00), Root::Instruction(Instruction::Sto(st2)), pop_op]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
+              && st2.get() == 0x02 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
+              0x02,
+            )))])
+          }
+          [pop_op1, pop_op2, Root::Node(x00)]
+            if op_type(pop_op1) == OpType::PopOp
+              && op_type(pop_op2) == OpType::PopOp
+              && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00) =>
+          {
+            Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
+              0x01,
+            )))])
+          }
+          [pop_op, Root::Node(x00), Root::Instruction(Instruction::Sto(st0))]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
+              && st0.get() == 0x00 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
+              0x01,
+            )))])
+          }
+          [Root::Node(x00), Root::Instruction(Instruction::Sto(st1)), pop_op]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x00, &HashMap::new()) == Ok(0x00)
+              && st1.get() == 0x01 =>
+          {
+            Some(vec![Root::Instruction(Instruction::Xnd(Size::assert(
+              0x01,
+            )))])
+          }
+          [pop_op1, pop_op2, Root::Node(x01)]
+            if op_type(pop_op1) == OpType::PopOp
+              && op_type(pop_op2) == OpType::PopOp
+              && resolve_node_value(&x01, &HashMap::new()) == Ok(0x01) =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+              Root::Instruction(Instruction::Shl),
+            ])
+          }
+          [pop_op, Root::Node(x01), Root::Instruction(Instruction::Sto(st0))]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x01, &HashMap::new()) == Ok(0x01)
+              && st0.get() == 0x00 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+              Root::Instruction(Instruction::Shl),
+            ])
+          }
+          [Root::Node(x01), Root::Instruction(Instruction::Sto(st1)), pop_op]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x01, &HashMap::new()) == Ok(0x01)
+              && st1.get() == 0x01 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+              Root::Instruction(Instruction::Shl),
+            ])
+          }
+          [pop_op1, pop_op2, Root::Node(x80)]
+            if op_type(pop_op1) == OpType::PopOp
+              && op_type(pop_op2) == OpType::PopOp
+              && resolve_node_value(&x80, &HashMap::new()) == Ok(0x80) =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+              Root::Instruction(Instruction::Shr),
+            ])
+          }
+          [pop_op, Root::Node(x80), Root::Instruction(Instruction::Sto(st0))]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x80, &HashMap::new()) == Ok(0x80)
+              && st0.get() == 0x00 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+              Root::Instruction(Instruction::Shr),
+            ])
+          }
+          [Root::Node(x80), Root::Instruction(Instruction::Sto(st1)), pop_op]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&x80, &HashMap::new()) == Ok(0x80)
+              && st1.get() == 0x01 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+              Root::Instruction(Instruction::Shr),
+            ])
+          }
+          [pop_op1, pop_op2, Root::Node(xff)]
+            if op_type(pop_op1) == OpType::PopOp
+              && op_type(pop_op2) == OpType::PopOp
+              && resolve_node_value(&xff, &HashMap::new()) == Ok(0xFF) =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+              Root::Instruction(Instruction::Not),
+            ])
+          }
+          [pop_op, Root::Node(xff), Root::Instruction(Instruction::Sto(st0))]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&xff, &HashMap::new()) == Ok(0xFF)
+              && st0.get() == 0x00 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+              Root::Instruction(Instruction::Not),
+            ])
+          }
+          [Root::Node(xff), Root::Instruction(Instruction::Sto(st1)), pop_op]
+            if op_type(pop_op) == OpType::PopOp
+              && resolve_node_value(&xff, &HashMap::new()) == Ok(0xFF)
+              && st1.get() == 0x01 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Xnd(Size::assert(0x01))),
+              Root::Instruction(Instruction::Not),
+            ])
+          }
 
-    // length 4
-    roots = match_replace(&roots, |window| {
-      match window {
-        // doubled `BinaryOp`s
-        [Root::Node(node1), and @ Root::Instruction(Instruction::Add(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Add(same_size2))]
-          if same_size1 == same_size2 =>
-        {
-          Some(vec![
-            Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
-            and.clone(),
-          ])
-        }
-        [Root::Node(node1), add @ Root::Instruction(Instruction::Add(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Sub(same_size2))]
-          if same_size1 == same_size2 =>
-        {
-          Some(vec![
-            Root::Node(Node::Sub(Box::new(node2.clone()), Box::new(node1.clone()))),
-            add.clone(),
-          ])
-        }
-        [Root::Node(node1), sub @ Root::Instruction(Instruction::Sub(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Sub(same_size2))]
-          if same_size1 == same_size2 =>
-        {
-          Some(vec![
-            Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
-            sub.clone(),
-          ])
-        }
-        [Root::Node(node1), sub @ Root::Instruction(Instruction::Sub(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Add(same_size2))]
-          if same_size1 == same_size2 =>
-        {
-          Some(vec![
-            Root::Node(Node::Sub(Box::new(node2.clone()), Box::new(node1.clone()))),
-            sub.clone(),
-          ])
-        }
-        [Root::Node(node1), rot @ Root::Instruction(Instruction::Rot(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Rot(same_size2))]
-          if same_size1 == same_size2 =>
-        {
-          Some(vec![
-            Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
-            rot.clone(),
-          ])
-        }
-        [Root::Node(node1), orr @ Root::Instruction(Instruction::Orr(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Orr(same_size2))]
-          if same_size1 == same_size2 =>
-        {
-          Some(vec![
-            Root::Node(Node::Orr(Box::new(node2.clone()), Box::new(node1.clone()))),
-            orr.clone(),
-          ])
-        }
-        [Root::Node(node1), and @ Root::Instruction(Instruction::And(same_size1)), Root::Node(node2), Root::Instruction(Instruction::And(same_size2))]
-          if same_size1 == same_size2 =>
-        {
-          Some(vec![
-            Root::Node(Node::And(Box::new(node2.clone()), Box::new(node1.clone()))),
-            and.clone(),
-          ])
-        }
-        [Root::Node(node1), xor @ Root::Instruction(Instruction::Xor(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Xor(same_size2))]
-          if same_size1 == same_size2 =>
-        {
-          Some(vec![
-            Root::Node(Node::Xor(Box::new(node2.clone()), Box::new(node1.clone()))),
-            xor.clone(),
-          ])
-        }
-        [Root::Node(node1), xnd @ Root::Instruction(Instruction::Xnd(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Xnd(same_size2))]
-          if same_size1 == same_size2 =>
-        {
-          Some(vec![
-            Root::Node(Node::Xnd(Box::new(node2.clone()), Box::new(node1.clone()))),
-            xnd.clone(),
-          ])
-        }
-        [Root::Node(node1), Root::Instruction(Instruction::And(same_size1)), Root::Node(node2), orr @ Root::Instruction(Instruction::Orr(same_size2))]
-          if same_size1 == same_size2
-            && (resolve_node_value(&node1, &HashMap::new()).ok())
-              .zip(resolve_node_value(&node2, &HashMap::new()).ok())
-              .map(|(value1, value2)| value1 ^ value2 == 0xFF)
-              .unwrap_or(false) =>
-        {
-          Some(vec![Root::Node(node2.clone()), orr.clone()])
-        }
-        [Root::Node(node1), Root::Instruction(Instruction::Orr(same_size1)), Root::Node(node2), and @ Root::Instruction(Instruction::And(same_size2))]
-          if same_size1 == same_size2
-            && (resolve_node_value(&node1, &HashMap::new()).ok())
-              .zip(resolve_node_value(&node2, &HashMap::new()).ok())
-              .map(|(value1, value2)| value1 ^ value2 == 0xFF)
-              .unwrap_or(false) =>
-        {
-          Some(vec![Root::Node(node2.clone()), and.clone()])
-        }
+          _ => None,
+        }),
+        (Some(Family::Jumps), &mut |window| match window {
+          // for `cc` macro return and if statement codegen
+          [Root::Node(Node::LabelRef(same_label)), Root::Instruction(Instruction::Sti), Root::LabelDefs(same_labels)]
+            if same_labels.contains(&same_label) =>
+          {
+            Some(vec![Root::LabelDefs(same_labels.clone())])
+          }
+
+          _ => None,
+        }),
+        (Some(Family::OpTypes), &mut |window| match window {
+          // `OpType`s
+          [push_op, binary_op, pop_op]
+            if op_type(push_op) == OpType::PushOp
+              && op_type(binary_op) == OpType::BinaryOp
+              && op_type(pop_op) == OpType::PopOp =>
+          {
+            Some(vec![pop_op.clone()])
+          }
+          [dual_op, pop_op1, pop_op2]
+            if op_type(dual_op) == OpType::DualOp
+              && op_type(pop_op1) == OpType::PopOp
+              && op_type(pop_op2) == OpType::PopOp =>
+          {
+            Some(vec![pop_op1.clone(), pop_op2.clone()])
+          }
 
-        // `Conditional`s
-        [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Iff(if2))]
-          if op_type(push_op) == OpType::PushOp && if2.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Conditional(node1.clone(), node2.clone()),
-            push_op.clone(),
-          ])
-        }
+          _ => None,
+        }),
+      ],
+    );
 
-        // `Node`s
-        [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Add(ad2))]
-          if op_type(push_op) == OpType::PushOp && ad2.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
-            push_op.clone(),
-          ])
-        }
-        [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Sub(su2))]
-          if op_type(push_op) == OpType::PushOp && su2.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Node(Node::Sub(Box::new(node2.clone()), Box::new(node1.clone()))),
-            push_op.clone(),
-          ])
-        }
-        [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Rot(ro2))]
-          if op_type(push_op) == OpType::PushOp && ro2.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Node(Node::Rot(Box::new(node2.clone()), Box::new(node1.clone()))),
-            push_op.clone(),
-          ])
-        }
-        [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Orr(or2))]
-          if op_type(push_op) == OpType::PushOp && or2.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Node(Node::Orr(Box::new(node2.clone()), Box::new(node1.clone()))),
-            push_op.clone(),
-          ])
-        }
-        [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::And(an2))]
-          if op_type(push_op) == OpType::PushOp && an2.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Node(Node::And(Box::new(node2.clone()), Box::new(node1.clone()))),
-            push_op.clone(),
-          ])
-        }
-        [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Xor(xo2))]
-          if op_type(push_op) == OpType::PushOp && xo2.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Node(Node::Xor(Box::new(node2.clone()), Box::new(node1.clone()))),
-            push_op.clone(),
-          ])
-        }
-        [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Xnd(xn2))]
-          if op_type(push_op) == OpType::PushOp && xn2.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Node(Node::Xnd(Box::new(node2.clone()), Box::new(node1.clone()))),
-            push_op.clone(),
-          ])
-        }
+    // length 4
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [
+        (Some(Family::Nodes), &mut |window| match window {
+          // doubled `BinaryOp`s
+          [Root::Node(node1), and @ Root::Instruction(Instruction::Add(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Add(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![
+              Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
+              and.clone(),
+            ])
+          }
+          [Root::Node(node1), add @ Root::Instruction(Instruction::Add(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Sub(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![
+              Root::Node(Node::Sub(Box::new(node2.clone()), Box::new(node1.clone()))),
+              add.clone(),
+            ])
+          }
+          [Root::Node(node1), sub @ Root::Instruction(Instruction::Sub(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Sub(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![
+              Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
+              sub.clone(),
+            ])
+          }
+          [Root::Node(node1), sub @ Root::Instruction(Instruction::Sub(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Add(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![
+              Root::Node(Node::Sub(Box::new(node2.clone()), Box::new(node1.clone()))),
+              sub.clone(),
+            ])
+          }
+          [Root::Node(node1), rot @ Root::Instruction(Instruction::Rot(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Rot(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![
+              Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
+              rot.clone(),
+            ])
+          }
+          [Root::Node(node1), orr @ Root::Instruction(Instruction::Orr(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Orr(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![
+              Root::Node(Node::Orr(Box::new(node2.clone()), Box::new(node1.clone()))),
+              orr.clone(),
+            ])
+          }
+          [Root::Node(node1), and @ Root::Instruction(Instruction::And(same_size1)), Root::Node(node2), Root::Instruction(Instruction::And(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![
+              Root::Node(Node::And(Box::new(node2.clone()), Box::new(node1.clone()))),
+              and.clone(),
+            ])
+          }
+          [Root::Node(node1), xor @ Root::Instruction(Instruction::Xor(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Xor(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![
+              Root::Node(Node::Xor(Box::new(node2.clone()), Box::new(node1.clone()))),
+              xor.clone(),
+            ])
+          }
+          [Root::Node(node1), xnd @ Root::Instruction(Instruction::Xnd(same_size1)), Root::Node(node2), Root::Instruction(Instruction::Xnd(same_size2))]
+            if same_size1 == same_size2 =>
+          {
+            Some(vec![
+              Root::Node(Node::Xnd(Box::new(node2.clone()), Box::new(node1.clone()))),
+              xnd.clone(),
+            ])
+          }
+          [Root::Node(node1), Root::Instruction(Instruction::And(same_size1)), Root::Node(node2), orr @ Root::Instruction(Instruction::Orr(same_size2))]
+            if same_size1 == same_size2
+              && (resolve_node_value(&node1, &HashMap::new()).ok())
+                .zip(resolve_node_value(&node2, &HashMap::new()).ok())
+                .map(|(value1, value2)| value1 ^ value2 == 0xFF)
+                .unwrap_or(false) =>
+          {
+            Some(vec![Root::Node(node2.clone()), orr.clone()])
+          }
+          [Root::Node(node1), Root::Instruction(Instruction::Orr(same_size1)), Root::Node(node2), and @ Root::Instruction(Instruction::And(same_size2))]
+            if same_size1 == same_size2
+              && (resolve_node_value(&node1, &HashMap::new()).ok())
+                .zip(resolve_node_value(&node2, &HashMap::new()).ok())
+                .map(|(value1, value2)| value1 ^ value2 == 0xFF)
+                .unwrap_or(false) =>
+          {
+            Some(vec![Root::Node(node2.clone()), and.clone()])
+          }
 
-        // `Swp`s
-        [node1 @ Root::Node(_), push_op, node2 @ Root::Node(_), Root::Instruction(Instruction::Swp(sw2))]
-          if op_type(push_op) == OpType::PushOp && sw2.get() == 0x02 =>
-        {
-          Some(vec![node2.clone(), push_op.clone(), node1.clone()])
-        }
-        [Root::Instruction(Instruction::Ldo(ofst)), push_op, node @ Root::Node(_), Root::Instruction(Instruction::Swp(sw2))]
-          if op_type(push_op) == OpType::PushOp
-            && ofst.get().checked_add(2).and_then(Ofst::new).is_some()
-            && sw2.get() == 0x02 =>
-        {
-          Some(vec![
-            node.clone(),
-            push_op.clone(),
-            Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() + 2))),
-          ])
-        }
-        [node @ Root::Node(_), push_op, Root::Instruction(Instruction::Ldo(ofst)), Root::Instruction(Instruction::Swp(x0o))]
-          if op_type(push_op) == OpType::PushOp
-            && ofst.get().checked_sub(2).and_then(Ofst::new).is_some()
-            && x0o.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() - 2))),
-            push_op.clone(),
-            node.clone(),
-          ])
-        }
-        [Root::Instruction(Instruction::Ldo(ofst1)), push_op, Root::Instruction(Instruction::Ldo(ofst2)), Root::Instruction(Instruction::Swp(sw2))]
-          if op_type(push_op) == OpType::PushOp
-            && ofst1.get().checked_add(2).and_then(Ofst::new).is_some()
-            && ofst2.get().checked_sub(2).and_then(Ofst::new).is_some()
-            && sw2.get() == 0x02 =>
-        {
-          Some(vec![
-            Root::Instruction(Instruction::Ldo(Ofst::assert(ofst2.get() - 2))),
-            push_op.clone(),
-            Root::Instruction(Instruction::Ldo(Ofst::assert(ofst1.get() + 2))),
-          ])
-        }
+          _ => None,
+        }),
+        (Some(Family::Conditionals), &mut |window| match window {
+          // `Conditional`s
+          [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Iff(if2))]
+            if op_type(push_op) == OpType::PushOp && if2.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Conditional(node1.clone(), node2.clone()),
+              push_op.clone(),
+            ])
+          }
+
+          _ => None,
+        }),
+        (Some(Family::Nodes), &mut |window| match window {
+          // `Node`s
+          [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Add(ad2))]
+            if op_type(push_op) == OpType::PushOp && ad2.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Sub(su2))]
+            if op_type(push_op) == OpType::PushOp && su2.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Node(Node::Sub(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Rot(ro2))]
+            if op_type(push_op) == OpType::PushOp && ro2.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Node(Node::Rot(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Orr(or2))]
+            if op_type(push_op) == OpType::PushOp && or2.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Node(Node::Orr(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::And(an2))]
+            if op_type(push_op) == OpType::PushOp && an2.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Node(Node::And(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Xor(xo2))]
+            if op_type(push_op) == OpType::PushOp && xo2.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Node(Node::Xor(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op, Root::Node(node2), Root::Instruction(Instruction::Xnd(xn2))]
+            if op_type(push_op) == OpType::PushOp && xn2.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Node(Node::Xnd(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op.clone(),
+            ])
+          }
+
+          _ => None,
+        }),
+        (Some(Family::Swps), &mut |window| match window {
+          // `Swp`s
+          [node1 @ Root::Node(_), push_op, node2 @ Root::Node(_), Root::Instruction(Instruction::Swp(sw2))]
+            if op_type(push_op) == OpType::PushOp && sw2.get() == 0x02 =>
+          {
+            Some(vec![node2.clone(), push_op.clone(), node1.clone()])
+          }
+          [Root::Instruction(Instruction::Ldo(ofst)), push_op, node @ Root::Node(_), Root::Instruction(Instruction::Swp(sw2))]
+            if op_type(push_op) == OpType::PushOp
+              && ofst.get().checked_add(2).and_then(Ofst::new).is_some()
+              && sw2.get() == 0x02 =>
+          {
+            Some(vec![
+              node.clone(),
+              push_op.clone(),
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() + 2))),
+            ])
+          }
+          [node @ Root::Node(_), push_op, Root::Instruction(Instruction::Ldo(ofst)), Root::Instruction(Instruction::Swp(x0o))]
+            if op_type(push_op) == OpType::PushOp
+              && ofst.get().checked_sub(2).and_then(Ofst::new).is_some()
+              && x0o.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() - 2))),
+              push_op.clone(),
+              node.clone(),
+            ])
+          }
+          [Root::Instruction(Instruction::Ldo(ofst1)), push_op, Root::Instruction(Instruction::Ldo(ofst2)), Root::Instruction(Instruction::Swp(sw2))]
+            if op_type(push_op) == OpType::PushOp
+              && ofst1.get().checked_add(2).and_then(Ofst::new).is_some()
+              && ofst2.get().checked_sub(2).and_then(Ofst::new).is_some()
+              && sw2.get() == 0x02 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst2.get() - 2))),
+              push_op.clone(),
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst1.get() + 2))),
+            ])
+          }
 
+          _ => None,
+        }),
+        (Some(Family::Ldos), &mut |window| match window {
+          // `Ldo`s
+          [node @ Root::Node(_), push_op1, push_op2, Root::Instruction(Instruction::Ldo(ld2))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && ld2.get() == 0x02 =>
+          {
+            Some(vec![
+              node.clone(),
+              push_op1.clone(),
+              push_op2.clone(),
+              node.clone(),
+            ])
+          }
+
+          _ => None,
+        }),
+      ],
+    );
+
+    // length 5
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(Some(Family::Ldos), &mut |window| match window {
         // `Ldo`s
-        [node @ Root::Node(_), push_op1, push_op2, Root::Instruction(Instruction::Ldo(ld2))]
+        [node @ Root::Node(_), push_op1, push_op2, push_op3, Root::Instruction(Instruction::Ldo(ld3))]
           if op_type(push_op1) == OpType::PushOp
             && op_type(push_op2) == OpType::PushOp
-            && ld2.get() == 0x02 =>
+            && op_type(push_op3) == OpType::PushOp
+            && ld3.get() == 0x03 =>
         {
           Some(vec![
             node.clone(),
             push_op1.clone(),
             push_op2.clone(),
+            push_op3.clone(),
             node.clone(),
           ])
         }
 
         _ => None,
-      }
-    });
-
-    // length 5
-    roots = match_replace(&roots, |window| match window {
-      // `Ldo`s
-      [node @ Root::Node(_), push_op1, push_op2, push_op3, Root::Instruction(Instruction::Ldo(ld3))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && ld3.get() == 0x03 =>
-      {
-        Some(vec![
-          node.clone(),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-          node.clone(),
-        ])
-      }
-
-      _ => None,
-    });
+      })],
+    );
 
     // length 6
-    roots = match_replace(&roots, |window| match window {
-      // `Conditional`s
-      [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Iff(if4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && if4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Conditional(node1.clone(), node2.clone()),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-        ])
-      }
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [
+        (Some(Family::Conditionals), &mut |window| match window {
+          // `Conditional`s
+          [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Iff(if4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && if4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Conditional(node1.clone(), node2.clone()),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+            ])
+          }
 
-      // `Node`s
-      [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Add(ad4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && ad4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-        ])
-      }
-      [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Sub(su4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && su4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Node(Node::Sub(Box::new(node2.clone()), Box::new(node1.clone()))),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-        ])
-      }
-      [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Rot(ro4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && ro4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Node(Node::Rot(Box::new(node2.clone()), Box::new(node1.clone()))),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-        ])
-      }
-      [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Orr(or4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && or4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Node(Node::Orr(Box::new(node2.clone()), Box::new(node1.clone()))),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-        ])
-      }
-      [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::And(an4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && an4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Node(Node::And(Box::new(node2.clone()), Box::new(node1.clone()))),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-        ])
-      }
-      [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Xor(xo4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && xo4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Node(Node::Xor(Box::new(node2.clone()), Box::new(node1.clone()))),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-        ])
-      }
-      [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Xnd(xn4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && xn4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Node(Node::Xnd(Box::new(node2.clone()), Box::new(node1.clone()))),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-        ])
-      }
+          _ => None,
+        }),
+        (Some(Family::Nodes), &mut |window| match window {
+          // `Node`s
+          [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Add(ad4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && ad4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Node(Node::Add(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Sub(su4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && su4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Node(Node::Sub(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Rot(ro4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && ro4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Node(Node::Rot(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Orr(or4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && or4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Node(Node::Orr(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::And(an4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && an4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Node(Node::And(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Xor(xo4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && xo4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Node(Node::Xor(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+            ])
+          }
+          [Root::Node(node1), push_op1, push_op2, push_op3, Root::Node(node2), Root::Instruction(Instruction::Xnd(xn4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && xn4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Node(Node::Xnd(Box::new(node2.clone()), Box::new(node1.clone()))),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+            ])
+          }
 
-      // `Swp`s
-      [node1 @ Root::Node(_), push_op1, push_op2, push_op3, node2 @ Root::Node(_), Root::Instruction(Instruction::Swp(sw4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && sw4.get() == 0x04 =>
-      {
-        Some(vec![
-          node2.clone(),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-          node1.clone(),
-        ])
-      }
-      [Root::Instruction(Instruction::Ldo(ofst)), push_op1, push_op2, push_op3, node @ Root::Node(_), Root::Instruction(Instruction::Swp(sw4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && ofst.get().checked_add(4).and_then(Ofst::new).is_some()
-          && sw4.get() == 0x04 =>
-      {
-        Some(vec![
-          node.clone(),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-          Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() + 4))),
-        ])
-      }
-      [node @ Root::Node(_), push_op1, push_op2, push_op3, Root::Instruction(Instruction::Ldo(ofst)), Root::Instruction(Instruction::Swp(sw4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && ofst.get().checked_sub(4).and_then(Ofst::new).is_some()
-          && sw4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() - 4))),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-          node.clone(),
-        ])
-      }
-      [Root::Instruction(Instruction::Ldo(ofst1)), push_op1, push_op2, push_op3, Root::Instruction(Instruction::Ldo(ofst2)), Root::Instruction(Instruction::Swp(sw4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && ofst1.get().checked_add(4).and_then(Ofst::new).is_some()
-          && ofst2.get().checked_sub(4).and_then(Ofst::new).is_some()
-          && sw4.get() == 0x04 =>
-      {
-        Some(vec![
-          Root::Instruction(Instruction::Ldo(Ofst::assert(ofst2.get() - 4))),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-          Root::Instruction(Instruction::Ldo(Ofst::assert(ofst1.get() + 4))),
-        ])
-      }
+          _ => None,
+        }),
+        (Some(Family::Swps), &mut |window| match window {
+          // `Swp`s
+          [node1 @ Root::Node(_), push_op1, push_op2, push_op3, node2 @ Root::Node(_), Root::Instruction(Instruction::Swp(sw4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && sw4.get() == 0x04 =>
+          {
+            Some(vec![
+              node2.clone(),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+              node1.clone(),
+            ])
+          }
+          [Root::Instruction(Instruction::Ldo(ofst)), push_op1, push_op2, push_op3, node @ Root::Node(_), Root::Instruction(Instruction::Swp(sw4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && ofst.get().checked_add(4).and_then(Ofst::new).is_some()
+              && sw4.get() == 0x04 =>
+          {
+            Some(vec![
+              node.clone(),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() + 4))),
+            ])
+          }
+          [node @ Root::Node(_), push_op1, push_op2, push_op3, Root::Instruction(Instruction::Ldo(ofst)), Root::Instruction(Instruction::Swp(sw4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && ofst.get().checked_sub(4).and_then(Ofst::new).is_some()
+              && sw4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst.get() - 4))),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+              node.clone(),
+            ])
+          }
+          [Root::Instruction(Instruction::Ldo(ofst1)), push_op1, push_op2, push_op3, Root::Instruction(Instruction::Ldo(ofst2)), Root::Instruction(Instruction::Swp(sw4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && ofst1.get().checked_add(4).and_then(Ofst::new).is_some()
+              && ofst2.get().checked_sub(4).and_then(Ofst::new).is_some()
+              && sw4.get() == 0x04 =>
+          {
+            Some(vec![
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst2.get() - 4))),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+              Root::Instruction(Instruction::Ldo(Ofst::assert(ofst1.get() + 4))),
+            ])
+          }
 
-      // `Ldo`s
-      [node @ Root::Node(_), push_op1, push_op2, push_op3, push_op4, Root::Instruction(Instruction::Ldo(ld4))]
-        if op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && op_type(push_op4) == OpType::PushOp
-          && ld4.get() == 0x04 =>
-      {
-        Some(vec![
-          node.clone(),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-          push_op4.clone(),
-          node.clone(),
-        ])
-      }
+          _ => None,
+        }),
+        (Some(Family::Ldos), &mut |window| match window {
+          // `Ldo`s
+          [node @ Root::Node(_), push_op1, push_op2, push_op3, push_op4, Root::Instruction(Instruction::Ldo(ld4))]
+            if op_type(push_op1) == OpType::PushOp
+              && op_type(push_op2) == OpType::PushOp
+              && op_type(push_op3) == OpType::PushOp
+              && op_type(push_op4) == OpType::PushOp
+              && ld4.get() == 0x04 =>
+          {
+            Some(vec![
+              node.clone(),
+              push_op1.clone(),
+              push_op2.clone(),
+              push_op3.clone(),
+              push_op4.clone(),
+              node.clone(),
+            ])
+          }
 
-      _ => None,
-    });
+          _ => None,
+        }),
+      ],
+    );
   }
 
   // optimize duplicate `Node`s (pushing them might take up two bytes) into `Ldo`s (always take up one byte)
@@ -1752,95 +2774,126 @@ fn optimize(roots: Vec<(Pos, Root)>, _errors: &mut impl Extend<(Pos, Error)>) ->
     last_roots = roots.clone();
 
     // length 2
-    roots = match_replace(&roots, |window| match window {
-      [same_node1 @ Root::Node(_), same_node2 @ Root::Node(_)] if same_node1 == same_node2 => {
-        Some(vec![
-          same_node1.clone(),
-          Root::Instruction(Instruction::Ldo(Ofst::assert(0x00))),
-        ])
-      }
-      [Root::Instruction(Instruction::Swp(size)), Root::Instruction(Instruction::Pop)]
-        if size.get().checked_sub(1).and_then(Ofst::new).is_some() =>
-      {
-        Some(vec![Root::Instruction(Instruction::Sto(Ofst::assert(
-          size.get() - 1,
-        )))])
-      }
-      _ => None,
-    });
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(Some(Family::Ldos), &mut |window| match window {
+        [same_node1 @ Root::Node(_), same_node2 @ Root::Node(_)] if same_node1 == same_node2 => {
+          Some(vec![
+            same_node1.clone(),
+            Root::Instruction(Instruction::Ldo(Ofst::assert(0x00))),
+          ])
+        }
+        [Root::Instruction(Instruction::Swp(size)), Root::Instruction(Instruction::Pop)]
+          if size.get().checked_sub(1).and_then(Ofst::new).is_some() =>
+        {
+          Some(vec![Root::Instruction(Instruction::Sto(Ofst::assert(
+            size.get() - 1,
+          )))])
+        }
+
+        _ => None,
+      })],
+    );
 
     // length 3
-    roots = match_replace(&roots, |window| match window {
-      [same_node1 @ Root::Node(_), push_op, same_node2 @ Root::Node(_)]
-        if same_node1 == same_node2 && op_type(push_op) == OpType::PushOp =>
-      {
-        Some(vec![
-          same_node1.clone(),
-          push_op.clone(),
-          Root::Instruction(Instruction::Ldo(Ofst::assert(0x01))),
-        ])
-      }
-      _ => None,
-    });
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(Some(Family::Ldos), &mut |window| match window {
+        [same_node1 @ Root::Node(_), push_op, same_node2 @ Root::Node(_)]
+          if same_node1 == same_node2 && op_type(push_op) == OpType::PushOp =>
+        {
+          Some(vec![
+            same_node1.clone(),
+            push_op.clone(),
+            Root::Instruction(Instruction::Ldo(Ofst::assert(0x01))),
+          ])
+        }
+
+        _ => None,
+      })],
+    );
 
     // length 4
-    roots = match_replace(&roots, |window| match window {
-      [same_node1 @ Root::Node(_), push_op1, push_op2, same_node2 @ Root::Node(_)]
-        if same_node1 == same_node2
-          && op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp =>
-      {
-        Some(vec![
-          same_node1.clone(),
-          push_op1.clone(),
-          push_op2.clone(),
-          Root::Instruction(Instruction::Ldo(Ofst::assert(0x02))),
-        ])
-      }
-      _ => None,
-    });
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(Some(Family::Ldos), &mut |window| match window {
+        [same_node1 @ Root::Node(_), push_op1, push_op2, same_node2 @ Root::Node(_)]
+          if same_node1 == same_node2
+            && op_type(push_op1) == OpType::PushOp
+            && op_type(push_op2) == OpType::PushOp =>
+        {
+          Some(vec![
+            same_node1.clone(),
+            push_op1.clone(),
+            push_op2.clone(),
+            Root::Instruction(Instruction::Ldo(Ofst::assert(0x02))),
+          ])
+        }
+
+        _ => None,
+      })],
+    );
 
     // length 5
-    roots = match_replace(&roots, |window| match window {
-      [same_node1 @ Root::Node(_), push_op1, push_op2, push_op3, same_node2 @ Root::Node(_)]
-        if same_node1 == same_node2
-          && op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp =>
-      {
-        Some(vec![
-          same_node1.clone(),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-          Root::Instruction(Instruction::Ldo(Ofst::assert(0x03))),
-        ])
-      }
-      _ => None,
-    });
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(Some(Family::Ldos), &mut |window| match window {
+        [same_node1 @ Root::Node(_), push_op1, push_op2, push_op3, same_node2 @ Root::Node(_)]
+          if same_node1 == same_node2
+            && op_type(push_op1) == OpType::PushOp
+            && op_type(push_op2) == OpType::PushOp
+            && op_type(push_op3) == OpType::PushOp =>
+        {
+          Some(vec![
+            same_node1.clone(),
+            push_op1.clone(),
+            push_op2.clone(),
+            push_op3.clone(),
+            Root::Instruction(Instruction::Ldo(Ofst::assert(0x03))),
+          ])
+        }
+
+        _ => None,
+      })],
+    );
 
     // length 6
-    roots = match_replace(&roots, |window| match window {
-      [same_node1 @ Root::Node(_), push_op1, push_op2, push_op3, push_op4, same_node2 @ Root::Node(_)]
-        if same_node1 == same_node2
-          && op_type(push_op1) == OpType::PushOp
-          && op_type(push_op2) == OpType::PushOp
-          && op_type(push_op3) == OpType::PushOp
-          && op_type(push_op4) == OpType::PushOp =>
-      {
-        Some(vec![
-          same_node1.clone(),
-          push_op1.clone(),
-          push_op2.clone(),
-          push_op3.clone(),
-          push_op4.clone(),
-          Root::Instruction(Instruction::Ldo(Ofst::assert(0x04))),
-        ])
-      }
-      _ => None,
-    });
+    roots = match_replace(
+      &roots,
+      &mut rewritten,
+      disabled,
+      &mut [(Some(Family::Ldos), &mut |window| match window {
+        [same_node1 @ Root::Node(_), push_op1, push_op2, push_op3, push_op4, same_node2 @ Root::Node(_)]
+          if same_node1 == same_node2
+            && op_type(push_op1) == OpType::PushOp
+            && op_type(push_op2) == OpType::PushOp
+            && op_type(push_op3) == OpType::PushOp
+            && op_type(push_op4) == OpType::PushOp =>
+        {
+          Some(vec![
+            same_node1.clone(),
+            push_op1.clone(),
+            push_op2.clone(),
+            push_op3.clone(),
+            push_op4.clone(),
+            Root::Instruction(Instruction::Ldo(Ofst::assert(0x04))),
+          ])
+        }
+
+        _ => None,
+      })],
+    );
   }
 
+  rewrites.extend(rewritten);
   roots
 }
 
@@ -1870,8 +2923,115 @@ fn resolve_node_value(node: &Node, label_definitions: &HashMap<Label, u8>) -> Re
       resolve_node_value(node2, label_definitions)? ^ resolve_node_value(node1, label_definitions)?
     }
     Node::Xnd(_node1, _node2) => 0x00,
+    Node::Mul(node1, node2) => resolve_node_value(node2, label_definitions)?
+      .wrapping_mul(resolve_node_value(node1, label_definitions)?),
+    Node::Div(node1, node2) => {
+      let dividend = resolve_node_value(node2, label_definitions)?;
+      dividend
+        .checked_div(resolve_node_value(node1, label_definitions)?)
+        .unwrap_or(0xFF)
+    }
+    Node::Mod(node1, node2) => {
+      let dividend = resolve_node_value(node2, label_definitions)?;
+      dividend
+        .checked_rem(resolve_node_value(node1, label_definitions)?)
+        .unwrap_or(dividend)
+    }
     Node::Shl(node) => resolve_node_value(node, label_definitions)?.wrapping_shl(1),
     Node::Shr(node) => resolve_node_value(node, label_definitions)?.wrapping_shr(1),
     Node::Not(node) => !resolve_node_value(node, label_definitions)?,
   })
 }
+
+impl std::fmt::Display for Origin {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let Origin(pos, macros) = self;
+    let origin = macros
+      .iter()
+      .map(|r#macro| format!("{} ", r#macro))
+      .chain([pos.to_string()])
+      .collect::<String>();
+    f.pad(&origin)
+  }
+}
+
+impl std::fmt::Display for Root {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Root::Instruction(instruction) => {
+        write!(f, "{}", instruction_to_token(Ok(instruction.clone())))
+      }
+      Root::Conditional(node1, node2) => write!(f, "{} {} {}", node1, node2, Token::Iff),
+      Root::LabelDefs(labels) => write!(
+        f,
+        "{}",
+        labels
+          .iter()
+          .map(|label| token_to_mnemonic(Token::LabelDef(label.clone())).0)
+          .collect::<Vec<String>>()
+          .join(" ")
+      ),
+      Root::Node(node) => write!(f, "{}", node),
+      Root::Operator(Operator::Mul) => write!(f, "{}", Token::Mul),
+      Root::Operator(Operator::Div) => write!(f, "{}", Token::Div),
+      Root::Operator(Operator::Mod) => write!(f, "{}", Token::Mod),
+      Root::Const => write!(f, "{}", Token::AtConst),
+      Root::Data(Some(node)) => write!(f, "{} {}", node, Token::AtData),
+      Root::Data(None) => write!(f, "{}", Token::AtData),
+      Root::Dyn(Some(instruction)) => write!(
+        f,
+        "{} {}",
+        instruction_to_token(Ok(instruction.clone())),
+        Token::AtDyn
+      ),
+      Root::Dyn(None) => write!(f, "{}", Token::AtDyn),
+      Root::Org(Some(node)) => write!(f, "{} {}", node, Token::AtOrg),
+      Root::Org(None) => write!(f, "{}", Token::AtOrg),
+      Root::Bank(Some(node)) => write!(f, "{} {}", node, Token::AtBank),
+      Root::Bank(None) => write!(f, "{}", Token::AtBank),
+    }
+  }
+}
+
+impl std::fmt::Display for Node {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    // nodes are written back in postfix, as they would appear in assembly. constant subtrees are
+    // written as their value so that constant folding shows up in listings
+    if let Ok(value) = resolve_node_value(self, &HashMap::new()) {
+      return write!(f, "{}", Token::XXX(value));
+    }
+    match self {
+      Node::LabelRef(label) => write!(f, "{}", label),
+      Node::Value(value) => write!(f, "{}", Token::XXX(*value)),
+      Node::Add(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::Add),
+      Node::Sub(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::Sub),
+      Node::Rot(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::Rot),
+      Node::Orr(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::Orr),
+      Node::And(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::And),
+      Node::Xor(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::Xor),
+      Node::Xnd(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::Xnd),
+      Node::Mul(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::Mul),
+      Node::Div(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::Div),
+      Node::Mod(node1, node2) => write!(f, "{} {} {}", node2, node1, Token::Mod),
+      Node::Shl(node) => write!(f, "{} {}", node, Token::Shl),
+      Node::Shr(node) => write!(f, "{} {}", node, Token::Shr),
+      Node::Not(node) => write!(f, "{} {}", node, Token::Not),
+    }
+  }
+}
+
+impl std::fmt::Display for Family {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.pad(match self {
+      Family::Nodes => "nodes",
+      Family::OpTypes => "op-types",
+      Family::Ldos => "ldos",
+      Family::UnaryOps => "unary-ops",
+      Family::Labels => "labels",
+      Family::Conditionals => "conditionals",
+      Family::Swps => "swps",
+      Family::Stos => "stos",
+      Family::Jumps => "jumps",
+    })
+  }
+}
@@ -47,7 +47,7 @@ fn build_microcode(errors: &mut impl Extend<Error>) -> [u16; common::MIC_SIZE] {
   // sets specified fields to `true` and wraps to ensure compatibility with `seq!`
   macro_rules! ControlWord {
     ($($field:ident),*) => {
-      vec![Ok::<ControlWord, TickTrap>(ControlWord {
+      vec![Ok::<ControlWord, MicrocodeTrap>(ControlWord {
         $($field: Signal::Active,)*
         ..ControlWord::default()
       })]
@@ -116,7 +116,7 @@ fn build_microcode(errors: &mut impl Extend<Error>) -> [u16; common::MIC_SIZE] {
   let nfetch = seq![ip_alxl, cinsum_ip, mem_ylzl, nand_il];
   let walk = seq![set_al, mem_yl, sum_ip];
 
-  let microcode: [[[Result<ControlWord, TickTrap>; 0x20]; 0x02]; 0x80] = [[[(); 0x20]; 0x02]; 0x80]
+  let microcode: [[[Result<ControlWord, _>; 0x20]; 0x02]; 0x80] = [[[(); 0x20]; 0x02]; 0x80]
     .iter()
     .enumerate()
     .map(|(opcode, rest)| (opcode as u8 | 0x80, rest)) // ignore `psh`s as they will be mapped to `phn`s by `sim`
@@ -317,7 +317,7 @@ fn build_microcode(errors: &mut impl Extend<Error>) -> [u16; common::MIC_SIZE] {
                     clr_yl, //
                     match carry {
                       true => seq![walk, clr_yl],
-                      false => vec![Err(TickTrap::DebugRequest)],
+                      false => vec![Err(MicrocodeTrap::DebugRequest)],
                     }
                   ]
                 }
@@ -352,7 +352,9 @@ fn build_microcode(errors: &mut impl Extend<Error>) -> [u16; common::MIC_SIZE] {
               let pre = seq![seq, clr_sc];
               let post = seq![noop];
               match 0x20usize.overflowing_sub(pre.len() + post.len()) {
-                (padding, false) => seq![pre, vec![Err(TickTrap::MicrocodeFault); padding], post],
+                (padding, false) => {
+                  seq![pre, vec![Err(MicrocodeTrap::MicrocodeFault); padding], post]
+                }
                 (wrapped, true) => {
                   if step == 0x00 {
                     errors.extend([Error(format!(
@@ -362,7 +364,7 @@ fn build_microcode(errors: &mut impl Extend<Error>) -> [u16; common::MIC_SIZE] {
                       wrapped.wrapping_neg()
                     ))]);
                   }
-                  vec![Err(TickTrap::MicrocodeFault); 0x20]
+                  vec![Err(MicrocodeTrap::MicrocodeFault); 0x20]
                 }
               }
               .get(step)
@@ -4,21 +4,55 @@ use common::*;
 
 fn main() {
   let args: Vec<String> = std::env::args().collect();
-  if args.len() != 3 {
-    eprintln!("Dasm: Usage: dasm <memory image file> <disassembly output file>");
+
+  let mut symbol_file: Option<&String> = None;
+  let mut positional_args: Vec<&String> = vec![];
+
+  let mut args_iter = args.iter().skip(1);
+  while let Some(arg) = args_iter.next() {
+    match arg.as_str() {
+      "--symbols" => {
+        symbol_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Dasm: Error: Option '--symbols' expects a symbol file");
+          std::process::exit(1);
+        }))
+      }
+      _ if arg.starts_with("--") => {
+        eprintln!("Dasm: Error: Unknown option '{}'", arg);
+        std::process::exit(1);
+      }
+      _ => positional_args.push(arg),
+    }
+  }
+
+  if positional_args.len() != 2 {
+    eprintln!(
+      "Dasm: Usage: dasm [--symbols <symbol file>] <memory image file> <disassembly output file>"
+    );
     std::process::exit(1);
   }
 
-  let memory_image_file: &String = &args[1];
-  let disassembly_output_file: &String = &args[2];
+  let memory_image_file: &String = positional_args[0];
+  let disassembly_output_file: &String = positional_args[1];
+
+  let symbols: Option<Symbols> = symbol_file.map(|symbol_file| {
+    let symbols = std::fs::read_to_string(symbol_file).unwrap_or_else(|_| {
+      eprintln!("Dasm: Error: Unable to read file '{}'", symbol_file);
+      std::process::exit(1);
+    });
+    common::parse_symbols(&symbols).unwrap_or_else(|error| {
+      eprintln!("Dasm: Error: Symbol file '{}': {}", symbol_file, error);
+      std::process::exit(1);
+    })
+  });
 
-  let memory_image: [u8; common::MEM_SIZE] = std::fs::read(memory_image_file)
+  let (memory_image, banks) = std::fs::read(memory_image_file)
+    .map(common::split_memory_image)
     .unwrap_or_else(|_| {
       eprintln!("Dasm: Error: Unable to read file '{}'", memory_image_file);
       std::process::exit(1);
     })
-    .try_into()
-    .unwrap_or_else(|_| {
+    .unwrap_or_else(|| {
       eprintln!(
         "Dasm: Error: Memory image '{}' has incorrect size",
         memory_image_file,
@@ -26,10 +60,33 @@ fn main() {
       std::process::exit(1);
     });
 
-  let opcodes: Vec<u8> = Vec::from(memory_image);
+  // symbol files only describe the base memory image, so banks are disassembled without symbols
+  let disassembly: String = std::iter::once(disassemble(&memory_image, 0x00, symbols.as_ref()))
+    .chain(banks.iter().enumerate().map(|(index, bank)| {
+      format!(
+        "  {} {}\n{}",
+        Token::XXX(index as u8 + 1),
+        Token::AtBank,
+        disassemble(bank, common::BANK_WINDOW as u8, None)
+      )
+    }))
+    .collect();
+
+  let disassembly = format!(
+    "{}\n{}",
+    Token::MacroDef(Macro("main".to_string())),
+    disassembly
+  );
 
+  let disassembly = format!("# Generated by Dasm\n\n{}", disassembly);
+
+  std::fs::write(disassembly_output_file, disassembly).unwrap();
+}
+
+fn disassemble(opcodes: &[u8], origin: u8, symbols: Option<&Symbols>) -> String {
   let instructions: Vec<Result<Instruction, u8>> = opcodes
-    .into_iter()
+    .iter()
+    .copied()
     .map(common::opcode_to_instruction)
     .collect();
 
@@ -40,30 +97,41 @@ fn main() {
 
   let mnemonics: Vec<Mnemonic> = tokens.into_iter().map(common::token_to_mnemonic).collect();
 
-  let disassembly: String = mnemonics
+  mnemonics
     .into_iter()
     .enumerate()
-    .zip(memory_image.into_iter())
-    .map(|((index, mnemonic), opcode)| {
-      format!(
+    .map(|(index, mnemonic)| (origin.wrapping_add(index as u8), mnemonic))
+    .zip(opcodes.iter().copied())
+    .map(|((address, mnemonic), opcode)| {
+      let line = format!(
         "  {} {} # {} {} {}",
         mnemonic,
         Token::AtDyn,
-        Token::XXX(index as u8),
+        Token::XXX(address),
         Token::AtOrg,
         Token::AtDD(opcode),
-      )
+      );
+
+      // label definitions and source positions are emitted as comments so the disassembly
+      // still assembles without unused label errors
+      match symbols {
+        Some(symbols) => {
+          let labels = symbols
+            .labels
+            .iter()
+            .filter(|(_, &label_address)| label_address == address)
+            .map(|(label, _)| format!("# {}\n", Token::LabelDef(label.clone())))
+            .collect::<String>();
+          let origin = match (symbols.origins.get(&address), symbols.source_line(address)) {
+            (Some((pos, _)), Some(source_line)) => format!(" {} {}", pos, source_line),
+            (Some((pos, _)), None) => format!(" {}", pos),
+            (None, _) => String::new(),
+          };
+          format!("{}{}{}", labels, line, origin)
+        }
+        None => line,
+      }
     })
     .map(|line| line.to_string() + "\n")
-    .collect::<String>();
-
-  let disassembly = format!(
-    "{}\n{}",
-    Token::MacroDef(Macro("main".to_string())),
-    disassembly
-  );
-
-  let disassembly = format!("# Generated by Dasm\n\n{}", disassembly);
-
-  std::fs::write(disassembly_output_file, disassembly).unwrap();
+    .collect::<String>()
 }
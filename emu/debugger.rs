@@ -0,0 +1,607 @@
+use crate::*;
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{BufRead, Write};
+
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+enum Watch {
+  Read,   // address is read from, excluding instruction fetches
+  Write,  // address is written to
+  Change, // value at address changes
+}
+
+#[derive(Clone)]
+struct Frame {
+  target: u8,         // address of callee
+  return_address: u8, // address execution resumes at once callee returns
+}
+
+enum Stop {
+  TickTrap(TickTrap),
+  Breakpoint,
+  Watchpoint(u8, Watch),
+  HistoryExhausted,
+}
+
+// number of instructions that can be stepped back through. a state is a few hundred bytes,
+// so this amounts to a few dozen megabytes at most, plus a bank window for every bank switch
+const HISTORY_LEN: usize = 0x10000;
+
+// machine state before an instruction was executed. standard output is not part of it, as it
+// has already been written out by then
+struct State {
+  mem: [u8; common::MEM_SIZE],
+  mp: Microprocessor,
+  halt: HaltDetector,
+  stdin: VecDeque<u8>,
+  display: [u8; common::DISPLAY_BUFFER_LEN],
+  controller: u8,
+  clocks: u128,
+  call_stack: Vec<Frame>,
+  peripherals: Vec<Vec<u8>>, // peripheral checkpoints, such as the mapped bank
+}
+
+struct Debugger {
+  mc: Microcomputer,
+  stdin: VecDeque<u8>,
+  stdout: VecDeque<u8>,
+  display: [u8; common::DISPLAY_BUFFER_LEN],
+  controller: u8,
+  clocks: u128,
+
+  breakpoints: BTreeSet<u8>,
+  watchpoints: BTreeSet<(u8, Watch)>,
+  call_stack: Vec<Frame>, // shadow call stack, maintained by recognizing `!call`s and `!ret`s
+  history: VecDeque<State>, // states before each of the last `HISTORY_LEN` instructions
+}
+
+pub fn debug(mc: Microcomputer) {
+  let mut dbg = Debugger {
+    mc,
+    stdin: VecDeque::new(),
+    stdout: VecDeque::new(),
+    display: [0x00; common::DISPLAY_BUFFER_LEN],
+    controller: 0x00,
+    clocks: 0,
+
+    breakpoints: BTreeSet::new(),
+    watchpoints: BTreeSet::new(),
+    call_stack: vec![],
+    history: VecDeque::new(),
+  };
+
+  dbg.reset();
+  println!("Stopped at {}", dbg.location());
+
+  let mut lines = std::io::stdin().lock().lines();
+  loop {
+    print!("(emu) ");
+    std::io::stdout().flush().unwrap();
+
+    let line = match lines.next() {
+      Some(Ok(line)) => line,
+      _ => break,
+    };
+
+    let args: Vec<&str> = line.split_whitespace().collect();
+    match args[..] {
+      [] => {}
+      ["quit" | "q"] => break,
+      ["help" | "h"] => print!("{}", HELP),
+      ["reset"] => {
+        dbg.reset();
+        println!("Stopped at {}", dbg.location());
+      }
+
+      ["continue" | "c"] => dbg.run(|_| false),
+      ["step" | "s"] => dbg.run(|_| true),
+      ["step" | "s", count] => match count.parse::<usize>() {
+        Ok(count) => {
+          let mut remaining = count;
+          dbg.run(|_| {
+            remaining = remaining.saturating_sub(1);
+            remaining == 0
+          })
+        }
+        Err(_) => println!("Invalid step count '{}'", count),
+      },
+      ["next" | "n"] => match dbg.call_at_ip() {
+        Some(return_address) => {
+          let depth = dbg.call_stack.len();
+          dbg.run(|dbg| dbg.call_stack.len() <= depth && dbg.mc.mp.ip == return_address)
+        }
+        None => dbg.run(|_| true),
+      },
+      ["finish" | "f"] => match dbg.call_stack.len() {
+        0 => println!("Not within a call"),
+        depth => dbg.run(|dbg| dbg.call_stack.len() < depth),
+      },
+      ["back" | "bs"] => dbg.run_back(|_| true),
+      ["back" | "bs", count] => match count.parse::<usize>() {
+        Ok(count) => {
+          let mut remaining = count;
+          dbg.run_back(|_| {
+            remaining = remaining.saturating_sub(1);
+            remaining == 0
+          })
+        }
+        Err(_) => println!("Invalid step count '{}'", count),
+      },
+      ["reverse-continue" | "rc"] => dbg.run_back(|_| false),
+
+      ["break" | "b"] => dbg.breakpoints.iter().for_each(|address| {
+        println!("Breakpoint at {}", dbg.describe(*address));
+      }),
+      ["break" | "b", address] => match dbg.parse_address(address) {
+        Some(address) => {
+          dbg.breakpoints.insert(address);
+          println!("Breakpoint at {}", dbg.describe(address));
+        }
+        None => println!("Invalid address '{}'", address),
+      },
+      ["delete" | "d"] => {
+        dbg.breakpoints.clear();
+        dbg.watchpoints.clear();
+      }
+      ["delete" | "d", address] => match dbg.parse_address(address) {
+        Some(address) => {
+          dbg.breakpoints.remove(&address);
+          dbg.watchpoints.retain(|(watched, _)| *watched != address);
+        }
+        None => println!("Invalid address '{}'", address),
+      },
+      ["watch" | "w"] => dbg.watchpoints.iter().for_each(|(address, watch)| {
+        println!("{} watchpoint at {}", watch, dbg.describe(*address));
+      }),
+      ["watch" | "w", address] | ["watch" | "w", address, _] => {
+        let watch = match args.get(2) {
+          None | Some(&"c") => Some(Watch::Change),
+          Some(&"r") => Some(Watch::Read),
+          Some(&"w") => Some(Watch::Write),
+          Some(_) => None,
+        };
+        match (dbg.parse_address(address), watch) {
+          (Some(address), Some(watch)) => {
+            dbg.watchpoints.insert((address, watch));
+            println!("{} watchpoint at {}", watch, dbg.describe(address));
+          }
+          (None, _) => println!("Invalid address '{}'", address),
+          (_, None) => println!("Invalid watchpoint kind '{}'", args[2]),
+        }
+      }
+
+      ["regs" | "r"] => println!(
+        "IP {:02X}  SP {:02X}  CF {:01b}  Clocks {}",
+        dbg.mc.mp.ip, dbg.mc.mp.sp, dbg.mc.mp.cf as u8, dbg.clocks
+      ),
+      ["mem" | "m"] => print!(
+        "{}",
+        common::render_memory(
+          &dbg.mc.mem,
+          dbg.mc.mp.ip,
+          dbg.mc.mp.sp,
+          dbg.mc.mp.cf,
+          dbg.mc.symbols.as_ref()
+        )
+        .replace("\r\n", "\n")
+      ),
+      ["mem" | "m", address] | ["mem" | "m", address, _] => {
+        let len = args.get(2).map_or(Some(0x10), |len| parse_byte(len));
+        match (dbg.parse_address(address), len) {
+          (Some(address), Some(len)) => dbg.dump(address, len),
+          (None, _) => println!("Invalid address '{}'", address),
+          (_, None) => println!("Invalid length '{}'", args[2]),
+        }
+      }
+      ["stack" | "st"] => dbg.stack(0x08),
+      ["stack" | "st", len] => match parse_byte(len) {
+        Some(len) => dbg.stack(len),
+        None => println!("Invalid length '{}'", len),
+      },
+      ["backtrace" | "bt"] => {
+        println!("#0  {}", dbg.location());
+        for (depth, frame) in dbg.call_stack.iter().rev().enumerate() {
+          println!(
+            "#{}  {} called from {}",
+            depth + 1,
+            dbg.describe(frame.target),
+            dbg.describe(frame.return_address.wrapping_sub(1)),
+          );
+        }
+      }
+      ["disas" | "x"] => dbg.disassemble(dbg.mc.mp.ip, 0x08),
+      ["disas" | "x", address] | ["disas" | "x", address, _] => {
+        let len = args.get(2).map_or(Some(0x08), |len| parse_byte(len));
+        match (dbg.parse_address(address), len) {
+          (Some(address), Some(len)) => dbg.disassemble(address, len),
+          (None, _) => println!("Invalid address '{}'", address),
+          (_, None) => println!("Invalid length '{}'", args[2]),
+        }
+      }
+
+      ["set", "ip", value] => match parse_byte(value) {
+        Some(value) => dbg.mc.mp.ip = value,
+        None => println!("Invalid value '{}'", value),
+      },
+      ["set", "sp", value] => match parse_byte(value) {
+        Some(value) => dbg.mc.mp.sp = value,
+        None => println!("Invalid value '{}'", value),
+      },
+      ["set", "cf", "0"] => dbg.mc.mp.cf = false,
+      ["set", "cf", "1"] => dbg.mc.mp.cf = true,
+      ["set", "mem", address, ref values @ ..] if !values.is_empty() => {
+        let bytes: Option<Vec<u8>> = values.iter().map(|value| parse_byte(value)).collect();
+        match (dbg.parse_address(address), bytes) {
+          (Some(address), Some(bytes)) => {
+            for (offset, value) in bytes.into_iter().enumerate() {
+              dbg.mc.mem[address.wrapping_add(offset as u8) as usize] = value;
+            }
+          }
+          (None, _) => println!("Invalid address '{}'", address),
+          (_, None) => println!("Invalid values '{}'", values.join(" ")),
+        }
+      }
+
+      ["input" | "i", ..] => match unescape(line.trim_start()[args[0].len()..].trim_start()) {
+        Some(bytes) => dbg.stdin.extend(bytes),
+        None => println!("Invalid escape sequence"),
+      },
+      ["controller", value] => match parse_byte(value) {
+        Some(value) => dbg.controller = value,
+        None => println!("Invalid value '{}'", value),
+      },
+
+      _ => println!("Unknown command '{}'. Try 'help'", line.trim()),
+    }
+  }
+}
+
+impl Debugger {
+  fn reset(&mut self) {
+    self.mc.reset(
+      &mut self.stdin,
+      &mut self.stdout,
+      &mut self.display,
+      &mut self.controller,
+    );
+    self.clocks = 0;
+    self.call_stack.clear();
+    self.history.clear();
+  }
+
+  fn snapshot(&mut self) -> State {
+    State {
+      mem: self.mc.mem,
+      mp: self.mc.mp.clone(),
+      halt: self.mc.halt.clone(),
+      stdin: self.stdin.clone(),
+      display: self.display,
+      controller: self.controller,
+      clocks: self.clocks,
+      call_stack: self.call_stack.clone(),
+      peripherals: self
+        .mc
+        .peripherals
+        .iter_mut()
+        .map(|peripheral| peripheral.checkpoint())
+        .collect(),
+    }
+  }
+
+  fn restore(&mut self, state: State) {
+    self.mc.mem = state.mem;
+    self.mc.mp = state.mp;
+    self.mc.halt = state.halt;
+    self.mc.accesses.clear();
+    self.mc.clocks = state.clocks;
+    self.stdin = state.stdin;
+    self.display = state.display;
+    self.controller = state.controller;
+    self.clocks = state.clocks;
+    self.call_stack = state.call_stack;
+    self.rewind(&state.peripherals);
+  }
+
+  fn rewind(&mut self, checkpoints: &[Vec<u8>]) {
+    for (peripheral, checkpoint) in self.mc.peripherals.iter_mut().zip(checkpoints) {
+      peripheral.rewind(checkpoint);
+    }
+  }
+
+  // executes a single instruction, keeping the shadow call stack up to date
+  fn step(&mut self) -> Result<(), Stop> {
+    let ip = self.mc.mp.ip;
+    let sp = self.mc.mp.sp;
+    let mem = self.mc.mem;
+
+    if self.history.len() == HISTORY_LEN {
+      self.history.pop_front();
+    }
+    let state = self.snapshot();
+    self.history.push_back(state);
+
+    let sti = match common::opcode_to_instruction(mem[ip as usize]) {
+      Ok(Instruction::Sti) => Some((mem[sp as usize], mem[sp.wrapping_add(1) as usize])),
+      _ => None,
+    };
+
+    let result = self.mc.tick(
+      &mut self.stdin,
+      &mut self.stdout,
+      &mut self.display,
+      &mut self.controller,
+    );
+
+    let output: Vec<u8> = self.stdout.drain(..).collect();
+    std::io::stdout().write_all(&output).unwrap();
+
+    if let Err(TickTrap::ProgramHalt(_)) = result {
+      // halts are detected before the next instruction is executed, so there is nothing to undo
+      // but the checkpoint itself
+      if let Some(state) = self.history.pop_back() {
+        self.rewind(&state.peripherals);
+      }
+    }
+    self.clocks += result.map_err(Stop::TickTrap)?;
+
+    // `!ret` jumps to the return address on top of the stack. `!call` jumps to the callee
+    // with the return address, which points right after the `sti`, just below it. a jump
+    // to the next instruction is a branch falling through, not a call
+    if let Some((target, below)) = sti {
+      if self.call_stack.last().map(|frame| frame.return_address) == Some(target) {
+        self.call_stack.pop();
+      } else if below == ip.wrapping_add(1) && target != below {
+        self.call_stack.push(Frame {
+          target,
+          return_address: below,
+        });
+      }
+    }
+
+    let fetch = 1; // first access is always the instruction fetch
+    for access in self.mc.accesses.iter().skip(fetch) {
+      let (address, watch) = match *access {
+        MemoryAccess::Read(address, _) => (address, Watch::Read),
+        MemoryAccess::Write(address, _) => (address, Watch::Write),
+      };
+      if self.watchpoints.contains(&(address, watch)) {
+        return Err(Stop::Watchpoint(address, watch));
+      }
+    }
+    for (address, watch) in self.watchpoints.iter() {
+      if *watch == Watch::Change && mem[*address as usize] != self.mc.mem[*address as usize] {
+        return Err(Stop::Watchpoint(*address, *watch));
+      }
+    }
+
+    Ok(())
+  }
+
+  // steps until `done` returns `true` or until a breakpoint, a watchpoint or a tick trap is hit.
+  // a breakpoint at the current instruction pointer is ignored so execution can be resumed
+  fn run(&mut self, mut done: impl FnMut(&Self) -> bool) {
+    let stop = loop {
+      if let Err(stop) = self.step() {
+        break Some(stop);
+      }
+      if done(self) {
+        break None;
+      }
+      if self.breakpoints.contains(&self.mc.mp.ip) {
+        break Some(Stop::Breakpoint);
+      }
+    };
+
+    self.report(stop);
+  }
+
+  // steps backward through history until `done` returns `true`, until a breakpoint or a change
+  // watchpoint is hit or until history is exhausted. read and write watchpoints are not
+  // considered, as memory accesses are not part of history
+  fn run_back(&mut self, mut done: impl FnMut(&Self) -> bool) {
+    let stop = loop {
+      let state = match self.history.pop_back() {
+        Some(state) => state,
+        None => break Some(Stop::HistoryExhausted),
+      };
+      let mem = self.mc.mem;
+      self.restore(state);
+
+      if let Some((address, watch)) = self.watchpoints.iter().find(|(address, watch)| {
+        *watch == Watch::Change && mem[*address as usize] != self.mc.mem[*address as usize]
+      }) {
+        break Some(Stop::Watchpoint(*address, *watch));
+      }
+      if done(self) {
+        break None;
+      }
+      if self.breakpoints.contains(&self.mc.mp.ip) {
+        break Some(Stop::Breakpoint);
+      }
+    };
+
+    self.report(stop);
+  }
+
+  fn report(&self, stop: Option<Stop>) {
+    match stop {
+      Some(Stop::TickTrap(tick_trap)) => println!("{}", tick_trap),
+      Some(Stop::HistoryExhausted) => println!("Reached start of history"),
+      Some(Stop::Breakpoint) => println!("Breakpoint hit"),
+      Some(Stop::Watchpoint(address, watch)) => {
+        println!(
+          "{} watchpoint hit at {}, value {:02X}",
+          watch,
+          self.describe(address),
+          self.mc.mem[address as usize]
+        )
+      }
+      None => {}
+    }
+    println!("Stopped at {}", self.location());
+  }
+
+  // if the instruction at the instruction pointer is the `sti` of a `!call`, returns its return address
+  fn call_at_ip(&self) -> Option<u8> {
+    let ip = self.mc.mp.ip;
+    let target = self.mc.mem[self.mc.mp.sp as usize];
+    let below = self.mc.mem[self.mc.mp.sp.wrapping_add(1) as usize];
+    match common::opcode_to_instruction(self.mc.mem[ip as usize]) {
+      Ok(Instruction::Sti) if below == ip.wrapping_add(1) && target != below => Some(below),
+      _ => None,
+    }
+  }
+
+  fn parse_address(&self, address: &str) -> Option<u8> {
+    // `:label` and `:label+offset` are resolved through the symbol file
+    match (address.starts_with(":"), self.mc.symbols.as_ref()) {
+      (true, Some(symbols)) => {
+        let (label, offset) = match address.split_once('+') {
+          Some((label, offset)) => (label, parse_byte(offset)?),
+          None => (address, 0x00),
+        };
+        match common::mnemonic_to_token(Mnemonic(label.to_string())) {
+          Some(Token::LabelRef(label)) => Some(symbols.resolve(&label)?.wrapping_add(offset)),
+          _ => None,
+        }
+      }
+      _ => parse_byte(address),
+    }
+  }
+
+  fn describe(&self, address: u8) -> String {
+    match self
+      .mc
+      .symbols
+      .as_ref()
+      .and_then(|symbols| symbols.describe(address))
+    {
+      Some(label) => format!("{:02X} {}", address, label),
+      None => format!("{:02X}", address),
+    }
+  }
+
+  fn location(&self) -> String {
+    let ip = self.mc.mp.ip;
+    let location = format!(
+      "{}  {}",
+      self.describe(ip),
+      common::instruction_to_token(common::opcode_to_instruction(self.mc.mem[ip as usize]))
+    );
+    let symbols = self.mc.symbols.as_ref();
+    match symbols.and_then(|symbols| Some((symbols.origins.get(&ip)?, symbols.source_line(ip)?))) {
+      Some(((pos, _), source_line)) => format!("{}\n  {}  {}", location, pos, source_line),
+      None => location,
+    }
+  }
+
+  fn dump(&self, address: u8, len: u8) {
+    let addresses: Vec<u8> = (0..len)
+      .map(|offset| address.wrapping_add(offset))
+      .collect();
+    for row in addresses.chunks(0x10) {
+      let values: Vec<String> = row
+        .iter()
+        .map(|address| format!("{:02X}", self.mc.mem[*address as usize]))
+        .collect();
+      println!("{}  {}", self.describe(row[0]), values.join(" "));
+    }
+  }
+
+  fn stack(&self, len: u8) {
+    let sp = self.mc.mp.sp;
+    for offset in 0..len {
+      let address = sp.wrapping_add(offset);
+      println!(
+        "SP+{:01X}  {}  {:02X}",
+        offset,
+        self.describe(address),
+        self.mc.mem[address as usize]
+      );
+    }
+  }
+
+  fn disassemble(&self, address: u8, len: u8) {
+    for offset in 0..len {
+      let address = address.wrapping_add(offset);
+      let opcode = self.mc.mem[address as usize];
+      println!(
+        "{} {}  {:02X}  {}",
+        match address == self.mc.mp.ip {
+          true => '>',
+          false => ' ',
+        },
+        self.describe(address),
+        opcode,
+        common::instruction_to_token(common::opcode_to_instruction(opcode))
+      );
+    }
+  }
+}
+
+fn parse_byte(value: &str) -> Option<u8> {
+  let value = value.strip_prefix("0x").unwrap_or(value);
+  u8::from_str_radix(value, 16).ok()
+}
+
+fn unescape(text: &str) -> Option<Vec<u8>> {
+  let mut bytes = vec![];
+  let mut chars = text.chars();
+  while let Some(char) = chars.next() {
+    match char {
+      '\\' => match chars.next()? {
+        'n' => bytes.push(b'\n'),
+        't' => bytes.push(b'\t'),
+        's' => bytes.push(b' '),
+        '\\' => bytes.push(b'\\'),
+        'x' => bytes.push(parse_byte(&chars.by_ref().take(2).collect::<String>())?),
+        _ => return None,
+      },
+      _ => bytes.extend(char.to_string().bytes()),
+    }
+  }
+  Some(bytes)
+}
+
+impl std::fmt::Display for Watch {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Watch::Read => write!(f, "Read"),
+      Watch::Write => write!(f, "Write"),
+      Watch::Change => write!(f, "Change"),
+    }
+  }
+}
+
+const HELP: &str = "\
+Execution
+  continue, c             Run until a breakpoint, a watchpoint or a tick trap is hit
+  step, s [count]         Step `count` instructions, in decimal
+  next, n                 Step one instruction, stepping over `!call`s
+  finish, f               Run until the current call returns
+  back, bs [count]        Step `count` instructions backward, in decimal
+  reverse-continue, rc    Run backward until a breakpoint or a change watchpoint is hit
+  reset                   Reset the microcomputer
+Breakpoints and watchpoints
+  break, b [address]      Set a breakpoint or list breakpoints
+  watch, w [address [k]]  Set a watchpoint of kind `r`, `w` or `c` (default) or list watchpoints
+  delete, d [address]     Delete breakpoints and watchpoints at `address` or everywhere
+Inspection
+  regs, r                 Print registers and clock count
+  mem, m [address [len]]  Print memory
+  stack, st [len]         Print the top of the stack
+  backtrace, bt           Print the shadow call stack
+  disas, x [address [n]]  Disassemble `n` instructions
+Patching
+  set ip|sp <value>       Set the instruction pointer or the stack pointer
+  set cf 0|1              Set the carry flag
+  set mem <address> <values...>
+                          Write values to memory
+Input
+  input, i <text>         Queue text to standard input; supports `\\n`, `\\t`, `\\s`, `\\\\` and `\\xXX`
+  controller <value>      Set the controller state
+Miscellaneous
+  help, h                 Print this message
+  quit, q                 Exit the debugger
+Addresses, lengths and values are hexadecimal and may be prefixed with `0x`. When a symbol file
+is loaded, addresses may also be given as `:label` or `:label+offset`.
+";
@@ -1,348 +1,487 @@
-use std::collections::VecDeque;
-
 #[path = "../misc/common/common.rs"]
 mod common;
 use common::*;
 
-fn main() {
-  let args: Vec<String> = std::env::args().collect();
-  if args.len() != 2 {
-    eprintln!("Emu: Usage: emu <memory image file>");
-    std::process::exit(1);
-  }
+#[cfg(feature = "gui")]
+#[path = "../misc/common/gui.rs"]
+mod gui;
 
-  let memory_image_file: &String = &args[1];
+#[path = "../misc/common/export.rs"]
+mod export;
+use export::*;
 
-  let memory_image: [u8; common::MEM_SIZE] = std::fs::read(memory_image_file)
-    .unwrap_or_else(|_| {
-      eprintln!("Emu: Error: Unable to read file '{}'", memory_image_file);
-      std::process::exit(1);
-    })
-    .try_into()
-    .unwrap_or_else(|_| {
-      eprintln!(
-        "Emu: Error: Memory image '{}' has incorrect size",
-        memory_image_file,
-      );
-      std::process::exit(1);
-    });
-
-  let mc = Microcomputer {
-    mem: memory_image,
-    mp: Microprocessor {
-      ip: 0x00,
-      sp: 0x00,
-      cf: false,
-    },
-  };
-
-  common::execute(mc, 1000000);
-}
-
-struct Microcomputer {
-  mem: [u8; common::MEM_SIZE], // memory
-  mp: Microprocessor,          // microprocessor
-}
-
-struct Microprocessor {
-  ip: u8,   // instruction pointer
-  sp: u8,   // stack pointer
-  cf: bool, // carry flag
-}
-
-impl Tickable for Microcomputer {
-  fn reset(
-    &mut self,
-    stdin: &mut VecDeque<u8>,
-    stdout: &mut VecDeque<u8>,
-    display: &mut [u8; common::DISPLAY_BUFFER_LEN],
-    _controller: &mut u8,
-  ) {
-    self.mp.ip = 0x00;
-    self.mp.sp = 0x00;
-    self.mp.cf = false;
-    stdin.clear();
-    stdout.clear();
-    stdin.push_back(self.mem[common::STDIO_BUFFER]);
-    display.copy_from_slice(
-      &self.mem[common::DISPLAY_BUFFER..common::DISPLAY_BUFFER + common::DISPLAY_BUFFER_LEN],
-    );
-  }
-
-  fn tick(
-    &mut self,
-    stdin: &mut VecDeque<u8>,
-    stdout: &mut VecDeque<u8>,
-    display: &mut [u8; common::DISPLAY_BUFFER_LEN],
-    controller: &mut u8,
-  ) -> Result<u128, TickTrap> {
-    let mp = &mut self.mp;
-
-    macro_rules! mem_read {
-      ($address:expr) => {{
-        let address = $address as usize;
-        if address == common::STDIO_BUFFER {
-          stdin.pop_front().unwrap_or(*controller)
-        } else {
-          self.mem[address]
-        }
-      }};
-    }
-
-    macro_rules! mem_write {
-      ($address:expr, $value:expr) => {{
-        let address = $address as usize;
-        let value = $value;
-        if address == common::STDIO_BUFFER {
-          stdout.push_back(value);
-        } else {
-          self.mem[address] = value;
-        }
-        if address & common::DISPLAY_BUFFER == common::DISPLAY_BUFFER {
-          display[address & !common::DISPLAY_BUFFER] = value
-        }
-      }};
-    }
-
-    macro_rules! sp_push {
-      ($value:expr) => {{
-        let value = $value;
-        mp.sp = mp.sp.wrapping_sub(1);
-        mem_write!(mp.sp, value);
-      }};
-    }
-
-    macro_rules! sp_pop {
-      () => {{
-        let value = mem_read!(mp.sp);
-        mp.sp = mp.sp.wrapping_add(1);
-        value
-      }};
-    }
+#[path = "../mic/microcode.rs"]
+mod microcode;
 
-    let opcode = mem_read!(mp.ip);
-    mp.ip = mp.ip.wrapping_add(1);
+mod debugger;
+mod microcomputer;
+mod profiler;
+use microcomputer::*;
+use profiler::*;
 
-    let instruction = common::opcode_to_instruction(opcode).map_err(|_| TickTrap::IllegalOpcode)?;
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
 
-    match instruction {
-      Instruction::Psh(imm) => {
-        sp_push!(imm.get());
-        Ok(10)
+  let mut batch_mode = false;
+  let mut gui_mode = false;
+  let mut debugger_mode = false;
+  let mut clock_limit: Option<u128> = None;
+  let mut exit_with_tos = false;
+  let mut clock_speed: Option<u128> = None;
+  let mut unthrottled = false;
+  let mut timer_rng = false;
+  let mut check_stack = false;
+  let mut rng_seed: Option<u8> = None;
+  let mut frame_period: Option<u128> = None;
+  let mut symbol_file: Option<&String> = None;
+  let mut trace_file: Option<&String> = None;
+  let mut save_snapshot_file: Option<&String> = None;
+  let mut load_snapshot_file: Option<&String> = None;
+  let mut frames_directory: Option<&String> = None;
+  let mut gif_file: Option<&String> = None;
+  let mut record_file: Option<&String> = None;
+  let mut replay_file: Option<&String> = None;
+  let mut profile_file: Option<&String> = None;
+  let mut folded_stacks_file: Option<&String> = None;
+  let mut coverage_file: Option<&String> = None;
+  let mut positional_args: Vec<&String> = vec![];
+
+  let mut args_iter = args.iter().skip(1);
+  while let Some(arg) = args_iter.next() {
+    match arg.as_str() {
+      "--batch" => batch_mode = true,
+      "--gui" => gui_mode = true,
+      "--debugger" => debugger_mode = true,
+      "--exit-with-tos" => exit_with_tos = true,
+      "--unthrottled" => unthrottled = true,
+      "--timer-rng" => timer_rng = true,
+      "--check-stack" => check_stack = true,
+      "--rng-seed" => {
+        rng_seed = Some(
+          args_iter
+            .next()
+            .and_then(|seed| u8::from_str_radix(seed, 16).ok())
+            .unwrap_or_else(|| {
+              eprintln!("Emu: Error: Option '--rng-seed' expects a byte in hexadecimal");
+              std::process::exit(1);
+            }),
+        )
       }
-
-      Instruction::Add(size) => {
-        let addr = mp.sp.wrapping_add(size.get());
-        let res = (mem_read!(addr) as u16)
-          .wrapping_add(sp_pop!() as u16)
-          .wrapping_add(mp.cf as u16);
-        mem_write!(addr, res as u8);
-        mp.cf = res > 0xFF;
-        Ok(14 + size.get() as u128)
+      "--clock-speed" => {
+        clock_speed = Some(
+          args_iter
+            .next()
+            .and_then(|hertz| hertz.parse().ok())
+            .filter(|hertz| *hertz > 0)
+            .unwrap_or_else(|| {
+              eprintln!("Emu: Error: Option '--clock-speed' expects a nonzero frequency in hertz");
+              std::process::exit(1);
+            }),
+        )
       }
-
-      Instruction::Sub(size) => {
-        let addr = mp.sp.wrapping_add(size.get());
-        let res = (mem_read!(addr) as u16)
-          .wrapping_sub(sp_pop!() as u16)
-          .wrapping_sub(mp.cf as u16);
-        mem_write!(addr, res as u8);
-        mp.cf = res > 0xFF;
-        Ok(14 + size.get() as u128)
+      "--clock-limit" => {
+        clock_limit = Some(
+          args_iter
+            .next()
+            .and_then(|clocks| clocks.parse().ok())
+            .unwrap_or_else(|| {
+              eprintln!("Emu: Error: Option '--clock-limit' expects a clock count");
+              std::process::exit(1);
+            }),
+        )
       }
-
-      Instruction::Iff(size) => {
-        let addr = mp.sp.wrapping_add(size.get());
-        let top = sp_pop!();
-        mem_write!(addr, if mp.cf { top } else { mem_read!(addr) });
-        Ok(13 + size.get() as u128)
+      "--frame-period" => {
+        frame_period = Some(
+          args_iter
+            .next()
+            .and_then(|clocks| clocks.parse().ok())
+            .filter(|clocks| *clocks > 0)
+            .unwrap_or_else(|| {
+              eprintln!("Emu: Error: Option '--frame-period' expects a nonzero clock count");
+              std::process::exit(1);
+            }),
+        )
       }
-
-      Instruction::Swp(size) => {
-        let addr = mp.sp.wrapping_add(size.get());
-        let top = sp_pop!();
-        sp_push!(mem_read!(addr));
-        mem_write!(addr, top);
-        Ok(13 + size.get() as u128)
+      "--trace" => {
+        trace_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--trace' expects a trace file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Rot(size) => {
-        let addr = mp.sp.wrapping_add(size.get());
-        let top = sp_pop!();
-        let shifted = (mem_read!(addr) as u16) << top % 8;
-        let res = (shifted & 0xFF) as u8 | (shifted >> 8) as u8;
-        mem_write!(addr, res);
-        mp.cf = false;
-        Ok((18 + size.get() as u128) * (top as u128 + 1))
+      "--profile" => {
+        profile_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--profile' expects a profile file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Orr(size) => {
-        let addr = mp.sp.wrapping_add(size.get());
-        let res = sp_pop!() | mem_read!(addr);
-        mem_write!(addr, res);
-        mp.cf = res == 0x00;
-        Ok(14 + size.get() as u128)
+      "--folded-stacks" => {
+        folded_stacks_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--folded-stacks' expects a folded stacks file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::And(size) => {
-        let addr = mp.sp.wrapping_add(size.get());
-        let res = sp_pop!() & mem_read!(addr);
-        mem_write!(addr, res);
-        mp.cf = res == 0x00;
-        Ok(11 + size.get() as u128)
+      "--coverage" => {
+        coverage_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--coverage' expects a coverage file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Xor(size) => {
-        let addr = mp.sp.wrapping_add(size.get());
-        let res = sp_pop!() ^ mem_read!(addr);
-        mem_write!(addr, res);
-        mp.cf = res == 0x00;
-        Ok(22 + size.get() as u128)
+      "--save-snapshot" => {
+        save_snapshot_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--save-snapshot' expects a snapshot file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Xnd(size) => {
-        let addr = mp.sp.wrapping_add(size.get());
-        let res = sp_pop!() & 0x00;
-        mem_write!(addr, res);
-        mp.cf = res == 0x00;
-        Ok(8 + size.get() as u128)
+      "--load-snapshot" => {
+        load_snapshot_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--load-snapshot' expects a snapshot file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Inc => {
-        sp_push!(sp_pop!().wrapping_add(1));
-        Ok(6)
+      "--record" => {
+        record_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--record' expects a recording file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Dec => {
-        sp_push!(sp_pop!().wrapping_sub(1));
-        Ok(8)
+      "--replay" => {
+        replay_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--replay' expects a recording file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Neg => {
-        sp_push!(sp_pop!().wrapping_neg());
-        Ok(11)
+      "--frames" => {
+        frames_directory = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--frames' expects a frame directory");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Shl => {
-        let top = sp_pop!();
-        sp_push!(top.wrapping_shl(1) | (mp.cf as u8));
-        mp.cf = top & 0b10000000 != 0x00;
-        Ok(9)
+      "--gif" => {
+        gif_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--gif' expects a GIF file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Shr => {
-        let top = sp_pop!();
-        sp_push!(top.wrapping_shr(1) | (mp.cf as u8) << 7);
-        mp.cf = top & 0b00000001 != 0x00;
-        Ok(16)
+      "--symbols" => {
+        symbol_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Emu: Error: Option '--symbols' expects a symbol file");
+          std::process::exit(1);
+        }))
       }
-
-      Instruction::Not => {
-        let res = !sp_pop!();
-        sp_push!(res);
-        mp.cf = res == 0x00;
-        Ok(8)
+      _ if arg.starts_with("--") => {
+        eprintln!("Emu: Error: Unknown option '{}'", arg);
+        std::process::exit(1);
       }
+      _ => positional_args.push(arg),
+    }
+  }
 
-      Instruction::Buf => {
-        let res = sp_pop!();
-        sp_push!(res);
-        mp.cf = res == 0x00;
-        Ok(9)
-      }
+  if positional_args.len() != 1 {
+    eprintln!(
+      "Emu: Usage: emu [--batch] [--gui] [--clock-limit <clocks>] [--exit-with-tos] [--clock-speed <hertz>] [--unthrottled] [--frame-period <clocks>] [--timer-rng] [--rng-seed <seed>] [--symbols <symbol file>] [--check-stack] [--trace <trace file>] [--save-snapshot <snapshot file>] [--load-snapshot <snapshot file>] [--record <recording file>] [--replay <recording file>] [--frames <frame directory>] [--gif <gif file>] [--profile <profile file>] [--folded-stacks <folded stacks file>] [--coverage <coverage file>] [--debugger] <memory image file>"
+    );
+    std::process::exit(1);
+  }
 
-      Instruction::Dbg => Err(TickTrap::DebugRequest),
+  if batch_mode && debugger_mode {
+    eprintln!("Emu: Error: Options '--batch' and '--debugger' are mutually exclusive");
+    std::process::exit(1);
+  }
 
-      Instruction::Ldo(ofst) => {
-        let addr = mp.sp.wrapping_add(ofst.get());
-        sp_push!(mem_read!(addr));
-        Ok(12 + ofst.get() as u128)
-      }
+  if gui_mode && !cfg!(feature = "gui") {
+    eprintln!("Emu: Error: Option '--gui' requires building with feature 'gui'");
+    std::process::exit(1);
+  }
 
-      Instruction::Sto(ofst) => {
-        let top = sp_pop!();
-        let addr = mp.sp.wrapping_add(ofst.get());
-        mem_write!(addr, top);
-        Ok(11 + ofst.get() as u128)
-      }
+  if gui_mode && (batch_mode || debugger_mode) {
+    eprintln!("Emu: Error: Option '--gui' cannot be combined with '--batch' or '--debugger'");
+    std::process::exit(1);
+  }
 
-      Instruction::Lda => {
-        sp_push!(mem_read!(sp_pop!()));
-        Ok(9)
-      }
+  if rng_seed.is_some() && !timer_rng {
+    eprintln!("Emu: Error: Option '--rng-seed' requires option '--timer-rng'");
+    std::process::exit(1);
+  }
 
-      Instruction::Sta => {
-        mem_write!(sp_pop!(), sp_pop!());
-        Ok(15)
-      }
+  if (unthrottled || frame_period.is_some()) && (batch_mode || debugger_mode) {
+    eprintln!(
+      "Emu: Error: Options '--unthrottled' and '--frame-period' cannot be combined with '--batch' or '--debugger'"
+    );
+    std::process::exit(1);
+  }
 
-      Instruction::Ldi => {
-        sp_push!(mp.ip);
-        Ok(9)
-      }
+  if trace_file.is_some() && debugger_mode {
+    eprintln!("Emu: Error: Options '--trace' and '--debugger' are mutually exclusive");
+    std::process::exit(1);
+  }
 
-      Instruction::Sti => {
-        mp.ip = sp_pop!();
-        Ok(6)
-      }
+  if (frames_directory.is_some() || gif_file.is_some()) && debugger_mode {
+    eprintln!("Emu: Error: Options '--frames' and '--gif' cannot be combined with '--debugger'");
+    std::process::exit(1);
+  }
 
-      Instruction::Lds => {
-        sp_push!(mp.sp);
-        Ok(10)
-      }
+  if gif_file.is_some() && !batch_mode && !gui_mode {
+    eprintln!("Emu: Error: Option '--gif' requires option '--batch' or '--gui'");
+    std::process::exit(1);
+  }
 
-      Instruction::Sts => {
-        mp.sp = sp_pop!();
-        Ok(5)
-      }
+  if (clock_limit.is_some() || exit_with_tos) && !batch_mode {
+    eprintln!("Emu: Error: Options '--clock-limit' and '--exit-with-tos' require option '--batch'");
+    std::process::exit(1);
+  }
 
-      Instruction::Clc => {
-        mp.cf = false;
-        Ok(6)
-      }
+  if (profile_file.is_some() || folded_stacks_file.is_some()) && !batch_mode {
+    eprintln!("Emu: Error: Options '--profile' and '--folded-stacks' require option '--batch'");
+    std::process::exit(1);
+  }
 
-      Instruction::Sec => {
-        mp.cf = true;
-        Ok(6)
-      }
+  if coverage_file.is_some() && !(batch_mode && symbol_file.is_some()) {
+    eprintln!("Emu: Error: Option '--coverage' requires options '--batch' and '--symbols'");
+    std::process::exit(1);
+  }
 
-      Instruction::Flc => {
-        mp.cf = !mp.cf;
-        Ok(6)
-      }
+  if check_stack && symbol_file.is_none() {
+    eprintln!("Emu: Error: Option '--check-stack' requires option '--symbols'");
+    std::process::exit(1);
+  }
 
-      Instruction::Nop => Ok(3),
+  let session_options = [
+    save_snapshot_file,
+    load_snapshot_file,
+    record_file,
+    replay_file,
+  ];
+  if session_options.iter().any(Option::is_some) && debugger_mode {
+    eprintln!(
+      "Emu: Error: Options '--save-snapshot', '--load-snapshot', '--record' and '--replay' cannot be combined with '--debugger'"
+    );
+    std::process::exit(1);
+  }
 
-      Instruction::Pop => {
-        mp.sp = mp.sp.wrapping_add(1);
-        Ok(5)
-      }
+  let memory_image_file: &String = positional_args[0];
 
-      Instruction::Phn(nimm) => {
-        sp_push!(nimm.get());
-        Ok(10)
-      }
-    }
+  let (memory_image, banks) = std::fs::read(memory_image_file)
+    .map(common::split_memory_image)
+    .unwrap_or_else(|_| {
+      eprintln!("Emu: Error: Unable to read file '{}'", memory_image_file);
+      std::process::exit(1);
+    })
+    .unwrap_or_else(|| {
+      eprintln!(
+        "Emu: Error: Memory image '{}' has incorrect size",
+        memory_image_file,
+      );
+      std::process::exit(1);
+    });
+
+  let symbols: Option<Symbols> = symbol_file.map(|symbol_file| {
+    let symbols = std::fs::read_to_string(symbol_file).unwrap_or_else(|_| {
+      eprintln!("Emu: Error: Unable to read file '{}'", symbol_file);
+      std::process::exit(1);
+    });
+    common::parse_symbols(&symbols).unwrap_or_else(|error| {
+      eprintln!("Emu: Error: Symbol file '{}': {}", symbol_file, error);
+      std::process::exit(1);
+    })
+  });
+
+  // clock counts are derived from the microcode so as to match those of Sim
+  let mut errors: Vec<Error> = vec![];
+  let timings = common::microcode_to_clocks(&microcode::build_microcode(&mut errors));
+  if !errors.is_empty() {
+    let errors = errors
+      .iter()
+      .map(|error| format!("Emu: Error: {}", error))
+      .collect::<Vec<String>>()
+      .join("\n");
+
+    eprintln!("{}", errors);
+    std::process::exit(1);
   }
-}
 
-impl std::fmt::Display for Microcomputer {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(
-      f,
-      "{}\r\n{}",
-      self.mp,
-      common::render_memory(&self.mem, self.mp.ip, self.mp.sp, self.mp.cf),
-    )
+  let mut peripherals = common::default_peripherals();
+  if timer_rng {
+    peripherals.push(Box::new(TimerRng::new(rng_seed.unwrap_or(0x00))));
   }
-}
+  if !banks.is_empty() {
+    peripherals.push(Box::new(BankSwitch::new(banks)));
+  }
+
+  let mut mc = Microcomputer {
+    mem: memory_image,
+    mp: Microprocessor {
+      ip: 0x00,
+      sp: 0x00,
+      cf: false,
+    },
+    halt: HaltDetector::default(),
+    accesses: vec![],
+    clocks: 0,
+    timings,
+    retired: None,
+    stack_check: symbols
+      .as_ref()
+      .filter(|_| check_stack)
+      .map(StackCheck::new),
+    symbols,
+    peripherals,
+  };
+
+  let resume: Option<Snapshot> = load_snapshot_file.map(|snapshot_file| {
+    let snapshot = std::fs::read_to_string(snapshot_file).unwrap_or_else(|_| {
+      eprintln!("Emu: Error: Unable to read file '{}'", snapshot_file);
+      std::process::exit(1);
+    });
+    common::parse_snapshot(&snapshot)
+      .and_then(|snapshot| mc.load(&snapshot).map(|()| snapshot))
+      .unwrap_or_else(|error| {
+        eprintln!("Emu: Error: Snapshot file '{}': {}", snapshot_file, error);
+        std::process::exit(1);
+      })
+  });
+
+  let replay = replay_file
+    .map(|replay_file| {
+      let recording = std::fs::read_to_string(replay_file).unwrap_or_else(|_| {
+        eprintln!("Emu: Error: Unable to read file '{}'", replay_file);
+        std::process::exit(1);
+      });
+      common::parse_recording(&recording).unwrap_or_else(|error| {
+        eprintln!("Emu: Error: Recording file '{}': {}", replay_file, error);
+        std::process::exit(1);
+      })
+    })
+    .unwrap_or_default();
+
+  let record = record_file.map(|record_file| {
+    let mut record = std::fs::File::create(record_file)
+      .map(std::io::LineWriter::new)
+      .unwrap_or_else(|_| {
+        eprintln!("Emu: Error: Unable to write file '{}'", record_file);
+        std::process::exit(1);
+      });
+    use std::io::Write;
+    writeln!(record, "# Generated by Emu").unwrap();
+    Box::new(record) as Box<dyn std::io::Write>
+  });
+
+  let session = Session {
+    resume,
+    snapshot_file: save_snapshot_file,
+    replay,
+    record,
+  };
+
+  if debugger_mode {
+    debugger::debug(mc);
+    std::process::exit(0);
+  }
+
+  let mut profiler = Profiler::new(&mut mc);
+  let clock_speed = clock_speed.unwrap_or(1000000);
+  let timing = Timing {
+    clock_speed,
+    unthrottled,
+    frame_period: frame_period.unwrap_or(clock_speed / 60),
+  };
+
+  let mut exporter = Exporter::new(&mut profiler, frames_directory, gif_file, clock_speed)
+    .unwrap_or_else(|error| {
+      eprintln!("Emu: Error: {}", error);
+      std::process::exit(1);
+    });
+  let exit_code = match trace_file {
+    Some(trace_file) => {
+      let mut trace = std::fs::File::create(trace_file)
+        .map(std::io::LineWriter::new)
+        .unwrap_or_else(|_| {
+          eprintln!("Emu: Error: Unable to write file '{}'", trace_file);
+          std::process::exit(1);
+        });
+      use std::io::Write;
+      writeln!(trace, "# Generated by Emu").unwrap();
+      let mc = &mut exporter;
+      run(
+        Tracer { mc, trace },
+        batch_mode,
+        gui_mode,
+        timing,
+        clock_limit,
+        exit_with_tos,
+        session,
+      )
+    }
+    None => run(
+      &mut exporter,
+      batch_mode,
+      gui_mode,
+      timing,
+      clock_limit,
+      exit_with_tos,
+      session,
+    ),
+  };
+  exporter.finish().unwrap_or_else(|error| {
+    eprintln!("Emu: Error: {}", error);
+    std::process::exit(1);
+  });
+
+  let symbols = profiler.mc.symbols.as_ref();
+  let write = |file: &String, contents: String| {
+    std::fs::write(file, contents).unwrap_or_else(|_| {
+      eprintln!("Emu: Error: Unable to write file '{}'", file);
+      std::process::exit(1);
+    })
+  };
+  if let Some(profile_file) = profile_file {
+    write(profile_file, profiler.profile.report(symbols));
+  }
+  if let Some(folded_stacks_file) = folded_stacks_file {
+    write(folded_stacks_file, profiler.profile.folded_stacks(symbols));
+  }
+  if let (Some(coverage_file), Some(symbols)) = (coverage_file, symbols) {
+    write(coverage_file, profiler.profile.lcov(symbols));
+  }
+  if let Some(stack_check) = &profiler.mc.stack_check {
+    match stack_check.high_water {
+      Some((depth, address)) => eprintln!(
+        "Stack high-water mark of {:02X} bytes at {:02X}",
+        depth, address
+      ),
+      None => eprintln!("Stack high-water mark of 00 bytes"),
+    }
+  }
+
+  std::process::exit(exit_code);
+
+  fn run<MC: std::fmt::Display + Tickable>(
+    mc: MC,
+    batch_mode: bool,
+    gui_mode: bool,
+    timing: Timing,
+    clock_limit: Option<u128>,
+    exit_with_tos: bool,
+    session: Session,
+  ) -> i32 {
+    if gui_mode {
+      #[cfg(feature = "gui")]
+      gui::execute(mc, timing, session).unwrap_or_else(|error| {
+        eprintln!("Emu: Error: {}", error);
+        std::process::exit(1);
+      });
+      return 0;
+    }
+
+    if batch_mode {
+      return common::execute_headless(mc, clock_limit, exit_with_tos, session).unwrap_or_else(
+        |error| {
+          eprintln!("Emu: Error: {}", error);
+          std::process::exit(1);
+        },
+      );
+    }
 
-impl std::fmt::Display for Microprocessor {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(
-      f,
-      "IP  SP  CF\r\n{:02X}  {:02X}  {:01b} \r\n",
-      self.ip, self.sp, self.cf as u8,
-    )
+    common::execute(mc, timing, session);
+    0
   }
 }
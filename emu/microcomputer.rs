@@ -0,0 +1,522 @@
+use crate::*;
+use std::collections::VecDeque;
+
+pub struct Microcomputer {
+  pub mem: [u8; common::MEM_SIZE],           // memory
+  pub mp: Microprocessor,                    // microprocessor
+  pub halt: HaltDetector,                    // halt detection
+  pub accesses: Vec<MemoryAccess>,           // memory accesses of last instruction
+  pub clocks: u128,                          // clock count, for tracing
+  pub timings: [[Option<u128>; 2]; 0x100],   // clocks per opcode and carry flag, from microcode
+  pub retired: Option<TraceEntry>,           // last retired instruction, for tracing
+  pub symbols: Option<Symbols>,              // symbols, for display only
+  pub peripherals: Vec<Box<dyn Peripheral>>, // memory-mapped devices
+  pub stack_check: Option<StackCheck>,       // checked mode, for stack collision detection
+}
+
+// the stack base is the stack pointer at reset or as last set by `sts`. pushes and `sto`s below the
+// stack base into code, data or the display buffer are stack collisions, and the deepest of them
+// below the stack base is the high-water mark
+#[derive(Clone)]
+pub struct StackCheck {
+  pub protected: [bool; common::MEM_SIZE], // addresses of code and data
+  pub display: [bool; common::MEM_SIZE],   // addresses of the display buffer left unused
+  pub base: u8,                            // stack base
+  pub high_water: Option<(u8, u8)>,        // greatest depth below stack base, and address
+}
+
+impl StackCheck {
+  pub fn new(symbols: &Symbols) -> Self {
+    let mut protected = [false; common::MEM_SIZE];
+    let mut display = [false; common::MEM_SIZE];
+    for address in symbols.code.iter().chain(&symbols.data) {
+      protected[*address as usize] = true;
+    }
+    for address in symbols.display.iter() {
+      display[*address as usize] = true;
+    }
+    StackCheck {
+      protected,
+      display,
+      base: 0x00,
+      high_water: None,
+    }
+  }
+
+  // records a write to the stack and returns whether it collides. writes at or above the stack
+  // base, such as when the stack pointer is first moved away from `0x00`, are not part of the stack.
+  // a stack left at `0x00` lives in the display buffer by convention, so the display buffer is only
+  // protected once the stack has been moved elsewhere
+  fn write(&mut self, address: u8) -> bool {
+    let depth = self.base.wrapping_sub(address);
+    if depth == 0x00 || depth > 0x80 {
+      return false;
+    }
+    if !matches!(self.high_water, Some((high_water, _)) if high_water >= depth) {
+      self.high_water = Some((depth, address));
+    }
+    self.protected[address as usize] || self.display[address as usize] && self.base != 0x00
+  }
+}
+
+#[derive(Clone)]
+pub struct Microprocessor {
+  pub ip: u8,   // instruction pointer
+  pub sp: u8,   // stack pointer
+  pub cf: bool, // carry flag
+}
+
+impl Tickable for Microcomputer {
+  fn reset(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; common::DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) {
+    self.mp.ip = 0x00;
+    self.mp.sp = 0x00;
+    self.mp.cf = false;
+    self.halt = HaltDetector::default();
+    self.clocks = 0;
+    self.retired = None;
+    if let Some(stack_check) = &mut self.stack_check {
+      stack_check.base = 0x00;
+      stack_check.high_water = None;
+    }
+    let mut io = Io {
+      stdin,
+      stdout,
+      display,
+      controller,
+    };
+    for peripheral in self.peripherals.iter_mut() {
+      peripheral.reset(&mut self.mem, &mut io);
+    }
+  }
+
+  fn tick(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; common::DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) -> Result<u128, TickTrap> {
+    let mp = &mut self.mp;
+    let (ip, sp, cf) = (mp.ip, mp.sp, mp.cf);
+    self.accesses.clear();
+
+    // halts are detected at instruction boundaries, before the next instruction is fetched
+    if self.halt.step(mp.ip, mp.sp, mp.cf) {
+      return Err(TickTrap::ProgramHalt(self.mem[mp.sp as usize]));
+    }
+
+    let now = self.clocks; // clock count peripherals observe accesses at
+    let mut io = Io {
+      stdin,
+      stdout,
+      display,
+      controller,
+    };
+
+    macro_rules! mem_read {
+      ($address:expr) => {{
+        let address: u8 = $address;
+        let value = self
+          .peripherals
+          .iter_mut()
+          .filter(|peripheral| peripheral.claims(address))
+          .find_map(|peripheral| peripheral.read(address, now, &mut io));
+        let value = match value {
+          Some(value) => {
+            self.halt.mark_dirty();
+            value
+          }
+          None => self.mem[address as usize],
+        };
+        self.accesses.push(MemoryAccess::Read(address, value));
+        value
+      }};
+    }
+
+    macro_rules! mem_write {
+      ($address:expr, $value:expr) => {{
+        let address: u8 = $address;
+        let value = $value;
+        self.accesses.push(MemoryAccess::Write(address, value));
+        let mut consumed = false;
+        for peripheral in self.peripherals.iter_mut() {
+          if peripheral.claims(address) {
+            consumed |= peripheral.write(address, value, now, &mut io);
+          }
+        }
+        if consumed {
+          self.halt.mark_dirty();
+        } else {
+          if self.mem[address as usize] != value {
+            self.halt.mark_dirty();
+          }
+          self.mem[address as usize] = value;
+          for peripheral in self.peripherals.iter_mut() {
+            if peripheral.claims(address) && peripheral.remap(address, &mut self.mem) {
+              self.halt.mark_dirty();
+            }
+          }
+        }
+      }};
+    }
+
+    macro_rules! sp_push {
+      ($value:expr) => {{
+        let value = $value;
+        mp.sp = mp.sp.wrapping_sub(1);
+        mem_write!(mp.sp, value);
+      }};
+    }
+
+    macro_rules! sp_pop {
+      () => {{
+        let value = mem_read!(mp.sp);
+        mp.sp = mp.sp.wrapping_add(1);
+        value
+      }};
+    }
+
+    // in checked mode, stack collisions trap before the offending instruction takes effect. the
+    // address it pushes or stores to is worked out from a peek at its opcode, as fetching it may
+    // already have side effects
+    if let Some(stack_check) = &mut self.stack_check {
+      let opcode = self
+        .peripherals
+        .iter()
+        .filter(|peripheral| peripheral.claims(ip))
+        .find_map(|peripheral| peripheral.peek(ip, now, &io))
+        .unwrap_or(self.mem[ip as usize]);
+      let address = match common::opcode_to_instruction(opcode) {
+        Ok(
+          Instruction::Psh(_)
+          | Instruction::Phn(_)
+          | Instruction::Ldo(_)
+          | Instruction::Ldi
+          | Instruction::Lds,
+        ) => Some(sp.wrapping_sub(1)),
+        Ok(
+          Instruction::Swp(_)
+          | Instruction::Inc
+          | Instruction::Dec
+          | Instruction::Neg
+          | Instruction::Shl
+          | Instruction::Shr
+          | Instruction::Not
+          | Instruction::Buf
+          | Instruction::Lda,
+        ) => Some(sp), // popped then pushed
+        Ok(Instruction::Sto(ofst)) => Some(sp.wrapping_add(1).wrapping_add(ofst.get())),
+        _ => None,
+      };
+      if let Some(address) = address.filter(|address| stack_check.write(*address)) {
+        return Err(TickTrap::StackCollision(address));
+      }
+    }
+
+    let opcode = mem_read!(mp.ip);
+    mp.ip = mp.ip.wrapping_add(1);
+
+    let instruction = common::opcode_to_instruction(opcode).map_err(|_| TickTrap::IllegalOpcode)?;
+
+    // number of times the microcode sequence of the instruction is run through
+    let passes = match instruction {
+      Instruction::Psh(imm) => {
+        sp_push!(imm.get());
+        Ok(1)
+      }
+
+      Instruction::Add(size) => {
+        let addr = mp.sp.wrapping_add(size.get());
+        let res = (mem_read!(addr) as u16)
+          .wrapping_add(sp_pop!() as u16)
+          .wrapping_add(mp.cf as u16);
+        mem_write!(addr, res as u8);
+        mp.cf = res > 0xFF;
+        Ok(1)
+      }
+
+      Instruction::Sub(size) => {
+        let addr = mp.sp.wrapping_add(size.get());
+        let res = (mem_read!(addr) as u16)
+          .wrapping_sub(sp_pop!() as u16)
+          .wrapping_sub(mp.cf as u16);
+        mem_write!(addr, res as u8);
+        mp.cf = res > 0xFF;
+        Ok(1)
+      }
+
+      Instruction::Iff(size) => {
+        let addr = mp.sp.wrapping_add(size.get());
+        let top = sp_pop!();
+        mem_write!(addr, if mp.cf { top } else { mem_read!(addr) });
+        Ok(1)
+      }
+
+      Instruction::Swp(size) => {
+        let addr = mp.sp.wrapping_add(size.get());
+        let top = sp_pop!();
+        sp_push!(mem_read!(addr));
+        mem_write!(addr, top);
+        Ok(1)
+      }
+
+      Instruction::Rot(size) => {
+        let addr = mp.sp.wrapping_add(size.get());
+        let top = sp_pop!();
+        let shifted = (mem_read!(addr) as u16) << top % 8;
+        let res = (shifted & 0xFF) as u8 | (shifted >> 8) as u8;
+        mem_write!(addr, res);
+        if top != 0x00 {
+          mem_write!(mp.sp.wrapping_sub(1), 0x00); // microcode counts down in place
+        }
+        mp.cf = false;
+        Ok(top as u128 + 1)
+      }
+
+      Instruction::Orr(size) => {
+        let addr = mp.sp.wrapping_add(size.get());
+        let res = sp_pop!() | mem_read!(addr);
+        mem_write!(addr, res);
+        mp.cf = res == 0x00;
+        Ok(1)
+      }
+
+      Instruction::And(size) => {
+        let addr = mp.sp.wrapping_add(size.get());
+        let res = sp_pop!() & mem_read!(addr);
+        mem_write!(addr, res);
+        mp.cf = res == 0x00;
+        Ok(1)
+      }
+
+      Instruction::Xor(size) => {
+        let addr = mp.sp.wrapping_add(size.get());
+        let res = sp_pop!() ^ mem_read!(addr);
+        mem_write!(addr, res);
+        mp.cf = res == 0x00;
+        Ok(1)
+      }
+
+      Instruction::Xnd(size) => {
+        let addr = mp.sp.wrapping_add(size.get());
+        let res = sp_pop!() & 0x00;
+        mem_write!(addr, res);
+        mp.cf = res == 0x00;
+        Ok(1)
+      }
+
+      Instruction::Inc => {
+        sp_push!(sp_pop!().wrapping_add(1));
+        Ok(1)
+      }
+
+      Instruction::Dec => {
+        sp_push!(sp_pop!().wrapping_sub(1));
+        Ok(1)
+      }
+
+      Instruction::Neg => {
+        sp_push!(sp_pop!().wrapping_neg());
+        Ok(1)
+      }
+
+      Instruction::Shl => {
+        let top = sp_pop!();
+        sp_push!(top.wrapping_shl(1) | (mp.cf as u8));
+        mp.cf = top & 0b10000000 != 0x00;
+        Ok(1)
+      }
+
+      Instruction::Shr => {
+        let top = sp_pop!();
+        sp_push!(top.wrapping_shr(1) | (mp.cf as u8) << 7);
+        mp.cf = top & 0b00000001 != 0x00;
+        Ok(1)
+      }
+
+      Instruction::Not => {
+        let res = !sp_pop!();
+        sp_push!(res);
+        mp.cf = res == 0x00;
+        Ok(1)
+      }
+
+      Instruction::Buf => {
+        let res = sp_pop!();
+        sp_push!(res);
+        mp.cf = res == 0x00;
+        Ok(1)
+      }
+
+      Instruction::Dbg => Err(TickTrap::DebugRequest),
+
+      Instruction::Ldo(ofst) => {
+        let addr = mp.sp.wrapping_add(ofst.get());
+        sp_push!(mem_read!(addr));
+        Ok(1)
+      }
+
+      Instruction::Sto(ofst) => {
+        let top = sp_pop!();
+        let addr = mp.sp.wrapping_add(ofst.get());
+        mem_write!(addr, top);
+        Ok(1)
+      }
+
+      Instruction::Lda => {
+        sp_push!(mem_read!(sp_pop!()));
+        Ok(1)
+      }
+
+      Instruction::Sta => {
+        mem_write!(sp_pop!(), sp_pop!());
+        Ok(1)
+      }
+
+      Instruction::Ldi => {
+        sp_push!(mp.ip);
+        Ok(1)
+      }
+
+      Instruction::Sti => {
+        mp.ip = sp_pop!();
+        Ok(1)
+      }
+
+      Instruction::Lds => {
+        sp_push!(mp.sp);
+        Ok(1)
+      }
+
+      Instruction::Sts => {
+        mp.sp = sp_pop!();
+        if let Some(stack_check) = &mut self.stack_check {
+          stack_check.base = mp.sp;
+        }
+        Ok(1)
+      }
+
+      Instruction::Clc => {
+        mp.cf = false;
+        Ok(1)
+      }
+
+      Instruction::Sec => {
+        mp.cf = true;
+        Ok(1)
+      }
+
+      Instruction::Flc => {
+        mp.cf = !mp.cf;
+        Ok(1)
+      }
+
+      Instruction::Nop => Ok(1),
+
+      Instruction::Pop => {
+        mp.sp = mp.sp.wrapping_add(1);
+        Ok(1)
+      }
+
+      Instruction::Phn(nimm) => {
+        sp_push!(nimm.get());
+        Ok(1)
+      }
+    }?;
+    let clocks =
+      passes * self.timings[opcode as usize][cf as usize].ok_or(TickTrap::MicrocodeFault)?;
+
+    self.retired = Some(TraceEntry {
+      clocks: self.clocks,
+      ip,
+      sp,
+      cf,
+      instruction: common::opcode_to_instruction(opcode),
+      accesses: self.accesses.clone(),
+    });
+    self.clocks += clocks;
+
+    Ok(clocks)
+  }
+
+  fn retired(&mut self) -> Option<TraceEntry> {
+    self.retired.take()
+  }
+
+  fn save(&self, snapshot: &mut Snapshot) {
+    snapshot.put("machine", "Emu");
+    snapshot.put("clocks", self.clocks);
+    snapshot.put_bytes("mem", &self.mem);
+    snapshot.put_bytes("ip", &[self.mp.ip]);
+    snapshot.put_bytes("sp", &[self.mp.sp]);
+    snapshot.put("cf", self.mp.cf as u8);
+    self.halt.save(snapshot);
+    if let Some(stack_check) = &self.stack_check {
+      let high_water = stack_check.high_water.unwrap_or((0x00, stack_check.base));
+      snapshot.put_bytes("stack", &[stack_check.base, high_water.0, high_water.1]);
+    }
+    for peripheral in self.peripherals.iter() {
+      peripheral.save(snapshot);
+    }
+  }
+
+  fn load(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+    match snapshot.get::<String>("machine")?.as_str() {
+      "Emu" => {}
+      machine => Err(Error(format!("Snapshot was taken by {}, not Emu", machine)))?,
+    }
+    let ([ip], [sp]) = (snapshot.get_bytes("ip")?, snapshot.get_bytes("sp")?);
+    self.clocks = snapshot.get("clocks")?;
+    self.mem = snapshot.get_bytes("mem")?;
+    self.mp = Microprocessor {
+      ip,
+      sp,
+      cf: snapshot.get_bit("cf")?,
+    };
+    self.halt = HaltDetector::load(snapshot)?;
+    if let Some(stack_check) = &mut self.stack_check {
+      let [base, depth, address] = snapshot.get_bytes("stack")?;
+      stack_check.base = base;
+      stack_check.high_water = (depth != 0x00).then_some((depth, address));
+    }
+    for peripheral in self.peripherals.iter_mut() {
+      peripheral.load(snapshot)?;
+    }
+    self.accesses.clear();
+    self.retired = None;
+    Ok(())
+  }
+}
+
+impl std::fmt::Display for Microcomputer {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}\r\n{}",
+      self.mp,
+      common::render_memory(
+        &self.mem,
+        self.mp.ip,
+        self.mp.sp,
+        self.mp.cf,
+        self.symbols.as_ref(),
+      ),
+    )
+  }
+}
+
+impl std::fmt::Display for Microprocessor {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "IP  SP  CF\r\n{:02X}  {:02X}  {:01b} \r\n",
+      self.ip, self.sp, self.cf as u8,
+    )
+  }
+}
@@ -0,0 +1,285 @@
+use crate::*;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+
+// wraps a `Tickable` and attributes the clocks of every instruction it retires to the address the
+// instruction was fetched from
+pub struct Profiler<MC: Tickable> {
+  pub mc: MC,
+  pub profile: Profile,
+  pub retired: Option<TraceEntry>, // passed through to enclosing `Tickable`s
+}
+
+pub struct Profile {
+  pub executions: [u128; common::MEM_SIZE], // instructions executed per address
+  pub clocks: [u128; common::MEM_SIZE],     // clocks spent per address
+}
+
+impl<MC: Tickable> Profiler<MC> {
+  pub fn new(mc: MC) -> Self {
+    Profiler {
+      mc,
+      profile: Profile::new(),
+      retired: None,
+    }
+  }
+}
+
+impl<MC: Tickable> Tickable for Profiler<MC> {
+  fn reset(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; common::DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) {
+    self.mc.reset(stdin, stdout, display, controller);
+    self.profile = Profile::new();
+    self.retired = None;
+  }
+
+  fn tick(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; common::DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) -> Result<u128, TickTrap> {
+    let result = self.mc.tick(stdin, stdout, display, controller);
+    self.retired = self.mc.retired();
+    if let (Ok(clocks), Some(entry)) = (&result, &self.retired) {
+      self.profile.executions[entry.ip as usize] += 1;
+      self.profile.clocks[entry.ip as usize] += clocks;
+    }
+    result
+  }
+
+  fn retired(&mut self) -> Option<TraceEntry> {
+    self.retired.take()
+  }
+
+  fn save(&self, snapshot: &mut Snapshot) {
+    self.mc.save(snapshot);
+  }
+
+  fn load(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+    self.profile = Profile::new();
+    self.retired = None;
+    self.mc.load(snapshot)
+  }
+}
+
+impl<MC: Tickable + std::fmt::Display> std::fmt::Display for Profiler<MC> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.mc)
+  }
+}
+
+impl Profile {
+  pub fn new() -> Self {
+    Profile {
+      executions: [0; common::MEM_SIZE],
+      clocks: [0; common::MEM_SIZE],
+    }
+  }
+
+  // hotspots sorted by clocks spent, by address, by label and by macro. the clocks of an address
+  // count towards every macro in its expansion chain
+  pub fn report(&self, symbols: Option<&Symbols>) -> String {
+    let total_clocks: u128 = self.clocks.iter().sum();
+    let total_executions: u128 = self.executions.iter().sum();
+
+    let mut addresses: Vec<(String, u128, u128)> = vec![];
+    let mut labels: BTreeMap<String, (u128, u128)> = BTreeMap::new();
+    let mut macros: BTreeMap<String, (u128, u128)> = BTreeMap::new();
+
+    for address in 0..common::MEM_SIZE {
+      let (executions, clocks) = (self.executions[address], self.clocks[address]);
+      if executions == 0 {
+        continue;
+      }
+      let address = address as u8;
+
+      let mut location = format!("{:02X}", address);
+      if let Some(symbols) = symbols {
+        if let Some(label) = symbols.describe(address) {
+          location += &format!(" {}", label);
+        }
+        if let Some((pos, chain)) = symbols.origins.get(&address) {
+          location += &format!(" {}", pos);
+          for r#macro in chain.iter() {
+            location += &format!(" {}", r#macro);
+          }
+        }
+
+        let label = symbols.label(address).map(|(label, _)| label.to_string());
+        let entry = labels.entry(label.unwrap_or("??".to_string())).or_default();
+        *entry = (entry.0 + executions, entry.1 + clocks);
+
+        let mut chain: Vec<String> = symbols
+          .origins
+          .get(&address)
+          .map(|(_, chain)| chain.iter().map(|r#macro| r#macro.to_string()).collect())
+          .unwrap_or_default();
+        chain.sort();
+        chain.dedup(); // recursive expansions count once
+        for r#macro in chain {
+          let entry = macros.entry(r#macro).or_default();
+          *entry = (entry.0 + executions, entry.1 + clocks);
+        }
+      }
+      addresses.push((location, executions, clocks));
+    }
+
+    let mut fmt = "".to_string();
+    fmt += "# Generated by Emu\n";
+    fmt += &format!(
+      "# {} clocks over {} instructions\n",
+      total_clocks, total_executions
+    );
+
+    let mut section = |title: &str, mut rows: Vec<(String, u128, u128)>| {
+      rows.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+      fmt += &format!("\n# {}\n", title);
+      fmt += &format!(
+        "{:>10} {:>7} {:>10}  {}\n",
+        "clocks", "share", "executions", "location"
+      );
+      for (location, executions, clocks) in rows {
+        let share = clocks as f64 / std::cmp::max(total_clocks, 1) as f64 * 100.0;
+        fmt += &format!(
+          "{:>10} {:>6.2}% {:>10}  {}\n",
+          clocks, share, executions, location
+        );
+      }
+    };
+
+    section("By address", addresses);
+    if symbols.is_some() {
+      let flatten = |rows: BTreeMap<String, (u128, u128)>| {
+        rows
+          .into_iter()
+          .map(|(location, (executions, clocks))| (location, executions, clocks))
+          .collect()
+      };
+      section("By label", flatten(labels));
+      section("By macro", flatten(macros));
+    }
+
+    fmt
+  }
+
+  // one line per address in the folded stack format read by flamegraph tools, with the nearest
+  // global label as root frame followed by the macro expansion chain and the address itself
+  pub fn folded_stacks(&self, symbols: Option<&Symbols>) -> String {
+    let mut fmt = "".to_string();
+
+    for address in 0..common::MEM_SIZE {
+      if self.executions[address] == 0 {
+        continue;
+      }
+      let address = address as u8;
+
+      let mut frames: Vec<String> = vec![];
+      if let Some(symbols) = symbols {
+        if let Some((label, _)) = symbols.label(address) {
+          frames.push(label.to_string());
+        }
+        if let Some((_, chain)) = symbols.origins.get(&address) {
+          frames.extend(chain.iter().map(|r#macro| r#macro.to_string()));
+        }
+      }
+      frames.push(format!("{:02X}", address));
+
+      fmt += &format!("{} {}\n", frames.join(";"), self.clocks[address as usize]);
+    }
+
+    fmt
+  }
+
+  // tracefile in the lcov format mapping execution counts back to source lines. a line is
+  // instrumented if it produced an instruction or a byte that was executed, and its execution count
+  // is the highest among the bytes it produced. global labels are reported as functions
+  pub fn lcov(&self, symbols: &Symbols) -> String {
+    #[derive(Default)]
+    struct Coverage {
+      lines: BTreeMap<usize, u128>,          // execution count per line
+      functions: Vec<(usize, String, u128)>, // line, name and execution count per function
+    }
+
+    let mut files: BTreeMap<PathBuf, Coverage> = BTreeMap::new();
+    let source = |address: u8| {
+      let (Pos(File(path), row, _), _) = symbols.origins.get(&address)?;
+      match path.to_string_lossy().starts_with('[') {
+        true => None, // generated by the assembler itself
+        false => Some((path.clone(), *row + 1)),
+      }
+    };
+
+    for address in 0..common::MEM_SIZE {
+      let executions = self.executions[address];
+      let address = address as u8;
+      if executions == 0 && !symbols.code.contains(&address) {
+        continue;
+      }
+      if let Some((path, line)) = source(address) {
+        let count = files
+          .entry(path)
+          .or_default()
+          .lines
+          .entry(line)
+          .or_default();
+        *count = std::cmp::max(*count, executions);
+      }
+    }
+
+    for (label, &address) in symbols.labels.iter() {
+      if let Label::Global(name) = label {
+        if !symbols.code.contains(&address) {
+          continue;
+        }
+        if let Some((path, line)) = source(address) {
+          let executions = self.executions[address as usize];
+          let coverage = files.entry(path).or_default();
+          coverage.functions.push((line, name.clone(), executions));
+        }
+      }
+    }
+
+    let mut fmt = "".to_string();
+    for (path, coverage) in files {
+      fmt += "TN:\n";
+      fmt += &format!("SF:{}\n", path.display());
+      for (line, name, _) in coverage.functions.iter() {
+        fmt += &format!("FN:{},{}\n", line, name);
+      }
+      for (_, name, executions) in coverage.functions.iter() {
+        fmt += &format!("FNDA:{},{}\n", executions, name);
+      }
+      fmt += &format!("FNF:{}\n", coverage.functions.len());
+      fmt += &format!(
+        "FNH:{}\n",
+        coverage
+          .functions
+          .iter()
+          .filter(|(_, _, executions)| *executions > 0)
+          .count()
+      );
+      for (line, executions) in coverage.lines.iter() {
+        fmt += &format!("DA:{},{}\n", line, executions);
+      }
+      fmt += &format!("LF:{}\n", coverage.lines.len());
+      fmt += &format!(
+        "LH:{}\n",
+        coverage
+          .lines
+          .values()
+          .filter(|&&executions| executions > 0)
+          .count()
+      );
+      fmt += "end_of_record\n";
+    }
+
+    fmt
+  }
+}
@@ -19,24 +19,15 @@ store_bit! # store_bit(rot, addr, bit)
   # *addr = rest | bit
   or2 sta
   # return*
-set_bit! # set_bit(rot, addr)
-  # mask = 0x01 << rot
-  swp x01 sw2 ro2
-  # *addr |= mask
-  ld0 lda or2 sta
-  # return*
-clear_bit! # clear_bit(rot, addr)
-  # mask = ~0x01 << rot
-  swp x01 not sw2 ro2
-  # *addr &= mask
-  ld0 lda an2 sta
-  # return*
-flip_bit! # flip_bit(rot, addr)
-  # mask = 0x01 << rot
-  swp x01 sw2 ro2
-  # *addr ^= mask
-  ld0 lda xo2 sta
+apply_mask(mask, op)! # apply_mask(rot, addr)
+  # mask <<= rot
+  swp $mask sw2 ro2
+  # *addr = *addr op mask
+  ld0 lda $op sta
   # return*
+set_bit! !apply_mask(x01, or2) # set_bit(rot, addr)
+clear_bit! !apply_mask(x01 not, an2) # clear_bit(rot, addr)
+flip_bit! !apply_mask(x01, xo2) # flip_bit(rot, addr)
 
 nibble_addr! # (rot, addr) = nibble_addr(buffer, index)
   # addr = index // 2 + buffer
@@ -56,24 +47,9 @@ store_nibble! # store_nibble(rot, addr, nibble)
   # *addr = rest | nibble
   or2 sta
   # return*
-set_nibble! # set_nibble(rot, addr)
-  # mask = 0x0F << rot
-  swp x0F sw2 ro2
-  # *addr |= mask
-  ld0 lda or2 sta
-  # return*
-clear_nibble! # clear_nibble(rot, addr)
-  # mask = ~0x0F << rot
-  swp x0F not sw2 ro2
-  # *addr &= mask
-  ld0 lda an2 sta
-  # return*
-flip_nibble! # flip_nibble(rot, addr)
-  # mask = 0x0F << rot
-  swp x0F sw2 ro2
-  # *addr ^= mask
-  ld0 lda xo2 sta
-  # return*
+set_nibble! !apply_mask(x0F, or2) # set_nibble(rot, addr)
+clear_nibble! !apply_mask(x0F not, an2) # clear_nibble(rot, addr)
+flip_nibble! !apply_mask(x0F, xo2) # flip_nibble(rot, addr)
 
 display_char.def!
   display_char: # display_char(index, buffer, pos)
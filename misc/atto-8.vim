@@ -16,7 +16,7 @@ syntax match atto8Instruction "\<\(add\|sub\|iff\|swp\|rot\|orr\|and\|xor\|xnd\|
 syntax match atto8Instruction "\<\(ad\|su\|if\|sw\|ro\|or\|an\|xo\|xn\)[1248]\{1\}\>"
 syntax match atto8Instruction "\<\(ld\|st\)[0-9A-F]\{1\}\>"
 syntax match atto8XXX "\<x[0-9A-F]\{2\}\>"
-syntax match atto8Directive "@\<\(error\|const\|data\|dyn\|org\|[0-9A-F]\{2\}\)\>"
+syntax match atto8Directive "@\<\(error\|const\|data\|dyn\|org\|bank\|[0-9A-F]\{2\}\)\>"
 syntax match atto8MacroRef "!\<[^! ]\+\>"
 syntax match atto8MacroDef "\<[^! ]\+\>!"
 syntax match atto8LabelRef ":\<[^: ]\+\>\|\.\<[^. ]\+\>"
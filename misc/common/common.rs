@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::path::PathBuf;
 
 pub const MEM_SIZE: usize = 0x100;
@@ -8,6 +8,14 @@ pub const MIC_SIZE: usize = 0x2000; // 0x80 * 0x02 * 0x20
 pub const DISPLAY_BUFFER: usize = 0xE0;
 pub const DISPLAY_BUFFER_LEN: usize = 0x20;
 pub const STDIO_BUFFER: usize = 0x00;
+pub const BANK_REGISTER: usize = 0xDD;
+pub const BANK_WINDOW: usize = 0x80;
+pub const BANK_WINDOW_LEN: usize = 0x40;
+pub const BANK_COUNT: usize = 0x100; // the bank register selects among at most 256 banks
+pub const TIMER_REGISTER: usize = 0xDE;
+pub const RNG_REGISTER: usize = 0xDF;
+pub const TIMER_PERIOD_LOG2: u32 = 14; // the timer ticks every 16384 clock cycles
+pub const SLOW_MOTION_FACTOR: u128 = 10;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct ControlWord {
@@ -45,6 +53,260 @@ pub enum TickTrap {
   // instruction level
   IllegalOpcode,
   DebugRequest,
+  ProgramHalt(u8),    // top of stack
+  StackCollision(u8), // address written to
+}
+
+// the tick traps microcode can raise through unofficial control words. program halts and stack
+// collisions are detected by watching the machine run instead, so they have no control word
+#[derive(Clone, Copy, Debug)]
+pub enum MicrocodeTrap {
+  MicrocodeFault,
+  BusContention,
+  IllegalOpcode,
+  DebugRequest,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryAccess {
+  Read(u8, u8),  // address and value read
+  Write(u8, u8), // address and value written
+}
+
+// a program is considered halted once it enters a loop during which memory is left unaltered and
+// no input or output is performed. as the machine is then fully deterministic, it is bound to loop
+// forever. cycles are detected through Brent's algorithm on `IP`, `SP` and `CF`
+#[derive(Clone, Default)]
+pub struct HaltDetector {
+  snapshot: Option<(u8, u8, bool)>, // IP, SP and CF at last snapshot
+  steps: usize,                     // instructions since last snapshot
+  power: usize,                     // instructions until next snapshot
+  dirty: bool,                      // memory altered or input or output performed
+}
+
+impl HaltDetector {
+  pub fn mark_dirty(&mut self) {
+    self.dirty = true;
+  }
+
+  // to be called at every instruction boundary. returns whether the program has halted
+  pub fn step(&mut self, ip: u8, sp: u8, cf: bool) -> bool {
+    if self.dirty || self.snapshot.is_none() {
+      *self = HaltDetector {
+        snapshot: Some((ip, sp, cf)),
+        steps: 0,
+        power: 1,
+        dirty: false,
+      };
+      return false;
+    }
+
+    if self.snapshot == Some((ip, sp, cf)) {
+      return true;
+    }
+
+    self.steps += 1;
+    if self.steps == self.power {
+      self.snapshot = Some((ip, sp, cf));
+      self.steps = 0;
+      self.power *= 2;
+    }
+
+    false
+  }
+
+  pub fn save(&self, snapshot: &mut Snapshot) {
+    snapshot.put(
+      "halt",
+      match self.snapshot {
+        Some((ip, sp, cf)) => format!(
+          "{:02X} {:02X} {:01b} {} {} {:01b}",
+          ip, sp, cf as u8, self.steps, self.power, self.dirty as u8
+        ),
+        None => "".to_string(),
+      },
+    );
+  }
+
+  pub fn load(snapshot: &Snapshot) -> Result<Self, Error> {
+    let state: String = snapshot.get("halt")?;
+    let error = || Error("Malformed snapshot field `halt`".to_string());
+
+    match state.split_whitespace().collect::<Vec<&str>>()[..] {
+      [] => Ok(HaltDetector::default()),
+      [ip, sp, cf, steps, power, dirty] => Ok(HaltDetector {
+        snapshot: Some((
+          u8::from_str_radix(ip, 16).map_err(|_| error())?,
+          u8::from_str_radix(sp, 16).map_err(|_| error())?,
+          parse_bit(cf).ok_or_else(error)?,
+        )),
+        steps: steps.parse().map_err(|_| error())?,
+        power: power.parse().map_err(|_| error())?,
+        dirty: parse_bit(dirty).ok_or_else(error)?,
+      }),
+      _ => Err(error()),
+    }
+  }
+}
+
+// a display buffer state is considered to be shown from the clock cycle it is written at until the
+// clock cycle it is overwritten at. the time every pixel is lit is integrated over frames of
+// `frame_period` clock cycles, so that programs which rapidly flicker pixels to produce shades of
+// gray render identically regardless of host load
+pub struct DisplayModel {
+  frame_period: u128,                // clock cycles per frame
+  clocks: u128,                      // clock cycles elapsed during the current frame
+  integrated: u128,                  // clock cycles of the current frame integrated so far
+  display: [u8; DISPLAY_BUFFER_LEN], // display buffer state being shown
+  on_time: [u128; 0x100],            // clock cycles every pixel was lit for in the current frame
+  pub intensities: [u8; 0x100],      // intensity of every pixel over the last complete frame
+}
+
+impl DisplayModel {
+  pub fn new(frame_period: u128, display: &[u8; DISPLAY_BUFFER_LEN]) -> Self {
+    let mut intensities = [0x00; 0x100];
+    for (index, intensity) in intensities.iter_mut().enumerate() {
+      *intensity = pixel(display, index) as u8 * 0xFF;
+    }
+
+    DisplayModel {
+      frame_period: std::cmp::max(frame_period, 1),
+      clocks: 0,
+      integrated: 0,
+      display: *display,
+      on_time: [0; 0x100],
+      intensities,
+    }
+  }
+
+  // to be called after every tick with the clock cycles it took and the resulting display buffer
+  pub fn advance(&mut self, clocks: u128, display: &[u8; DISPLAY_BUFFER_LEN]) {
+    self.clocks += clocks;
+    while self.clocks >= self.frame_period {
+      self.integrate(self.frame_period);
+      for (intensity, on_time) in self.intensities.iter_mut().zip(self.on_time.iter_mut()) {
+        *intensity = ((*on_time * 0xFF + self.frame_period / 2) / self.frame_period) as u8;
+        *on_time = 0;
+      }
+      self.clocks -= self.frame_period;
+      self.integrated = 0;
+    }
+
+    if self.display != *display {
+      self.integrate(self.clocks);
+      self.display = *display;
+    }
+  }
+
+  // to be called once execution stops. lets `display` be shown for a whole frame, as it otherwise
+  // would be forever
+  pub fn settle(&mut self, display: &[u8; DISPLAY_BUFFER_LEN]) {
+    self.advance(0, display);
+    self.advance(2 * self.frame_period - self.clocks, display);
+  }
+
+  fn integrate(&mut self, clocks: u128) {
+    for (index, on_time) in self.on_time.iter_mut().enumerate() {
+      if pixel(&self.display, index) {
+        *on_time += clocks - self.integrated;
+      }
+    }
+    self.integrated = clocks;
+  }
+}
+
+// timing of interactive frontends
+#[derive(Clone, Copy, Debug)]
+pub struct Timing {
+  pub clock_speed: u128,  // target clock cycles per second
+  pub unthrottled: bool,  // whether to run as fast as the host allows instead
+  pub frame_period: u128, // clock cycles per display frame
+}
+
+// speeds cycled through by interactive frontends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpeedMode {
+  Normal,
+  SlowMotion,
+  Paused,
+}
+
+impl SpeedMode {
+  pub fn next(self) -> Self {
+    match self {
+      SpeedMode::Normal => SpeedMode::SlowMotion,
+      SpeedMode::SlowMotion => SpeedMode::Paused,
+      SpeedMode::Paused => SpeedMode::Normal,
+    }
+  }
+
+  // target clock speed given the configured clock speed
+  pub fn clock_speed(self, clock_speed: u128) -> u128 {
+    match self {
+      SpeedMode::Normal => clock_speed,
+      SpeedMode::SlowMotion => std::cmp::max(clock_speed / SLOW_MOTION_FACTOR, 1),
+      SpeedMode::Paused => 0,
+    }
+  }
+
+  // status line reporting effective speed `speed` relative to the configured clock speed
+  pub fn status(self, unthrottled: bool, speed: f64, clock_speed: u128) -> String {
+    let mode = match self {
+      SpeedMode::Normal if unthrottled => "Unthrottled",
+      SpeedMode::Normal => "Running",
+      SpeedMode::SlowMotion => "Slow motion",
+      SpeedMode::Paused => return "Paused".to_string(),
+    };
+
+    format!(
+      "{} at {} ({:.0}% of {})",
+      mode,
+      format_frequency(speed),
+      speed / clock_speed as f64 * 100.0,
+      format_frequency(clock_speed as f64)
+    )
+  }
+}
+
+// measures effective clock speed over windows of wall-clock time
+pub struct SpeedMeter {
+  time: std::time::Instant, // wall-clock time at start of window
+  clocks: u128,             // clock count at start of window
+}
+
+impl SpeedMeter {
+  pub fn new(clocks: u128) -> Self {
+    SpeedMeter {
+      time: std::time::Instant::now(),
+      clocks,
+    }
+  }
+
+  // returns the clock speed over the last window once it is complete
+  pub fn measure(&mut self, clocks: u128) -> Option<f64> {
+    let elapsed = self.time.elapsed();
+    if elapsed < std::time::Duration::from_millis(500) {
+      return None;
+    }
+    let speed = (clocks - self.clocks) as f64 / elapsed.as_secs_f64();
+    *self = SpeedMeter::new(clocks);
+    Some(speed)
+  }
+}
+
+pub fn format_frequency(hertz: f64) -> String {
+  match hertz {
+    _ if hertz >= 1e6 => format!("{:.2} MHz", hertz / 1e6),
+    _ if hertz >= 1e3 => format!("{:.2} kHz", hertz / 1e3),
+    _ => format!("{:.0} Hz", hertz),
+  }
+}
+
+// whether the pixel at `index = y * 0x10 + x` is lit
+pub fn pixel(display: &[u8; DISPLAY_BUFFER_LEN], index: usize) -> bool {
+  let (x, y) = (index & 0x0F, index >> 0x04);
+  let address = (x >> 0x03) | (y << 0x01);
+  display[address] >> (0x07 - (x & 0x07)) & 0x01 != 0
 }
 
 pub trait Tickable {
@@ -62,14 +324,450 @@ pub trait Tickable {
     display: &mut [u8; DISPLAY_BUFFER_LEN],
     controller: &mut u8,
   ) -> Result<u128, TickTrap>;
+  fn retired(&mut self) -> Option<TraceEntry>; // takes the instruction retired during the last tick, if any
+  fn save(&self, snapshot: &mut Snapshot); // writes machine state to `snapshot.fields`
+  fn load(&mut self, snapshot: &Snapshot) -> Result<(), Error>; // reads machine state back
+}
+
+// the host-side state peripherals have access to during a tick
+pub struct Io<'a> {
+  pub stdin: &'a mut VecDeque<u8>,
+  pub stdout: &'a mut VecDeque<u8>,
+  pub display: &'a mut [u8; DISPLAY_BUFFER_LEN],
+  pub controller: &'a mut u8,
+}
+
+// a memory-mapped device. reads and writes to the addresses a peripheral claims are dispatched to
+// it along with the clock count. a read for which a peripheral returns a value and a write which a
+// peripheral consumes do not reach memory and count as input or output for halt detection. writes
+// that do reach memory are followed by a call to `remap`, through which a peripheral may rearrange
+// memory, returning whether it did
+pub trait Peripheral {
+  fn claims(&self, address: u8) -> bool;
+  fn reset(&mut self, mem: &mut [u8; MEM_SIZE], io: &mut Io);
+  fn peek(&self, address: u8, clocks: u128, io: &Io) -> Option<u8>; // like `read` without side effects
+  fn read(&mut self, address: u8, clocks: u128, io: &mut Io) -> Option<u8>;
+  fn write(&mut self, address: u8, value: u8, clocks: u128, io: &mut Io) -> bool; // whether consumed
+  fn remap(&mut self, _address: u8, _mem: &mut [u8; MEM_SIZE]) -> bool {
+    false
+  }
+  fn save(&self, _snapshot: &mut Snapshot) {}
+  fn load(&mut self, _snapshot: &Snapshot) -> Result<(), Error> {
+    Ok(())
+  }
+  // for stepping back through history. `checkpoint` is called before every instruction and should be
+  // cheap, leaving out anything memory already holds. `rewind` undoes the instruction executed since
+  fn checkpoint(&mut self) -> Vec<u8> {
+    vec![]
+  }
+  fn rewind(&mut self, _checkpoint: &[u8]) {}
+}
+
+// the peripherals every Atto‑8 microcomputer is equipped with
+pub fn default_peripherals() -> Vec<Box<dyn Peripheral>> {
+  vec![Box::new(Stdio), Box::new(DisplayBuffer)]
+}
+
+// standard input and output, falling back to controller states when `stdin` is empty
+pub struct Stdio;
+
+impl Peripheral for Stdio {
+  fn claims(&self, address: u8) -> bool {
+    address as usize == STDIO_BUFFER
+  }
+
+  // `stdin` is initialized with the contents of the input buffer
+  fn reset(&mut self, mem: &mut [u8; MEM_SIZE], io: &mut Io) {
+    io.stdin.clear();
+    io.stdout.clear();
+    io.stdin.push_back(mem[STDIO_BUFFER]);
+  }
+
+  fn peek(&self, _address: u8, _clocks: u128, io: &Io) -> Option<u8> {
+    Some(*io.stdin.front().unwrap_or(io.controller))
+  }
+
+  fn read(&mut self, _address: u8, _clocks: u128, io: &mut Io) -> Option<u8> {
+    Some(io.stdin.pop_front().unwrap_or(*io.controller))
+  }
+
+  fn write(&mut self, _address: u8, value: u8, _clocks: u128, io: &mut Io) -> bool {
+    io.stdout.push_back(value);
+    true
+  }
+}
+
+// the display buffer, which is mirrored into `display` and otherwise behaves as memory
+pub struct DisplayBuffer;
+
+impl Peripheral for DisplayBuffer {
+  fn claims(&self, address: u8) -> bool {
+    address as usize & DISPLAY_BUFFER == DISPLAY_BUFFER
+  }
+
+  fn reset(&mut self, mem: &mut [u8; MEM_SIZE], io: &mut Io) {
+    io.display
+      .copy_from_slice(&mem[DISPLAY_BUFFER..DISPLAY_BUFFER + DISPLAY_BUFFER_LEN]);
+  }
+
+  fn peek(&self, _address: u8, _clocks: u128, _io: &Io) -> Option<u8> {
+    None
+  }
+
+  fn read(&mut self, _address: u8, _clocks: u128, _io: &mut Io) -> Option<u8> {
+    None
+  }
+
+  fn write(&mut self, address: u8, value: u8, _clocks: u128, io: &mut Io) -> bool {
+    io.display[address as usize & !DISPLAY_BUFFER] = value;
+    false
+  }
+}
+
+// optional timer and random number generator. the timer is a free-running counter that ticks every
+// `1 << TIMER_PERIOD_LOG2` clock cycles and can be written to. the random number generator is a
+// 16-bit Galois LFSR stepped eight times per read, which writes reseed. as both are derived from the
+// clock count and the seed only, execution remains deterministic
+pub struct TimerRng {
+  seed: u8,    // seed the random number generator is reset to
+  timer: u8,   // timer value at `epoch`
+  epoch: u128, // clock count of last timer write
+  lfsr: u16,   // random number generator state
+}
+
+impl TimerRng {
+  pub fn new(seed: u8) -> Self {
+    TimerRng {
+      seed,
+      timer: 0x00,
+      epoch: 0,
+      lfsr: TimerRng::seed_lfsr(seed),
+    }
+  }
+
+  // the state never becomes zero, as its upper byte is nonzero
+  fn seed_lfsr(seed: u8) -> u16 {
+    0xACE1 ^ seed as u16
+  }
+
+  fn step_lfsr(lfsr: u16) -> u16 {
+    (0..8).fold(lfsr, |lfsr, _| match lfsr & 0x01 {
+      0 => lfsr >> 1,
+      _ => lfsr >> 1 ^ 0xB400,
+    })
+  }
+
+  fn timer(&self, clocks: u128) -> u8 {
+    let ticks = clocks.saturating_sub(self.epoch) >> TIMER_PERIOD_LOG2;
+    self.timer.wrapping_add(ticks as u8)
+  }
+}
+
+impl Peripheral for TimerRng {
+  fn claims(&self, address: u8) -> bool {
+    address as usize == TIMER_REGISTER || address as usize == RNG_REGISTER
+  }
+
+  fn reset(&mut self, _mem: &mut [u8; MEM_SIZE], _io: &mut Io) {
+    *self = TimerRng::new(self.seed);
+  }
+
+  fn peek(&self, address: u8, clocks: u128, _io: &Io) -> Option<u8> {
+    match address as usize {
+      TIMER_REGISTER => Some(self.timer(clocks)),
+      _ => Some(TimerRng::step_lfsr(self.lfsr) as u8),
+    }
+  }
+
+  fn read(&mut self, address: u8, clocks: u128, io: &mut Io) -> Option<u8> {
+    let value = self.peek(address, clocks, io);
+    if address as usize == RNG_REGISTER {
+      self.lfsr = TimerRng::step_lfsr(self.lfsr);
+    }
+    value
+  }
+
+  fn write(&mut self, address: u8, value: u8, clocks: u128, _io: &mut Io) -> bool {
+    match address as usize {
+      TIMER_REGISTER => (self.timer, self.epoch) = (value, clocks),
+      _ => self.lfsr = TimerRng::seed_lfsr(value),
+    }
+    true
+  }
+
+  fn save(&self, snapshot: &mut Snapshot) {
+    snapshot.put("timer", format!("{:02X} {}", self.timer, self.epoch));
+    snapshot.put("rng", format!("{:04X}", self.lfsr));
+  }
+
+  fn load(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+    let timer: String = snapshot.get("timer")?;
+    let rng: String = snapshot.get("rng")?;
+    let error = || Error("Malformed snapshot field `timer` or `rng`".to_string());
+
+    match timer.split_whitespace().collect::<Vec<&str>>()[..] {
+      [timer, epoch] => {
+        self.timer = u8::from_str_radix(timer, 16).map_err(|_| error())?;
+        self.epoch = epoch.parse().map_err(|_| error())?;
+      }
+      _ => Err(error())?,
+    }
+    self.lfsr = u16::from_str_radix(&rng, 16)
+      .ok()
+      .filter(|lfsr| *lfsr != 0x0000)
+      .ok_or_else(error)?;
+    Ok(())
+  }
+
+  fn checkpoint(&mut self) -> Vec<u8> {
+    std::iter::once(self.timer)
+      .chain(self.epoch.to_le_bytes())
+      .chain(self.lfsr.to_le_bytes())
+      .collect()
+  }
+
+  fn rewind(&mut self, checkpoint: &[u8]) {
+    let (timer, rest) = checkpoint.split_first().unwrap();
+    let (epoch, lfsr) = rest.split_at(std::mem::size_of::<u128>());
+    self.timer = *timer;
+    self.epoch = u128::from_le_bytes(epoch.try_into().unwrap());
+    self.lfsr = u16::from_le_bytes(lfsr.try_into().unwrap());
+  }
+}
+
+// optional bank switching. writing to the bank register maps the bank it selects, modulo the number
+// of banks, into the bank window. as the mapped bank always lives in memory, the copy of it held in
+// `banks` is stale until it is unmapped, and microcomputers need not know about banks. unmapping a
+// bank is the only way its contents in `banks` change, so stepping back only needs the mapped bank
+// and, across a remap, the previous contents of the bank unmapped
+pub struct BankSwitch {
+  banks: Vec<[u8; BANK_WINDOW_LEN]>, // contents of every bank
+  bank: usize,                       // bank mapped into the bank window
+  unmapped: Option<(usize, [u8; BANK_WINDOW_LEN])>, // bank last unmapped and its previous contents
+}
+
+impl BankSwitch {
+  // `banks` are the contents of banks 1 and onward, bank 0 being part of the memory image
+  pub fn new(banks: Vec<[u8; BANK_WINDOW_LEN]>) -> Self {
+    BankSwitch {
+      banks: std::iter::once([0x00; BANK_WINDOW_LEN])
+        .chain(banks)
+        .collect(),
+      bank: 0,
+      unmapped: None,
+    }
+  }
+
+  fn map(&mut self, bank: usize, mem: &mut [u8; MEM_SIZE]) -> bool {
+    if bank == self.bank {
+      return false;
+    }
+    let window = &mut mem[BANK_WINDOW..BANK_WINDOW + BANK_WINDOW_LEN];
+    self.unmapped = Some((self.bank, self.banks[self.bank]));
+    self.banks[self.bank].copy_from_slice(window);
+    window.copy_from_slice(&self.banks[bank]);
+    self.bank = bank;
+    true
+  }
+}
+
+impl Peripheral for BankSwitch {
+  fn claims(&self, address: u8) -> bool {
+    address as usize == BANK_REGISTER
+  }
+
+  // the contents of banks are retained across resets, just like memory
+  fn reset(&mut self, mem: &mut [u8; MEM_SIZE], _io: &mut Io) {
+    self.map(0, mem);
+    self.unmapped = None;
+    mem[BANK_REGISTER] = 0x00;
+  }
+
+  fn peek(&self, _address: u8, _clocks: u128, _io: &Io) -> Option<u8> {
+    None
+  }
+
+  fn read(&mut self, _address: u8, _clocks: u128, _io: &mut Io) -> Option<u8> {
+    None
+  }
+
+  fn write(&mut self, _address: u8, _value: u8, _clocks: u128, _io: &mut Io) -> bool {
+    false
+  }
+
+  fn remap(&mut self, _address: u8, mem: &mut [u8; MEM_SIZE]) -> bool {
+    self.map(mem[BANK_REGISTER] as usize % self.banks.len(), mem)
+  }
+
+  fn save(&self, snapshot: &mut Snapshot) {
+    snapshot.put("bank", format!("{:02X}", self.bank));
+    snapshot.put_bytes("banks", &self.banks.concat());
+  }
+
+  fn load(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+    let error = || Error("Malformed snapshot field `bank` or `banks`".to_string());
+    let bank = usize::from_str_radix(&snapshot.get::<String>("bank")?, 16).map_err(|_| error())?;
+    let banks = parse_bytes(&snapshot.get::<String>("banks")?).ok_or_else(error)?;
+
+    if bank >= self.banks.len() || banks.len() != self.banks.len() * BANK_WINDOW_LEN {
+      Err(error())?
+    }
+    for (index, bank) in banks.chunks(BANK_WINDOW_LEN).enumerate() {
+      self.banks[index].copy_from_slice(bank);
+    }
+    self.bank = bank;
+    self.unmapped = None;
+    Ok(())
+  }
+
+  // the remap made by the instruction executed since the previous checkpoint, if any, is recorded
+  // into this checkpoint and undone when rewinding past the previous one
+  fn checkpoint(&mut self) -> Vec<u8> {
+    let mut checkpoint = vec![self.bank as u8];
+    if let Some((bank, contents)) = self.unmapped.take() {
+      checkpoint.push(bank as u8);
+      checkpoint.extend(contents);
+    }
+    checkpoint
+  }
+
+  fn rewind(&mut self, checkpoint: &[u8]) {
+    if let Some((bank, contents)) = self.unmapped.take() {
+      self.banks[bank] = contents;
+    }
+    self.bank = checkpoint[0] as usize;
+    self.unmapped = match checkpoint[1..] {
+      [bank, ref contents @ ..] => Some((bank as usize, contents.try_into().unwrap())),
+      [] => None,
+    };
+  }
+}
+
+// splits a memory image into the initial contents of memory and the contents of any further banks,
+// which follow it one bank window at a time
+pub fn split_memory_image(image: Vec<u8>) -> Option<([u8; MEM_SIZE], Vec<[u8; BANK_WINDOW_LEN]>)> {
+  let banks = image.get(MEM_SIZE..)?;
+  if banks.len() % BANK_WINDOW_LEN != 0 || banks.len() / BANK_WINDOW_LEN >= BANK_COUNT {
+    return None;
+  }
+  Some((
+    image[..MEM_SIZE].try_into().unwrap(),
+    banks
+      .chunks(BANK_WINDOW_LEN)
+      .map(|bank| bank.try_into().unwrap())
+      .collect(),
+  ))
+}
+
+// an executed instruction along with the machine state it began executing in
+#[derive(Clone, Eq, PartialEq)]
+pub struct TraceEntry {
+  pub clocks: u128, // clock count at which the instruction began
+  pub ip: u8,       // instruction pointer
+  pub sp: u8,       // stack pointer
+  pub cf: bool,     // carry flag
+  pub instruction: Result<Instruction, u8>, // decoded instruction
+  pub accesses: Vec<MemoryAccess>, // memory accesses, starting with the instruction fetch
+}
+
+// wraps a `Tickable` and writes a trace entry to `trace` for every instruction it retires
+pub struct Tracer<MC: Tickable, W: std::io::Write> {
+  pub mc: MC,
+  pub trace: W,
+}
+
+impl<MC: Tickable, W: std::io::Write> Tickable for Tracer<MC, W> {
+  fn reset(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) {
+    self.mc.reset(stdin, stdout, display, controller);
+  }
+
+  fn tick(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) -> Result<u128, TickTrap> {
+    let result = self.mc.tick(stdin, stdout, display, controller);
+    if let Some(entry) = self.mc.retired() {
+      writeln!(self.trace, "{}", entry).unwrap();
+    }
+    result
+  }
+
+  fn retired(&mut self) -> Option<TraceEntry> {
+    None // already consumed by `tick`
+  }
+
+  fn save(&self, snapshot: &mut Snapshot) {
+    self.mc.save(snapshot);
+  }
+
+  fn load(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+    self.mc.load(snapshot)
+  }
 }
 
-pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, clock_speed: u128) {
+// allows a `Tickable` to be lent out and inspected once execution is over
+impl<MC: Tickable> Tickable for &mut MC {
+  fn reset(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) {
+    (**self).reset(stdin, stdout, display, controller);
+  }
+
+  fn tick(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) -> Result<u128, TickTrap> {
+    (**self).tick(stdin, stdout, display, controller)
+  }
+
+  fn retired(&mut self) -> Option<TraceEntry> {
+    (**self).retired()
+  }
+
+  fn save(&self, snapshot: &mut Snapshot) {
+    (**self).save(snapshot);
+  }
+
+  fn load(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+    (**self).load(snapshot)
+  }
+}
+
+impl<MC: Tickable + std::fmt::Display, W: std::io::Write> std::fmt::Display for Tracer<MC, W> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.mc)
+  }
+}
+
+pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, timing: Timing, mut session: Session) {
+  let Timing {
+    clock_speed,
+    unthrottled,
+    frame_period,
+  } = timing;
   let mut current_clocks = 0;
+  let mut halted = false;
   let mut initial_time = std::time::Instant::now();
   let mut next_call_clocks = 0;
-  let mut next_stdin_clocks = 0;
-  let mut next_stdout_clocks = 0;
+  let mut next_refresh_time = std::time::Instant::now();
+  let mut refresh = false;
+  let mut speed_mode = SpeedMode::Normal;
   let mut controller_timestamps = [None; 8];
   let mut status_line = "".to_string();
   let mut debug_mode = false;
@@ -78,23 +776,52 @@ pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, clock_speed: u128)
   let mut stdin = VecDeque::new();
   let mut stdout = VecDeque::new();
   let mut display = [0x00; DISPLAY_BUFFER_LEN];
+  let mut controller = 0x00;
+
+  // clock count since reset, for recording and replaying input. unlike `current_clocks`, it is
+  // not reset in debug mode
+  let mut total_clocks = 0;
+
+  match session.resume.take() {
+    Some(snapshot) => {
+      total_clocks = snapshot.get("clocks").unwrap_or(0);
+      (stdin, stdout) = (snapshot.stdin, snapshot.stdout);
+      (display, controller) = (snapshot.display, snapshot.controller);
+    }
+    None => mc.reset(&mut stdin, &mut stdout, &mut display, &mut controller),
+  }
+
+  let mut display_model = DisplayModel::new(frame_period, &display);
+  let mut speed_meter = SpeedMeter::new(total_clocks);
 
-  mc.reset(&mut stdin, &mut stdout, &mut display, &mut 0x00);
+  // live input is ignored while replaying so that execution stays deterministic
+  let replaying = !session.replay.is_empty();
+  let mut recorded_controller = controller;
 
   // this call will switch the termital to raw mode
   let input_channel = spawn_input_channel();
 
   loop {
-    let mut controller = controller_timestamps
-      .iter()
-      .enumerate()
-      .fold(0x00, |acc, (index, timestamp)| {
-        acc | ((timestamp.is_some() as u8) << index)
-      });
+    let target_speed = speed_mode.clock_speed(clock_speed);
+    let throttled = !(unthrottled && speed_mode == SpeedMode::Normal);
+    let idle = halted || speed_mode == SpeedMode::Paused;
+
+    if !replaying {
+      controller = controller_timestamps
+        .iter()
+        .enumerate()
+        .fold(0x00, |acc, (index, timestamp)| {
+          acc | ((timestamp.is_some() as u8) << index)
+        });
+    }
 
     // call `std::Instant::now()` at most 1000 times per second
-    if next_call_clocks <= current_clocks || debug_mode {
-      next_call_clocks += if debug_mode { 0 } else { clock_speed / 1000 };
+    if next_call_clocks <= current_clocks || debug_mode || idle {
+      next_call_clocks += if debug_mode || idle {
+        0
+      } else {
+        target_speed / 1000
+      };
 
       let timestamp_threshold = std::time::Duration::from_millis(200);
       controller_timestamps = controller_timestamps
@@ -106,29 +833,33 @@ pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, clock_speed: u128)
         .try_into()
         .unwrap();
 
-      let realtime = std::cmp::max(initial_time.elapsed().as_millis(), 1); // prevent division by zero
-      let realtime_offset = (1000 * current_clocks / clock_speed) as i128 - realtime as i128;
-      let realtime_ratio = realtime_offset as f64 / realtime as f64;
-      std::thread::sleep(std::time::Duration::from_millis(
-        std::cmp::max(realtime_offset, 0) as u64,
-      ));
+      if idle {
+        // nothing is being executed, so wait for the next refresh rather than spinning
+        std::thread::sleep(next_refresh_time.saturating_duration_since(std::time::Instant::now()));
+      } else if throttled {
+        let realtime = initial_time.elapsed().as_millis();
+        let realtime_offset = (1000 * current_clocks / target_speed) as i128 - realtime as i128;
+        std::thread::sleep(std::time::Duration::from_millis(
+          std::cmp::max(realtime_offset, 0) as u64,
+        ));
+      }
+
+      if let Some(speed) = speed_meter.measure(total_clocks) {
+        if !debug_mode && !halted {
+          status_line = speed_mode.status(unthrottled, speed, clock_speed);
+        }
+      }
 
-      if !debug_mode {
-        let realtime_tolerance = 0.01;
-        status_line = if -realtime_ratio > realtime_tolerance {
-          format!("Execution behind by {:.0}%", -realtime_ratio * 100.0)
-        } else if realtime_ratio > realtime_tolerance {
-          format!("Execution ahead by {:.0}%", realtime_ratio * 100.0)
-        } else {
-          format!("Execution on time")
-        };
+      // refresh at most 60 times per second of wall-clock time, as rendering is expensive
+      let now = std::time::Instant::now();
+      if next_refresh_time <= now {
+        next_refresh_time = now + std::time::Duration::from_millis(1000 / 60);
+        refresh = true;
       }
     }
 
     // read input at most 60 times per second
-    if next_stdin_clocks <= current_clocks || debug_mode {
-      next_stdin_clocks += if debug_mode { 0 } else { clock_speed / 60 };
-
+    if refresh || debug_mode {
       'until_empty: loop {
         use std::sync::mpsc::TryRecvError;
         match input_channel.try_recv() {
@@ -145,6 +876,23 @@ pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, clock_speed: u128)
             status_line = "Force debug".to_string();
           }
 
+          Ok(console::Key::Insert) => {
+            let snapshot = snapshot(&mc, &stdin, &stdout, &display, controller);
+            status_line = save_snapshot(snapshot, session.snapshot_file);
+          }
+
+          Ok(console::Key::BackTab) => {
+            speed_mode = speed_mode.next();
+            let speed = speed_mode.clock_speed(clock_speed) as f64;
+            status_line = speed_mode.status(unthrottled, speed, clock_speed);
+            speed_meter = SpeedMeter::new(total_clocks);
+            initial_time = std::time::Instant::now();
+            current_clocks = 0;
+            next_call_clocks = 0;
+          }
+
+          Ok(_) if replaying => {}
+
           Ok(key) => {
             let keys = [
               console::Key::ArrowUp,
@@ -166,14 +914,18 @@ pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, clock_speed: u128)
               .try_into()
               .unwrap();
 
-            stdin.extend(match key {
+            let input = match key {
               console::Key::Char(c) => vec![c as u8],
               console::Key::Backspace => vec![0x08],
               console::Key::Enter => vec![0x0A],
               console::Key::Tab => vec![0x09],
               console::Key::Del => vec![0x7F],
               _ => vec![],
-            });
+            };
+            for byte in input.iter() {
+              session.record(total_clocks, Input::Stdin(*byte));
+            }
+            stdin.extend(input);
           }
 
           Err(TryRecvError::Empty) => break 'until_empty,
@@ -183,8 +935,8 @@ pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, clock_speed: u128)
     }
 
     // write output at most 60 times per second
-    if next_stdout_clocks <= current_clocks || debug_mode {
-      next_stdout_clocks += if debug_mode { 0 } else { clock_speed / 60 };
+    if refresh || debug_mode {
+      refresh = false;
 
       stdout = stdout
         .into_iter()
@@ -213,7 +965,7 @@ pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, clock_speed: u128)
         print!("{}", mc);
       } else {
         print!("\r\n");
-        print!("{}", render_display(&display));
+        print!("{}", render_display(&display_model.intensities));
         print!("{}", render_controller(&controller));
       }
       print!("\r\n");
@@ -240,6 +992,12 @@ pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, clock_speed: u128)
             break 'until_valid;
           }
 
+          Ok(console::Key::Insert) => {
+            let snapshot = snapshot(&mc, &stdin, &stdout, &display, controller);
+            status_line = save_snapshot(snapshot, session.snapshot_file);
+            break 'until_valid;
+          }
+
           _ => continue 'until_valid,
         }
       }
@@ -248,43 +1006,222 @@ pub fn execute<MC: std::fmt::Display + Tickable>(mut mc: MC, clock_speed: u128)
       initial_time = std::time::Instant::now();
       current_clocks = 0;
       next_call_clocks = 0;
-      next_stdin_clocks = 0;
-      next_stdout_clocks = 0;
+      speed_meter = SpeedMeter::new(total_clocks);
+    }
+
+    if idle {
+      continue;
+    }
+
+    session.replay(total_clocks, &mut stdin, &mut controller);
+    if controller != recorded_controller {
+      session.record(total_clocks, Input::Controller(controller));
+      recorded_controller = controller;
     }
 
     match mc.tick(&mut stdin, &mut stdout, &mut display, &mut controller) {
       Ok(clocks) => {
         current_clocks += clocks;
+        total_clocks += clocks;
+        display_model.advance(clocks, &display);
+      }
+      Err(tick_trap @ TickTrap::ProgramHalt(_)) => {
+        // keep the display visible rather than entering debug mode
+        if !halted {
+          // draw the halt status right away rather than on the next timed refresh
+          display_model.settle(&display);
+          refresh = true;
+        }
+        halted = true;
+        status_line = tick_trap.to_string();
       }
       Err(tick_trap) => {
         debug_mode = true;
-        status_line = match tick_trap {
-          TickTrap::MicrocodeFault => format!("Microcode fault"),
-          TickTrap::BusContention => format!("Bus contention"),
-          TickTrap::IllegalOpcode => format!("Illegal opcode"),
-          TickTrap::DebugRequest => format!("Debug request"),
+        status_line = tick_trap.to_string();
+      }
+    };
+  }
+
+  use std::sync::mpsc;
+  use std::sync::mpsc::Receiver;
+  fn spawn_input_channel() -> Receiver<console::Key> {
+    let stdout = console::Term::stdout();
+
+    let (tx, rx) = mpsc::channel::<console::Key>();
+    std::thread::spawn(move || loop {
+      if let Ok(key) = stdout.read_key() {
+        tx.send(key).unwrap();
+      }
+    });
+
+    rx
+  }
+}
+
+pub fn execute_headless<MC: Tickable>(
+  mut mc: MC,
+  clock_limit: Option<u128>,
+  exit_with_tos: bool,
+  mut session: Session,
+) -> Result<i32, Error> {
+  let mut current_clocks = 0;
+
+  let mut stdin = VecDeque::new();
+  let mut stdout = VecDeque::new();
+  let mut display = [0x00; DISPLAY_BUFFER_LEN];
+  let mut controller = 0x00;
+
+  match session.resume.take() {
+    Some(snapshot) => {
+      current_clocks = snapshot.get("clocks").unwrap_or(0);
+      (stdin, stdout) = (snapshot.stdin, snapshot.stdout);
+      (display, controller) = (snapshot.display, snapshot.controller);
+    }
+    None => mc.reset(&mut stdin, &mut stdout, &mut display, &mut controller),
+  }
+
+  // standard input is read in its entirety before execution begins
+  let mut input = vec![];
+  use std::io::{Read, Write};
+  std::io::stdin()
+    .read_to_end(&mut input)
+    .map_err(|_| Error("Unable to read standard input".to_string()))?;
+  for byte in input.iter() {
+    session.record(current_clocks, Input::Stdin(*byte));
+  }
+  stdin.extend(input);
+
+  let mut recorded_controller = controller;
+
+  // a closed `stdout`, as when piping into `head`, stops execution rather than being an error
+  let mut output = std::io::stdout().lock();
+  let output_error = |error: std::io::Error| match error.kind() {
+    std::io::ErrorKind::BrokenPipe => Ok(()),
+    _ => Err(Error("Unable to write to standard output".to_string())),
+  };
+
+  let (status, exit_code) = loop {
+    if clock_limit.is_some_and(|clock_limit| current_clocks >= clock_limit) {
+      break ("Clock limit reached".to_string(), 2);
+    }
+
+    session.replay(current_clocks, &mut stdin, &mut controller);
+    if controller != recorded_controller {
+      session.record(current_clocks, Input::Controller(controller));
+      recorded_controller = controller;
+    }
+
+    let result = mc.tick(&mut stdin, &mut stdout, &mut display, &mut controller);
+
+    let written = output.write_all(stdout.make_contiguous());
+    stdout.clear();
+    if let Err(error) = written {
+      output_error(error)?;
+      break ("Standard output closed".to_string(), 0);
+    }
+
+    match result {
+      Ok(clocks) => current_clocks += clocks,
+      Err(tick_trap) => {
+        break (
+          tick_trap.to_string(),
+          match tick_trap {
+            TickTrap::MicrocodeFault => 3,
+            TickTrap::BusContention => 4,
+            TickTrap::IllegalOpcode => 5,
+            TickTrap::DebugRequest => 6,
+            TickTrap::StackCollision(_) => 7,
+            TickTrap::ProgramHalt(tos) => match exit_with_tos {
+              true => tos as i32,
+              false => 0,
+            },
+          },
+        )
+      }
+    };
+  };
+
+  output.flush().or_else(output_error)?;
+  eprintln!("{} after {} clocks", status, current_clocks);
+
+  if let Some(snapshot_file) = session.snapshot_file {
+    let snapshot = snapshot(&mc, &stdin, &stdout, &display, controller);
+    write_snapshot(&snapshot, snapshot_file)
+      .map_err(|_| Error(format!("Unable to write file '{}'", snapshot_file)))?;
+  }
+
+  Ok(exit_code)
+}
+
+impl Session<'_> {
+  // feeds back the recorded input events due at clock count `clocks`. controller changes are
+  // recorded by the caller, so only standard input is recorded anew here
+  pub fn replay(&mut self, clocks: u128, stdin: &mut VecDeque<u8>, controller: &mut u8) {
+    while let Some(event) = self.replay.front().filter(|event| event.clocks <= clocks) {
+      let event = *event;
+      self.replay.pop_front();
+      match event.input {
+        Input::Stdin(byte) => {
+          self.record(clocks, Input::Stdin(byte));
+          stdin.push_back(byte);
         }
+        Input::Controller(state) => *controller = state,
       }
-    };
+    }
   }
 
-  use std::sync::mpsc;
-  use std::sync::mpsc::Receiver;
-  fn spawn_input_channel() -> Receiver<console::Key> {
-    let stdout = console::Term::stdout();
+  pub fn record(&mut self, clocks: u128, input: Input) {
+    if let Some(record) = &mut self.record {
+      use std::io::Write;
+      writeln!(record, "{}", InputEvent { clocks, input }).unwrap();
+    }
+  }
+}
 
-    let (tx, rx) = mpsc::channel::<console::Key>();
-    std::thread::spawn(move || loop {
-      if let Ok(key) = stdout.read_key() {
-        tx.send(key).unwrap();
-      }
-    });
+pub fn snapshot<MC: Tickable>(
+  mc: &MC,
+  stdin: &VecDeque<u8>,
+  stdout: &VecDeque<u8>,
+  display: &[u8; DISPLAY_BUFFER_LEN],
+  controller: u8,
+) -> Snapshot {
+  let mut snapshot = Snapshot {
+    stdin: stdin.clone(),
+    stdout: stdout.clone(),
+    display: *display,
+    controller,
+    fields: BTreeMap::new(),
+  };
+  mc.save(&mut snapshot);
+  snapshot
+}
 
-    rx
+// writes `snapshot` to `snapshot_file` if any, returning a status line
+pub fn save_snapshot(snapshot: Snapshot, snapshot_file: Option<&String>) -> String {
+  match snapshot_file {
+    Some(snapshot_file) => match write_snapshot(&snapshot, snapshot_file) {
+      Ok(()) => format!("Snapshot saved to '{}'", snapshot_file),
+      Err(_) => format!("Unable to write file '{}'", snapshot_file),
+    },
+    None => "No snapshot file".to_string(),
   }
 }
 
-pub fn render_memory(memory: &[u8; MEM_SIZE], ip: u8, sp: u8, cf: bool) -> String {
+fn write_snapshot(snapshot: &Snapshot, snapshot_file: &String) -> std::io::Result<()> {
+  let machine: String = snapshot.get("machine").unwrap_or_default();
+  std::fs::write(
+    snapshot_file,
+    format!("# Generated by {}\n\n{}", machine, snapshot),
+  )
+}
+
+pub fn render_memory(
+  memory: &[u8; MEM_SIZE],
+  ip: u8,
+  sp: u8,
+  cf: bool,
+  symbols: Option<&Symbols>,
+) -> String {
   let mut fmt = "".to_string();
 
   fmt += "MEM\r\n";
@@ -308,10 +1245,28 @@ pub fn render_memory(memory: &[u8; MEM_SIZE], ip: u8, sp: u8, cf: bool) -> Strin
     fmt += "\r\n";
   }
 
+  if let Some(symbols) = symbols {
+    fmt += &symbols
+      .describe(ip)
+      .unwrap_or_else(|| format!("{:02X}", ip));
+    if let Some((pos, macros)) = symbols.origins.get(&ip) {
+      fmt += &format!(" {}", pos);
+      for r#macro in macros.iter() {
+        fmt += &format!(" {}", r#macro);
+      }
+    }
+    fmt += "\r\n";
+    if let Some(source_line) = symbols.source_line(ip) {
+      fmt += &format!("{}\r\n", source_line);
+    }
+  }
+
   fmt
 }
 
-pub fn render_display(display: &[u8; DISPLAY_BUFFER_LEN]) -> String {
+// pixels are rendered in pairs as upper half blocks, the upper pixel as the foreground color and
+// the lower pixel as the background color, both from the grayscale ramp of 256-color terminals
+pub fn render_display(intensities: &[u8; 0x100]) -> String {
   let mut fmt = "".to_string();
 
   // https://en.wikipedia.org/wiki/Block_Elements
@@ -320,23 +1275,23 @@ pub fn render_display(display: &[u8; DISPLAY_BUFFER_LEN]) -> String {
   let col_left: &str = " ";
   let col_right: &str = " ";
 
+  fn color(intensity: u8) -> u8 {
+    match intensity {
+      0x00..=0x03 => 16,  // black
+      0xF7..=0xFF => 231, // white
+      _ => 232 + std::cmp::min((intensity - 0x03) / 10, 23),
+    }
+  }
+
   fmt += &line_top;
   for y in (0..0x10).step_by(2) {
     fmt += &col_left;
     for x in 0..0x10 {
-      let mut pixel_pair = 0;
-      for y2 in 0..2 {
-        let address: u8 = (x >> 0x03) | ((y + y2) << 0x01);
-        let pixel = display[address as usize] >> (0x07 - (x & 0x07)) & 0x01;
-        pixel_pair |= pixel << y2;
-      }
-      fmt += match pixel_pair {
-        0b00 => " ",
-        0b01 => "\u{2580}",
-        0b10 => "\u{2584}",
-        0b11 => "\u{2588}",
-        _ => unreachable!(),
-      };
+      let style = console::Style::new()
+        .color256(color(intensities[y * 0x10 + x]))
+        .on_color256(color(intensities[(y + 1) * 0x10 + x]))
+        .force_styling(true);
+      fmt += &style.apply_to("\u{2580}").to_string();
     }
     fmt += &col_right;
     fmt += "\r\n";
@@ -429,16 +1384,60 @@ pub fn u16_into_result(u16: u16) -> Result<ControlWord, TickTrap> {
   }
 }
 
-pub fn result_into_u16(result: Result<ControlWord, TickTrap>) -> u16 {
+pub fn result_into_u16(result: Result<ControlWord, MicrocodeTrap>) -> u16 {
   match result {
-    Err(TickTrap::MicrocodeFault) => MICROCODE_FAULT_SENTINEL,
-    Err(TickTrap::BusContention) => BUS_CONTENTION_SENTINEL,
-    Err(TickTrap::IllegalOpcode) => ILLEGAL_OPCODE_SENTINEL,
-    Err(TickTrap::DebugRequest) => DEBUG_REQUEST_SENTINEL,
+    Err(MicrocodeTrap::MicrocodeFault) => MICROCODE_FAULT_SENTINEL,
+    Err(MicrocodeTrap::BusContention) => BUS_CONTENTION_SENTINEL,
+    Err(MicrocodeTrap::IllegalOpcode) => ILLEGAL_OPCODE_SENTINEL,
+    Err(MicrocodeTrap::DebugRequest) => DEBUG_REQUEST_SENTINEL,
     Ok(control_word) => control_word.into(),
   }
 }
 
+// derives the clock count of every opcode from a microcode image, indexed by opcode then by carry
+// flag. a sequence takes one clock per step preceding the step that clears the step counter, as
+// that step is merged into the first step of the next sequence. sequences that never clear the
+// step counter, such as those of illegal opcodes, are `None`
+pub fn microcode_to_clocks(microcode: &[u16; MIC_SIZE]) -> [[Option<u128>; 2]; 0x100] {
+  let mut clocks = [[None; 2]; 0x100];
+  for (opcode, clocks) in clocks.iter_mut().enumerate() {
+    let il = match opcode & 0x80 {
+      0b0 => opcode | 0xF0, // map `psh` to `phn` as both have equivalent microcode
+      _ => opcode,          // not `psh`; pass through
+    };
+    let il = il & (0x80 - 1); // ignore `psh`s as they have been mapped to `phn`s
+    for (cf, clocks) in clocks.iter_mut().enumerate() {
+      *clocks = (0..0x20)
+        .map(|sc| u16_into_result(microcode[(il * 0x02 * 0x20) | (cf * 0x20) | sc]))
+        .take_while(|control_word| control_word.is_ok())
+        .position(|control_word| {
+          matches!(
+            control_word,
+            Ok(ControlWord {
+              clr_sc: Signal::Active,
+              ..
+            })
+          )
+        })
+        .map(|sc| sc as u128);
+    }
+  }
+  clocks
+}
+
+impl std::fmt::Display for TickTrap {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TickTrap::MicrocodeFault => write!(f, "Microcode fault"),
+      TickTrap::BusContention => write!(f, "Bus contention"),
+      TickTrap::IllegalOpcode => write!(f, "Illegal opcode"),
+      TickTrap::DebugRequest => write!(f, "Debug request"),
+      TickTrap::ProgramHalt(_) => write!(f, "Program halted"),
+      TickTrap::StackCollision(address) => write!(f, "Stack collision at {:02X}", address),
+    }
+  }
+}
+
 impl std::fmt::Display for Signal {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
@@ -448,7 +1447,7 @@ impl std::fmt::Display for Signal {
   }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq, Hash)]
 pub struct File(pub PathBuf);
 
 #[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -460,28 +1459,95 @@ pub enum Label {
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub struct Macro(pub String);
 
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Param(pub String);
+
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Constant(pub String);
+
 #[derive(Clone, Eq, PartialEq)]
 pub struct Error(pub String);
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq, Hash)]
 pub struct Pos(pub File, pub usize, pub usize);
 
 #[derive(Clone, Eq, PartialEq)]
 pub struct Mnemonic(pub String);
 
+// the contents of a symbol file, as emitted by Asm. maps addresses back to labels and to the
+// source position and macro expansion chain of every byte of a memory image
+#[derive(Clone, Default)]
+pub struct Symbols {
+  pub labels: BTreeMap<Label, u8>,              // label definitions
+  pub data: BTreeSet<u8>,                       // addresses of raw data bytes
+  pub code: BTreeSet<u8>,                       // addresses of instructions
+  pub display: BTreeSet<u8>,                    // addresses of the display buffer left unused
+  pub origins: BTreeMap<u8, (Pos, Vec<Macro>)>, // source position and macro expansion chain
+  pub sources: HashMap<PathBuf, Option<Vec<String>>>, // source file lines, read when parsing
+}
+
+// the state of a machine and of its peripherals, from which execution can be resumed. peripheral
+// state is common to all machines whereas machine state is written by `Tickable::save`
+#[derive(Clone, Default)]
+pub struct Snapshot {
+  pub stdin: VecDeque<u8>,               // pending standard input
+  pub stdout: VecDeque<u8>,              // pending standard output
+  pub display: [u8; DISPLAY_BUFFER_LEN], // display buffer
+  pub controller: u8,                    // controller state
+  pub fields: BTreeMap<String, String>,  // machine state, by field name
+}
+
+// an input event fed to the machine before the tick beginning at clock count `clocks`. recordings
+// are sequences of input events and allow for sessions to be replayed deterministically
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct InputEvent {
+  pub clocks: u128, // clock count at which the input occurred
+  pub input: Input, // input that occurred
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum Input {
+  Stdin(u8),      // byte queued to standard input
+  Controller(u8), // new controller state
+}
+
+// what `execute` and `execute_headless` do beyond running the machine from reset
+#[derive(Default)]
+pub struct Session<'a> {
+  pub resume: Option<Snapshot>, // snapshot to resume from, which `mc` must already be loaded from
+  pub snapshot_file: Option<&'a String>, // file to save snapshots to
+  pub replay: VecDeque<InputEvent>, // recorded input events to feed back, in order
+  pub record: Option<Box<dyn std::io::Write>>, // where to record input events to
+}
+
 #[derive(Clone, Eq, PartialEq)]
 pub enum Token {
   LabelDef(Label),
   LabelRef(Label),
   MacroDef(Macro),
   MacroRef(Macro),
+  MacroDefParams(Macro, Vec<Param>),
+  MacroRefArgs(Macro, Vec<Vec<Token>>),
+  ParamRef(Param),
+  DefinedLabel(Label),
+  DefinedMacro(Macro),
+  ConstantRef(Constant),
   AtError,
+  AtIf,
+  AtElse,
+  AtEnd,
+  AtEqu,
   AtConst,
   AtData,
   AtDyn,
   AtOrg,
+  AtBank,
   AtDD(u8),
+  AtStr(Vec<u8>),
   XXX(u8),
+  Mul,
+  Div,
+  Mod,
   Add,
   AdS(Size),
   Sub,
@@ -746,13 +1812,65 @@ pub fn token_to_mnemonic(token: Token) -> Mnemonic {
     Token::LabelRef(Label::Global(identifier)) => Mnemonic(format!(":{}", identifier)),
     Token::MacroDef(Macro(r#macro)) => Mnemonic(format!("{}!", r#macro)),
     Token::MacroRef(Macro(r#macro)) => Mnemonic(format!("!{}", r#macro)),
+    Token::MacroDefParams(Macro(r#macro), params) => Mnemonic(format!(
+      "{}({})!",
+      r#macro,
+      params
+        .into_iter()
+        .map(|Param(param)| param)
+        .collect::<Vec<String>>()
+        .join(", ")
+    )),
+    Token::MacroRefArgs(Macro(r#macro), args) => Mnemonic(format!(
+      "!{}({})",
+      r#macro,
+      args
+        .into_iter()
+        .map(|arg| {
+          arg
+            .into_iter()
+            .map(|token| token_to_mnemonic(token).0)
+            .collect::<Vec<String>>()
+            .join(" ")
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+    )),
+    Token::ParamRef(Param(param)) => Mnemonic(format!("${}", param)),
+    Token::DefinedLabel(label) => {
+      Mnemonic(format!("?{}", token_to_mnemonic(Token::LabelRef(label)).0))
+    }
+    Token::DefinedMacro(r#macro) => Mnemonic(format!(
+      "?{}",
+      token_to_mnemonic(Token::MacroRef(r#macro)).0
+    )),
+    Token::ConstantRef(Constant(constant)) => Mnemonic(constant),
     Token::AtError => Mnemonic(format!("@error")),
+    Token::AtIf => Mnemonic("@if".to_string()),
+    Token::AtElse => Mnemonic("@else".to_string()),
+    Token::AtEnd => Mnemonic("@end".to_string()),
+    Token::AtEqu => Mnemonic("@equ".to_string()),
     Token::AtConst => Mnemonic(format!("@const")),
     Token::AtData => Mnemonic(format!("@data")),
     Token::AtDyn => Mnemonic(format!("@dyn")),
     Token::AtOrg => Mnemonic(format!("@org")),
+    Token::AtBank => Mnemonic("@bank".to_string()),
     Token::AtDD(value) => Mnemonic(format!("@{:02X}", value)),
+    Token::AtStr(bytes) => Mnemonic(format!(
+      "\"{}\"",
+      bytes
+        .into_iter()
+        .map(|byte| match (byte as char, char_to_escape(byte)) {
+          ('"', _) => "\\\"".to_string(),
+          (_, Some(escape)) => format!("\\{}", escape),
+          (char, None) => char.to_string(),
+        })
+        .collect::<String>()
+    )),
     Token::XXX(value) => Mnemonic(format!("x{:02X}", value)),
+    Token::Mul => Mnemonic("*".to_string()),
+    Token::Div => Mnemonic("/".to_string()),
+    Token::Mod => Mnemonic("%".to_string()),
     Token::Add => Mnemonic(format!("add")),
     Token::AdS(size) => Mnemonic(format!("ad{:01X}", size.get())),
     Token::Sub => Mnemonic(format!("sub")),
@@ -794,6 +1912,106 @@ pub fn token_to_mnemonic(token: Token) -> Mnemonic {
   }
 }
 
+// splits a line of assembly into mnemonics, along with the column each starts at. whitespace within
+// the parentheses following a macro name does not split mnemonics, so that macro parameters and
+// arguments can be spaced out
+pub fn split_mnemonics(line: &str) -> Vec<(usize, Mnemonic)> {
+  let chars: Vec<char> = line.chars().collect();
+  let mut mnemonics = vec![];
+  let mut mnemonic = String::new();
+  let (mut col, mut depth, mut index) = (0, 0, 0);
+  while index < chars.len() {
+    let char = chars[index];
+    match char {
+      _ if char.is_whitespace() && depth == 0 => {
+        if !mnemonic.is_empty() {
+          mnemonics.push((col, Mnemonic(std::mem::take(&mut mnemonic))));
+        }
+        col = index + 1;
+      }
+      '\'' | '"' if literal_len(&chars[index..]) > 0 => {
+        let len = literal_len(&chars[index..]);
+        mnemonic.extend(&chars[index..index + len]);
+        index += len;
+        continue;
+      }
+      '(' if depth > 0 || !mnemonic.trim_start_matches('!').is_empty() => {
+        depth += 1;
+        mnemonic.push(char);
+      }
+      ')' if depth > 0 => {
+        depth -= 1;
+        mnemonic.push(char);
+      }
+      _ => mnemonic.push(char),
+    }
+    index += 1;
+  }
+  if !mnemonic.is_empty() {
+    mnemonics.push((col, Mnemonic(mnemonic)));
+  }
+  mnemonics
+}
+
+// length of the character literal, such as `'('` or `'\n'`, or of the string literal, such as
+// `"a, b"`, at the start of `chars`, or `0` if there is none. whitespace, parentheses and commas
+// within literals are not treated as delimiters
+fn literal_len(chars: &[char]) -> usize {
+  match chars {
+    ['\'', '\\', _, '\'', ..] => 4,
+    ['\'', _, '\'', ..] => 3,
+    ['"', ..] => {
+      let mut index = 1;
+      while index < chars.len() {
+        match chars[index] {
+          '\\' => index += 2,
+          '"' => return index + 1,
+          _ => index += 1,
+        }
+      }
+      0
+    }
+    _ => 0,
+  }
+}
+
+// maps the character following a backslash in a character or string literal to the byte it stands for
+fn escape_to_byte(escape: char) -> Option<u8> {
+  match escape {
+    '0' => Some(0x00),
+    'a' => Some(0x07),
+    'b' => Some(0x08),
+    't' => Some(0x09),
+    'n' => Some(0x0A),
+    'v' => Some(0x0B),
+    'f' => Some(0x0C),
+    'r' => Some(0x0D),
+    'e' => Some(0x1B),
+    's' => Some(0x20),
+    '\'' => Some(0x27),
+    '"' => Some(0x22),
+    '\\' => Some(0x5C),
+    _ => None,
+  }
+}
+
+// maps bytes that cannot appear verbatim within a string literal to the character that escapes them
+fn char_to_escape(byte: u8) -> Option<char> {
+  match byte {
+    0x00 => Some('0'),
+    0x07 => Some('a'),
+    0x08 => Some('b'),
+    0x09 => Some('t'),
+    0x0A => Some('n'),
+    0x0B => Some('v'),
+    0x0C => Some('f'),
+    0x0D => Some('r'),
+    0x1B => Some('e'),
+    0x5C => Some('\\'),
+    _ => None,
+  }
+}
+
 pub fn mnemonic_to_token(mnemonic: Mnemonic) -> Option<Token> {
   fn parse_hex(literal: &str) -> Option<u8> {
     (literal.to_uppercase() == literal)
@@ -801,9 +2019,96 @@ pub fn mnemonic_to_token(mnemonic: Mnemonic) -> Option<Token> {
       .and(u8::from_str_radix(literal, 16).ok())
   }
 
+  // splits the contents of parentheses on top-level commas. empty parentheses hold no arguments
+  // rather than a single empty argument
+  fn split_args(args: &str) -> Vec<String> {
+    let chars: Vec<char> = args.chars().collect();
+    if args.trim().is_empty() {
+      return vec![];
+    }
+    let mut split = vec![String::new()];
+    let (mut depth, mut index) = (0, 0);
+    while index < chars.len() {
+      let len = std::cmp::max(literal_len(&chars[index..]), 1);
+      match chars[index] {
+        '(' if len == 1 => depth += 1,
+        ')' if len == 1 => depth -= 1,
+        ',' if depth == 0 => {
+          split.push(String::new());
+          index += 1;
+          continue;
+        }
+        _ => {}
+      }
+      split.last_mut().unwrap().extend(&chars[index..index + len]);
+      index += len;
+    }
+    split
+  }
+
+  // parses the contents of a string literal, resolving escape sequences. only ASCII is allowed
+  fn parse_string(literal: &[char]) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut chars = literal.iter();
+    while let Some(char) = chars.next() {
+      match char {
+        '\\' => bytes.push(escape_to_byte(*chars.next()?)?),
+        '"' => return None,
+        _ if char.is_ascii() => bytes.push(*char as u8),
+        _ => return None,
+      }
+    }
+    Some(bytes)
+  }
+
+  let chars: Vec<char> = mnemonic.0.chars().collect();
   let mnemonic = mnemonic.0.as_str();
 
   match mnemonic {
+    _ if !chars.is_empty() && literal_len(&chars) == chars.len() => match chars[..] {
+      ['\'', '\\', escape, '\''] => escape_to_byte(escape).map(Token::XXX),
+      ['\'', char, '\''] if char.is_ascii() => Some(Token::XXX(char as u8)),
+      ['"', ref string @ .., '"'] => parse_string(string).map(Token::AtStr),
+      _ => None,
+    },
+    _ if mnemonic.ends_with(")!") && mnemonic.find('(').is_some_and(|index| index > 0) => {
+      let (r#macro, params) = mnemonic[..mnemonic.len() - 2].split_once('(')?;
+      let params = split_args(params)
+        .into_iter()
+        .map(|param| match split_mnemonics(&param)[..] {
+          [(_, Mnemonic(ref param))] if !param.contains(['(', ')', ',', '$']) => {
+            Some(Param(param.clone()))
+          }
+          _ => None,
+        })
+        .collect::<Option<Vec<Param>>>()?;
+      Some(Token::MacroDefParams(Macro(r#macro.to_string()), params))
+    }
+    _ if mnemonic.starts_with("!")
+      && mnemonic.ends_with(")")
+      && mnemonic.find('(').is_some_and(|index| index > 1) =>
+    {
+      let (r#macro, args) = mnemonic[1..mnemonic.len() - 1].split_once('(')?;
+      let args = split_args(args)
+        .into_iter()
+        .map(|arg| {
+          split_mnemonics(&arg)
+            .into_iter()
+            .map(|(_, mnemonic)| mnemonic_to_token(mnemonic))
+            .collect::<Option<Vec<Token>>>()
+        })
+        .collect::<Option<Vec<Vec<Token>>>>()?;
+      Some(Token::MacroRefArgs(Macro(r#macro.to_string()), args))
+    }
+    _ if mnemonic.starts_with("$") && mnemonic.len() > 1 => {
+      Some(Token::ParamRef(Param(mnemonic[1..].to_string())))
+    }
+    _ if mnemonic.starts_with("?") => match mnemonic_to_token(Mnemonic(mnemonic[1..].to_string()))?
+    {
+      Token::LabelRef(label) => Some(Token::DefinedLabel(label)),
+      Token::MacroRef(r#macro) => Some(Token::DefinedMacro(r#macro)),
+      _ => None,
+    },
     _ if mnemonic.ends_with(":") => Some(Token::LabelDef(Label::Global(
       mnemonic[..mnemonic.len() - 1].to_string(),
     ))),
@@ -822,11 +2127,43 @@ pub fn mnemonic_to_token(mnemonic: Mnemonic) -> Option<Token> {
       mnemonic[..mnemonic.len() - 1].to_string(),
     ))),
     _ if mnemonic.starts_with("!") => Some(Token::MacroRef(Macro(mnemonic[1..].to_string()))),
+    _ if mnemonic.starts_with(|char: char| char.is_ascii_digit())
+      && mnemonic.chars().all(|char| char.is_ascii_digit()) =>
+    {
+      mnemonic.parse::<u8>().ok().map(Token::XXX)
+    }
+    _ if mnemonic.len() > 1
+      && mnemonic.starts_with("b")
+      && mnemonic[1..].chars().all(|char| char == '0' || char == '1') =>
+    {
+      u8::from_str_radix(&mnemonic[1..], 2).ok().map(Token::XXX)
+    }
+    _ if mnemonic.starts_with(|char: char| char.is_ascii_uppercase())
+      && mnemonic
+        .chars()
+        .all(|char| char.is_ascii_uppercase() || char.is_ascii_digit() || char == '_') =>
+    {
+      Some(Token::ConstantRef(Constant(mnemonic.to_string())))
+    }
+    "*" => Some(Token::Mul),
+    "/" => Some(Token::Div),
+    "%" => Some(Token::Mod),
+    "+" => Some(Token::Add),
+    "-" => Some(Token::Sub),
+    "&" => Some(Token::And),
+    "|" => Some(Token::Orr),
+    "^" => Some(Token::Xor),
+    "~" => Some(Token::Not),
     "@error" => Some(Token::AtError),
+    "@if" => Some(Token::AtIf),
+    "@else" => Some(Token::AtElse),
+    "@end" => Some(Token::AtEnd),
+    "@equ" => Some(Token::AtEqu),
     "@const" => Some(Token::AtConst),
     "@data" => Some(Token::AtData),
     "@dyn" => Some(Token::AtDyn),
     "@org" => Some(Token::AtOrg),
+    "@bank" => Some(Token::AtBank),
     "add" => Some(Token::Add),
     "sub" => Some(Token::Sub),
     "iff" => Some(Token::Iff),
@@ -876,6 +2213,343 @@ pub fn mnemonic_to_token(mnemonic: Mnemonic) -> Option<Token> {
   }
 }
 
+pub fn parse_symbols(symbols: &str) -> Result<Symbols, Error> {
+  // symbol files are line-oriented. blank lines and lines starting with `#` are ignored
+  //   label <label> <address>
+  //   data <address>
+  //   code <address>
+  //   display <address>
+  //   origin <address> <macros...> <pos>
+
+  fn parse_hex(literal: &str) -> Option<u8> {
+    u8::from_str_radix(literal, 16).ok()
+  }
+
+  fn parse_pos(pos: &str) -> Option<Pos> {
+    let mut fields = pos.strip_prefix("@")?.rsplitn(3, ':');
+    let col: usize = fields.next()?.parse().ok()?;
+    let row: usize = fields.next()?.parse().ok()?;
+    let file = File(fields.next()?.into());
+    Some(Pos(file, row.checked_sub(1)?, col.checked_sub(1)?))
+  }
+
+  let mut parsed = Symbols::default();
+
+  for (index, line) in symbols.lines().enumerate() {
+    let error = || {
+      Error(format!(
+        "Malformed symbol on line {}: `{}`",
+        index + 1,
+        line
+      ))
+    };
+
+    match line.split_once(' ') {
+      _ if line.trim().is_empty() || line.starts_with("#") => {}
+
+      Some(("label", rest)) => match rest.split_once(' ') {
+        Some((label, address)) => match (
+          mnemonic_to_token(Mnemonic(label.to_string())),
+          parse_hex(address),
+        ) {
+          (Some(Token::LabelRef(label)), Some(address)) => {
+            parsed.labels.insert(label, address);
+          }
+          _ => Err(error())?,
+        },
+        None => Err(error())?,
+      },
+
+      Some(("data", address)) => {
+        parsed.data.insert(parse_hex(address).ok_or_else(error)?);
+      }
+
+      Some(("code", address)) => {
+        parsed.code.insert(parse_hex(address).ok_or_else(error)?);
+      }
+
+      Some(("display", address)) => {
+        parsed.display.insert(parse_hex(address).ok_or_else(error)?);
+      }
+
+      Some(("origin", rest)) => {
+        let (address, mut rest) = rest.split_once(' ').ok_or_else(error)?;
+        let address = parse_hex(address).ok_or_else(error)?;
+
+        // file names may contain spaces, so the position is whatever follows the macro chain
+        let mut macros = vec![];
+        while let Some(r#macro) = rest.strip_prefix("!") {
+          let (r#macro, remainder) = r#macro.split_once(' ').ok_or_else(error)?;
+          macros.push(Macro(r#macro.to_string()));
+          rest = remainder;
+        }
+        let pos = parse_pos(rest).ok_or_else(error)?;
+
+        parsed
+          .sources
+          .entry(pos.0 .0.clone())
+          .or_insert_with_key(|path| {
+            std::fs::read_to_string(path)
+              .ok()
+              .map(|source| source.lines().map(str::to_string).collect())
+          });
+        parsed.origins.insert(address, (pos, macros));
+      }
+
+      _ => Err(error())?,
+    }
+  }
+
+  Ok(parsed)
+}
+
+pub fn parse_trace(trace: &str) -> Result<Vec<TraceEntry>, Error> {
+  // traces are line-oriented. blank lines and lines starting with `#` are ignored
+  //   <clocks> <ip> <sp> <cf> <opcode> <mnemonic> <accesses...>
+  // where accesses are either `rAA:VV` or `wAA:VV`. the mnemonic is informative only
+
+  fn parse_hex(literal: &str) -> Option<u8> {
+    u8::from_str_radix(literal, 16).ok()
+  }
+
+  fn parse_access(access: &str) -> Option<MemoryAccess> {
+    let (address, value) = access.get(1..)?.split_once(':')?;
+    let (address, value) = (parse_hex(address)?, parse_hex(value)?);
+    match access.get(..1)? {
+      "r" => Some(MemoryAccess::Read(address, value)),
+      "w" => Some(MemoryAccess::Write(address, value)),
+      _ => None,
+    }
+  }
+
+  trace
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with("#"))
+    .map(|(index, line)| {
+      let error = || {
+        Error(format!(
+          "Malformed trace entry on line {}: `{}`",
+          index + 1,
+          line
+        ))
+      };
+      let fields: Vec<&str> = line.split_whitespace().collect();
+
+      match fields[..] {
+        [clocks, ip, sp, cf, opcode, _mnemonic, ref accesses @ ..] => Ok(TraceEntry {
+          clocks: clocks.parse().map_err(|_| error())?,
+          ip: parse_hex(ip).ok_or_else(error)?,
+          sp: parse_hex(sp).ok_or_else(error)?,
+          cf: match cf {
+            "0" => false,
+            "1" => true,
+            _ => Err(error())?,
+          },
+          instruction: opcode_to_instruction(parse_hex(opcode).ok_or_else(error)?),
+          accesses: accesses
+            .iter()
+            .map(|access| parse_access(access))
+            .collect::<Option<Vec<MemoryAccess>>>()
+            .ok_or_else(error)?,
+        }),
+        _ => Err(error()),
+      }
+    })
+    .collect()
+}
+
+pub fn parse_snapshot(snapshot: &str) -> Result<Snapshot, Error> {
+  // snapshots are line-oriented. blank lines and lines starting with `#` are ignored
+  //   stdin <bytes...>
+  //   stdout <bytes...>
+  //   display <bytes...>
+  //   controller <byte>
+  //   <field> <value>
+  // where bytes are in hexadecimal. all four peripheral fields are required
+
+  let mut parsed = Snapshot::default();
+  let mut peripherals = BTreeSet::new();
+
+  for (index, line) in snapshot.lines().enumerate() {
+    let error = || {
+      Error(format!(
+        "Malformed snapshot on line {}: `{}`",
+        index + 1,
+        line
+      ))
+    };
+
+    if line.trim().is_empty() || line.starts_with("#") {
+      continue;
+    }
+
+    let (field, value) = line.split_once(' ').unwrap_or((line, ""));
+    match field {
+      "stdin" => parsed.stdin = parse_bytes(value).ok_or_else(error)?.into(),
+      "stdout" => parsed.stdout = parse_bytes(value).ok_or_else(error)?.into(),
+      "display" => {
+        parsed.display = parse_bytes(value)
+          .and_then(|bytes| bytes.try_into().ok())
+          .ok_or_else(error)?
+      }
+      "controller" => {
+        parsed.controller = match parse_bytes(value).ok_or_else(error)?[..] {
+          [controller] => controller,
+          _ => Err(error())?,
+        }
+      }
+      _ => {
+        parsed.fields.insert(field.to_string(), value.to_string());
+      }
+    }
+    peripherals.insert(field);
+  }
+
+  for field in ["stdin", "stdout", "display", "controller"] {
+    if !peripherals.contains(field) {
+      Err(Error(format!("Missing snapshot field `{}`", field)))?;
+    }
+  }
+
+  Ok(parsed)
+}
+
+pub fn parse_recording(recording: &str) -> Result<VecDeque<InputEvent>, Error> {
+  // recordings are line-oriented. blank lines and lines starting with `#` are ignored
+  //   <clocks> stdin <byte>
+  //   <clocks> controller <byte>
+  // where clock counts are in decimal and never decrease, and bytes are in hexadecimal
+
+  let mut parsed: VecDeque<InputEvent> = VecDeque::new();
+
+  for (index, line) in recording.lines().enumerate() {
+    let error = || {
+      Error(format!(
+        "Malformed input event on line {}: `{}`",
+        index + 1,
+        line
+      ))
+    };
+
+    if line.trim().is_empty() || line.starts_with("#") {
+      continue;
+    }
+
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (clocks, input, byte) = match fields[..] {
+      [clocks, input, byte] => (clocks, input, byte),
+      _ => Err(error())?,
+    };
+    let clocks: u128 = clocks.parse().map_err(|_| error())?;
+    let byte = u8::from_str_radix(byte, 16).map_err(|_| error())?;
+    let input = match input {
+      "stdin" => Input::Stdin(byte),
+      "controller" => Input::Controller(byte),
+      _ => Err(error())?,
+    };
+
+    if parsed.back().is_some_and(|event| event.clocks > clocks) {
+      Err(Error(format!(
+        "Out-of-order input event on line {}: `{}`",
+        index + 1,
+        line
+      )))?;
+    }
+    parsed.push_back(InputEvent { clocks, input });
+  }
+
+  Ok(parsed)
+}
+
+fn parse_bytes(bytes: &str) -> Option<Vec<u8>> {
+  bytes
+    .split_whitespace()
+    .map(|byte| u8::from_str_radix(byte, 16).ok())
+    .collect()
+}
+
+fn parse_bit(bit: &str) -> Option<bool> {
+  match bit {
+    "0" => Some(false),
+    "1" => Some(true),
+    _ => None,
+  }
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+  bytes
+    .iter()
+    .map(|byte| format!("{:02X}", byte))
+    .collect::<Vec<String>>()
+    .join(" ")
+}
+
+impl Snapshot {
+  pub fn put(&mut self, field: &str, value: impl std::fmt::Display) {
+    self.fields.insert(field.to_string(), value.to_string());
+  }
+
+  pub fn put_bytes(&mut self, field: &str, bytes: &[u8]) {
+    self.put(field, format_bytes(bytes));
+  }
+
+  pub fn get<T: std::str::FromStr>(&self, field: &str) -> Result<T, Error> {
+    self
+      .fields
+      .get(field)
+      .ok_or_else(|| Error(format!("Missing snapshot field `{}`", field)))?
+      .parse()
+      .map_err(|_| Error(format!("Malformed snapshot field `{}`", field)))
+  }
+
+  pub fn get_bytes<const N: usize>(&self, field: &str) -> Result<[u8; N], Error> {
+    parse_bytes(&self.get::<String>(field)?)
+      .and_then(|bytes| bytes.try_into().ok())
+      .ok_or_else(|| Error(format!("Malformed snapshot field `{}`", field)))
+  }
+
+  pub fn get_bit(&self, field: &str) -> Result<bool, Error> {
+    parse_bit(&self.get::<String>(field)?)
+      .ok_or_else(|| Error(format!("Malformed snapshot field `{}`", field)))
+  }
+}
+
+impl Symbols {
+  // nearest global label at or before `address`, along with its address
+  pub fn label(&self, address: u8) -> Option<(&Label, u8)> {
+    self
+      .labels
+      .iter()
+      .filter(|(label, &label_address)| {
+        matches!(label, Label::Global(_)) && label_address <= address
+      })
+      .max_by_key(|(_, &label_address)| label_address)
+      .map(|(label, &label_address)| (label, label_address))
+  }
+
+  // nearest global label at or before `address`, as `:label+offset`
+  pub fn describe(&self, address: u8) -> Option<String> {
+    self
+      .label(address)
+      .map(|(label, label_address)| match address - label_address {
+        0x00 => format!("{}", label),
+        offset => format!("{}+{:02X}", label, offset),
+      })
+  }
+
+  pub fn resolve(&self, label: &Label) -> Option<u8> {
+    self.labels.get(label).copied()
+  }
+
+  // source line that produced the byte at `address`, if its file could be read
+  pub fn source_line(&self, address: u8) -> Option<&str> {
+    let (Pos(File(path), row, _), _) = self.origins.get(&address)?;
+    let lines = self.sources.get(path)?.as_ref()?;
+    lines.get(*row).map(|line| line.trim())
+  }
+}
+
 pub fn instruction_to_token(instruction: Result<Instruction, u8>) -> Token {
   match instruction {
     Ok(Instruction::Psh(imm)) => Token::XXX(imm.get()),
@@ -941,6 +2615,18 @@ impl std::fmt::Display for Macro {
   }
 }
 
+impl std::fmt::Display for Param {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}", token_to_mnemonic(Token::ParamRef(self.clone())))
+  }
+}
+
+impl std::fmt::Display for Constant {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}", token_to_mnemonic(Token::ConstantRef(self.clone())))
+  }
+}
+
 impl std::fmt::Display for Error {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     write!(f, "{}", self.0)
@@ -953,6 +2639,91 @@ impl std::fmt::Display for Pos {
   }
 }
 
+impl std::fmt::Display for MemoryAccess {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      MemoryAccess::Read(address, value) => write!(f, "r{:02X}:{:02X}", address, value),
+      MemoryAccess::Write(address, value) => write!(f, "w{:02X}:{:02X}", address, value),
+    }
+  }
+}
+
+impl std::fmt::Display for TraceEntry {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(
+      f,
+      "{} {:02X} {:02X} {:01b} {:02X} {}",
+      self.clocks,
+      self.ip,
+      self.sp,
+      self.cf as u8,
+      instruction_to_opcode(self.instruction.clone()),
+      instruction_to_token(self.instruction.clone()),
+    )?;
+    for access in self.accesses.iter() {
+      write!(f, " {}", access)?;
+    }
+    Ok(())
+  }
+}
+
+impl std::fmt::Display for Symbols {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    for (label, address) in self.labels.iter() {
+      writeln!(f, "label {} {:02X}", label, address)?;
+    }
+    for address in self.data.iter() {
+      writeln!(f, "data {:02X}", address)?;
+    }
+    for address in self.code.iter() {
+      writeln!(f, "code {:02X}", address)?;
+    }
+    for address in self.display.iter() {
+      writeln!(f, "display {:02X}", address)?;
+    }
+    for (address, (pos, macros)) in self.origins.iter() {
+      write!(f, "origin {:02X}", address)?;
+      for r#macro in macros.iter() {
+        write!(f, " {}", r#macro)?;
+      }
+      writeln!(f, " {}", pos)?;
+    }
+    Ok(())
+  }
+}
+
+impl std::fmt::Display for Snapshot {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let stdin = format_bytes(self.stdin.iter().copied().collect::<Vec<u8>>().as_slice());
+    let stdout = format_bytes(self.stdout.iter().copied().collect::<Vec<u8>>().as_slice());
+    let fields = [
+      ("stdin", &stdin),
+      ("stdout", &stdout),
+      ("display", &format_bytes(&self.display)),
+      ("controller", &format_bytes(&[self.controller])),
+    ];
+    for (field, value) in fields
+      .into_iter()
+      .chain(self.fields.iter().map(|(f, v)| (f.as_str(), v)))
+    {
+      match value.is_empty() {
+        true => writeln!(f, "{}", field)?,
+        false => writeln!(f, "{} {}", field, value)?,
+      }
+    }
+    Ok(())
+  }
+}
+
+impl std::fmt::Display for InputEvent {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self.input {
+      Input::Stdin(byte) => write!(f, "{} stdin {:02X}", self.clocks, byte),
+      Input::Controller(state) => write!(f, "{} controller {:02X}", self.clocks, state),
+    }
+  }
+}
+
 impl std::fmt::Display for Mnemonic {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     write!(f, "{}", self.0)
@@ -0,0 +1,418 @@
+// exports the display buffer as PNG frames and as animated GIFs. encoding is done in-crate so as
+// to not depend on external libraries
+
+use crate::*;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+
+const EXPORT_SCALE: usize = 8; // image pixels per display pixel
+const EXPORT_SIZE: usize = 0x10 * EXPORT_SCALE;
+const GIF_FRAME_RATE: u128 = 50; // GIF delays are in centiseconds, and browsers clamp delays below 2
+
+// wraps a `Tickable` and exports the display buffer every time it changes, as numbered PNG frames
+// written to `frames` and as an animated GIF written to `gif` timed by clock count
+pub struct Exporter<MC: Tickable> {
+  pub mc: MC,
+  frames: Option<std::path::PathBuf>, // directory PNG frames are written to
+  gif: Option<std::fs::File>,         // file the animated GIF is streamed to
+  clock_speed: u128,                  // clock cycles per second, for GIF timing
+  clocks: u128,                       // clock count since reset
+  frame_count: usize,                 // PNG frames written so far
+  previous: Option<[u8; common::DISPLAY_BUFFER_LEN]>, // display buffer at last frame
+  pending: Option<(u128, [u8; common::DISPLAY_BUFFER_LEN])>, // GIF frame and its time slot
+  error: Option<Error>,               // first error encountered, reported by `finish`
+}
+
+impl<MC: Tickable> Exporter<MC> {
+  pub fn new(
+    mc: MC,
+    frames: Option<&String>,
+    gif: Option<&String>,
+    clock_speed: u128,
+  ) -> Result<Self, Error> {
+    let frames = frames
+      .map(|frames| {
+        std::fs::create_dir_all(frames)
+          .map(|()| frames.into())
+          .map_err(|_| Error(format!("Unable to create directory '{}'", frames)))
+      })
+      .transpose()?;
+    let gif = gif
+      .map(|gif| {
+        std::fs::File::create(gif)
+          .and_then(|mut file| {
+            file
+              .write_all(&gif_header(EXPORT_SIZE, EXPORT_SIZE))
+              .map(|()| file)
+          })
+          .map_err(|_| Error(format!("Unable to write file '{}'", gif)))
+      })
+      .transpose()?;
+
+    Ok(Exporter {
+      mc,
+      frames,
+      gif,
+      clock_speed,
+      clocks: 0,
+      frame_count: 0,
+      previous: None,
+      pending: None,
+      error: None,
+    })
+  }
+
+  // writes the last GIF frame, which lasts until now, and completes the GIF
+  pub fn finish(mut self) -> Result<(), Error> {
+    let slot = self.slot() + 1;
+    if let Some((pending_slot, pending)) = self.pending.take() {
+      let pixels = render_display(&pending);
+      self.write_gif(&gif_frame(
+        EXPORT_SIZE,
+        EXPORT_SIZE,
+        &pixels,
+        slot - pending_slot,
+      ));
+    }
+    self.write_gif(&[0x3B]); // trailer
+    self.error.map_or(Ok(()), Err)
+  }
+
+  fn slot(&self) -> u128 {
+    self.clocks * GIF_FRAME_RATE / std::cmp::max(self.clock_speed, 1)
+  }
+
+  fn export(&mut self, display: &[u8; common::DISPLAY_BUFFER_LEN]) {
+    if self.previous == Some(*display) {
+      return;
+    }
+    self.previous = Some(*display);
+
+    if let Some(frames) = &self.frames {
+      let pixels = render_display(display);
+      let path = frames.join(format!("{:06}.png", self.frame_count));
+      self.frame_count += 1;
+      if std::fs::write(&path, encode_png(EXPORT_SIZE, EXPORT_SIZE, &pixels)).is_err() {
+        let error = Error(format!("Unable to write file '{}'", path.display()));
+        self.error.get_or_insert(error);
+      }
+    }
+
+    // changes within a single time slot are merged into one GIF frame
+    if self.gif.is_some() {
+      let slot = self.slot();
+      match self.pending.take() {
+        Some((pending_slot, pending)) if pending_slot < slot => {
+          let pixels = render_display(&pending);
+          let frame = gif_frame(EXPORT_SIZE, EXPORT_SIZE, &pixels, slot - pending_slot);
+          self.write_gif(&frame);
+          self.pending = Some((slot, *display));
+        }
+        Some((pending_slot, _)) => self.pending = Some((pending_slot, *display)),
+        None => self.pending = Some((slot, *display)),
+      }
+    }
+  }
+
+  fn write_gif(&mut self, bytes: &[u8]) {
+    if let Some(gif) = &mut self.gif {
+      if gif.write_all(bytes).is_err() {
+        let error = Error("Unable to write GIF file".to_string());
+        self.error.get_or_insert(error);
+      }
+    }
+  }
+}
+
+impl<MC: Tickable> Tickable for Exporter<MC> {
+  fn reset(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; common::DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) {
+    self.mc.reset(stdin, stdout, display, controller);
+    self.clocks = 0;
+    self.export(display);
+  }
+
+  fn tick(
+    &mut self,
+    stdin: &mut VecDeque<u8>,
+    stdout: &mut VecDeque<u8>,
+    display: &mut [u8; common::DISPLAY_BUFFER_LEN],
+    controller: &mut u8,
+  ) -> Result<u128, TickTrap> {
+    let result = self.mc.tick(stdin, stdout, display, controller);
+    self.export(display);
+    self.clocks += result.unwrap_or(0);
+    result
+  }
+
+  fn retired(&mut self) -> Option<TraceEntry> {
+    self.mc.retired()
+  }
+
+  fn save(&self, snapshot: &mut Snapshot) {
+    self.mc.save(snapshot);
+  }
+
+  fn load(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+    self.mc.load(snapshot)
+  }
+}
+
+impl<MC: Tickable + std::fmt::Display> std::fmt::Display for Exporter<MC> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.mc)
+  }
+}
+
+// grayscale pixels, row by row
+fn render_display(display: &[u8; common::DISPLAY_BUFFER_LEN]) -> Vec<u8> {
+  (0..EXPORT_SIZE * EXPORT_SIZE)
+    .map(|index| {
+      let (x, y) = (
+        index % EXPORT_SIZE / EXPORT_SCALE,
+        index / EXPORT_SIZE / EXPORT_SCALE,
+      );
+      match common::pixel(display, y * 0x10 + x) {
+        false => 0x00,
+        true => 0xFF,
+      }
+    })
+    .collect()
+}
+
+// 8-bit grayscale PNG
+fn encode_png(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+  fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    png.extend(crc32(&[&kind[..], data].concat()).to_be_bytes());
+  }
+
+  let mut header = vec![];
+  header.extend((width as u32).to_be_bytes());
+  header.extend((height as u32).to_be_bytes());
+  header.extend([0x08, 0x00, 0x00, 0x00, 0x00]); // bit depth, grayscale, no interlacing
+
+  // every scanline is prefixed with filter type `None`
+  let scanlines: Vec<u8> = pixels
+    .chunks(width)
+    .flat_map(|row| std::iter::once(0x00).chain(row.iter().copied()))
+    .collect();
+
+  let mut png = vec![0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+  chunk(&mut png, b"IHDR", &header);
+  chunk(&mut png, b"IDAT", &zlib(&scanlines));
+  chunk(&mut png, b"IEND", &[]);
+  png
+}
+
+// GIF header with a global color table of 256 shades of gray, looping forever
+fn gif_header(width: usize, height: usize) -> Vec<u8> {
+  let mut gif = b"GIF89a".to_vec();
+  gif.extend((width as u16).to_le_bytes());
+  gif.extend((height as u16).to_le_bytes());
+  gif.extend([0xF7, 0x00, 0x00]); // global color table of 256 entries
+  gif.extend((0x00..=0xFF).flat_map(|shade| [shade, shade, shade]));
+  gif.extend([0x21, 0xFF, 0x0B]);
+  gif.extend(b"NETSCAPE2.0");
+  gif.extend([0x03, 0x01, 0x00, 0x00, 0x00]); // loop forever
+  gif
+}
+
+// GIF frame lasting `slots` time slots
+fn gif_frame(width: usize, height: usize, pixels: &[u8], slots: u128) -> Vec<u8> {
+  let delay = std::cmp::min(slots * (100 / GIF_FRAME_RATE), u16::MAX as u128) as u16;
+
+  let mut frame = vec![0x21, 0xF9, 0x04, 0x00];
+  frame.extend(delay.to_le_bytes());
+  frame.extend([0x00, 0x00]);
+  frame.extend([0x2C, 0x00, 0x00, 0x00, 0x00]);
+  frame.extend((width as u16).to_le_bytes());
+  frame.extend((height as u16).to_le_bytes());
+  frame.push(0x00);
+  frame.push(0x08); // minimum code size
+  for block in lzw(pixels).chunks(0xFF) {
+    frame.push(block.len() as u8);
+    frame.extend(block);
+  }
+  frame.push(0x00);
+  frame
+}
+
+// variable-width LZW as used by GIF, for a minimum code size of 8
+fn lzw(data: &[u8]) -> Vec<u8> {
+  const CLEAR: u16 = 0x100;
+  const END: u16 = 0x101;
+
+  let mut bits = Bits::default();
+  let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+  let mut next = END + 1;
+  let mut width = 9;
+
+  bits.write(CLEAR as u32, width);
+  let mut prefix: Option<u16> = None;
+  for &byte in data {
+    let Some(code) = prefix else {
+      prefix = Some(byte as u16);
+      continue;
+    };
+    if let Some(&extended) = codes.get(&(code, byte)) {
+      prefix = Some(extended);
+      continue;
+    }
+
+    bits.write(code as u32, width);
+    if next < 0x1000 {
+      codes.insert((code, byte), next);
+      next += 1;
+      // decoders lag one code behind, so widths grow once the code past the limit is assigned
+      if next > 1 << width {
+        width += 1;
+      }
+    } else {
+      bits.write(CLEAR as u32, width);
+      codes.clear();
+      next = END + 1;
+      width = 9;
+    }
+    prefix = Some(byte as u16);
+  }
+  if let Some(code) = prefix {
+    bits.write(code as u32, width);
+  }
+  bits.write(END as u32, width);
+
+  bits.bytes
+}
+
+// zlib stream compressed with the fixed Huffman codes of deflate and greedy LZ77 matching
+fn zlib(data: &[u8]) -> Vec<u8> {
+  const LENGTH_BASES: [u32; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+  ];
+  const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+  ];
+  const DISTANCE_BASES: [u32; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+  ];
+  const DISTANCE_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+  ];
+  const WINDOW: usize = 0x8000;
+
+  fn symbol(bits: &mut Bits, symbol: u32) {
+    match symbol {
+      0..=143 => bits.write_huffman(0x30 + symbol, 8),
+      144..=255 => bits.write_huffman(0x190 + symbol - 144, 9),
+      256..=279 => bits.write_huffman(symbol - 256, 7),
+      _ => bits.write_huffman(0xC0 + symbol - 280, 8),
+    }
+  }
+
+  let mut bits = Bits::default();
+  bits.write(0b1, 1); // final block
+  bits.write(0b01, 2); // fixed Huffman codes
+
+  let mut heads: HashMap<&[u8], usize> = HashMap::new();
+  let mut index = 0;
+  while index < data.len() {
+    let key = data.get(index..index + 3);
+    let candidate = key.and_then(|key| heads.get(key)).copied();
+    let length = candidate
+      .filter(|candidate| index - candidate <= WINDOW)
+      .map(|candidate| {
+        (0..std::cmp::min(258, data.len() - index))
+          .take_while(|offset| data[candidate + offset] == data[index + offset])
+          .count()
+      })
+      .unwrap_or(0);
+
+    let advance = match (length, candidate) {
+      (3.., Some(candidate)) => {
+        let distance = (index - candidate) as u32;
+        let code = LENGTH_BASES.partition_point(|base| *base <= length as u32) - 1;
+        symbol(&mut bits, 257 + code as u32);
+        bits.write(length as u32 - LENGTH_BASES[code], LENGTH_EXTRA[code]);
+        let code = DISTANCE_BASES.partition_point(|base| *base <= distance) - 1;
+        bits.write_huffman(code as u32, 5);
+        bits.write(distance - DISTANCE_BASES[code], DISTANCE_EXTRA[code]);
+        length
+      }
+      _ => {
+        symbol(&mut bits, data[index] as u32);
+        1
+      }
+    };
+
+    for index in index..index + advance {
+      if let Some(key) = data.get(index..index + 3) {
+        heads.insert(key, index);
+      }
+    }
+    index += advance;
+  }
+  symbol(&mut bits, 256); // end of block
+
+  let mut zlib = vec![0x78, 0x01];
+  zlib.extend(bits.bytes);
+  zlib.extend(adler32(data).to_be_bytes());
+  zlib
+}
+
+// bit stream packed least significant bit first, as used by both deflate and GIF
+#[derive(Default)]
+struct Bits {
+  bytes: Vec<u8>,
+  used: u32, // bits used in the last byte
+}
+
+impl Bits {
+  fn write(&mut self, value: u32, count: u32) {
+    for bit in 0..count {
+      if self.used == 0 {
+        self.bytes.push(0x00);
+      }
+      *self.bytes.last_mut().unwrap() |= ((value >> bit & 0x01) as u8) << self.used;
+      self.used = (self.used + 1) % 8;
+    }
+  }
+
+  // Huffman codes are packed most significant bit first
+  fn write_huffman(&mut self, code: u32, count: u32) {
+    for bit in (0..count).rev() {
+      self.write(code >> bit & 0x01, 1);
+    }
+  }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+  let mut crc = !0u32;
+  for byte in data {
+    crc ^= *byte as u32;
+    for _ in 0..8 {
+      crc = match crc & 0x01 {
+        0 => crc >> 1,
+        _ => crc >> 1 ^ 0xEDB88320,
+      };
+    }
+  }
+  !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+  let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+    let a = (a + *byte as u32) % 65521;
+    (a, (b + a) % 65521)
+  });
+  b << 16 | a
+}
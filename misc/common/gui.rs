@@ -0,0 +1,263 @@
+// graphical frontend, compiled in with `--features gui`. renders the display buffer into a scaled
+// pixel window along with a console pane for standard input and output
+
+use crate::*;
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use std::collections::{HashMap, VecDeque};
+
+const DISPLAY_SCALE: usize = 16; // window pixels per display pixel
+const FONT_SCALE: usize = 2; // window pixels per font pixel
+const CELL_SIZE: usize = 4 * FONT_SCALE; // glyphs are 3x3 font pixels plus spacing
+const CONSOLE_ROWS: usize = 12;
+const CONSOLE_COLUMNS: usize = WIDTH / CELL_SIZE;
+const CONSOLE_TOP: usize = 0x10 * DISPLAY_SCALE + CELL_SIZE;
+const WIDTH: usize = 0x10 * DISPLAY_SCALE;
+const HEIGHT: usize = CONSOLE_TOP + CONSOLE_ROWS * CELL_SIZE + CELL_SIZE;
+const FPS: usize = 60;
+
+const CONSOLE_FOREGROUND: u32 = 0xC0C0C0;
+const CONSOLE_BACKGROUND: u32 = 0x202020;
+
+// controller buttons in bit order, matching the terminal frontend
+const CONTROLLER_KEYS: [Key; 8] = [
+  Key::Up,
+  Key::Down,
+  Key::Left,
+  Key::Right,
+  Key::PageUp,
+  Key::PageDown,
+  Key::Home,
+  Key::End,
+];
+
+// forwards printable characters typed into the window
+struct Keyboard(std::sync::mpsc::Sender<u32>);
+
+impl minifb::InputCallback for Keyboard {
+  fn add_char(&mut self, uni_char: u32) {
+    self.0.send(uni_char).ok();
+  }
+}
+
+// when resuming from a snapshot, `mc` is expected to have been loaded from it already
+pub fn execute<MC: Tickable>(
+  mut mc: MC,
+  timing: Timing,
+  mut session: Session,
+) -> Result<(), Error> {
+  let Timing {
+    clock_speed,
+    unthrottled,
+    frame_period,
+  } = timing;
+  let mut stdin = VecDeque::new();
+  let mut stdout = VecDeque::new();
+  let mut display = [0x00; common::DISPLAY_BUFFER_LEN];
+  let mut controller = 0x00;
+  let mut total_clocks = 0;
+  let mut status_line = "".to_string();
+  let mut halted = false;
+  let mut speed_mode = SpeedMode::Normal;
+
+  match session.resume.take() {
+    Some(snapshot) => {
+      total_clocks = snapshot.get("clocks").unwrap_or(0);
+      (stdin, stdout) = (snapshot.stdin, snapshot.stdout);
+      (display, controller) = (snapshot.display, snapshot.controller);
+    }
+    None => mc.reset(&mut stdin, &mut stdout, &mut display, &mut controller),
+  }
+
+  let mut display_model = DisplayModel::new(frame_period, &display);
+  let mut speed_meter = SpeedMeter::new(total_clocks);
+
+  // live input is ignored while replaying so that execution stays deterministic
+  let replaying = !session.replay.is_empty();
+  let mut recorded_controller = controller;
+
+  let mut window = Window::new("Atto‑8", WIDTH, HEIGHT, WindowOptions::default())
+    .map_err(|error| Error(format!("Unable to open window: {}", error)))?;
+  window.set_target_fps(FPS);
+  let (sender, receiver) = std::sync::mpsc::channel();
+  window.set_input_callback(Box::new(Keyboard(sender)));
+
+  let font = parse_font(include_str!("../fonts/3x3.txt"));
+  let mut buffer = vec![0x000000; WIDTH * HEIGHT];
+
+  while window.is_open() {
+    let mut input: Vec<u8> = vec![];
+    let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+    for key in window.get_keys_pressed(KeyRepeat::Yes) {
+      match key {
+        Key::Delete => stdout.clear(),
+        Key::Insert => {
+          let snapshot = common::snapshot(&mc, &stdin, &stdout, &display, controller);
+          status_line = common::save_snapshot(snapshot, session.snapshot_file);
+        }
+        Key::Tab if shift => {
+          speed_mode = speed_mode.next();
+          let speed = speed_mode.clock_speed(clock_speed) as f64;
+          status_line = speed_mode.status(unthrottled, speed, clock_speed);
+          speed_meter = SpeedMeter::new(total_clocks);
+        }
+        Key::Enter => input.push(0x0A),
+        Key::Backspace => input.push(0x08),
+        Key::Tab => input.push(0x09),
+        _ => {}
+      }
+    }
+    // control characters are not forwarded by `Keyboard` and are handled above instead
+    input.extend(
+      receiver
+        .try_iter()
+        .filter(|c| (0x20..0x7F).contains(c))
+        .map(|c| c as u8),
+    );
+
+    if !replaying {
+      for byte in input {
+        session.record(total_clocks, Input::Stdin(byte));
+        stdin.push_back(byte);
+      }
+      controller = CONTROLLER_KEYS
+        .iter()
+        .enumerate()
+        .fold(0x00, |acc, (index, key)| {
+          acc | ((window.is_key_down(*key) as u8) << index)
+        });
+    }
+
+    // run for one frame's worth of clock cycles, or for as many as fit in a frame when unthrottled
+    let frame_start = std::time::Instant::now();
+    let frame_budget = std::time::Duration::from_millis(1000 / FPS as u64 * 3 / 4);
+    let frame_speed = speed_mode.clock_speed(clock_speed) / FPS as u128;
+    let mut frame_clocks = total_clocks + frame_speed;
+    while !halted && total_clocks < frame_clocks {
+      session.replay(total_clocks, &mut stdin, &mut controller);
+      if controller != recorded_controller {
+        session.record(total_clocks, Input::Controller(controller));
+        recorded_controller = controller;
+      }
+
+      match mc.tick(&mut stdin, &mut stdout, &mut display, &mut controller) {
+        Ok(clocks) => {
+          total_clocks += clocks;
+          display_model.advance(clocks, &display);
+        }
+        Err(tick_trap) => {
+          display_model.settle(&display);
+          halted = true;
+          status_line = tick_trap.to_string();
+        }
+      }
+
+      let throttled = !(unthrottled && speed_mode == SpeedMode::Normal);
+      if !throttled && total_clocks >= frame_clocks && frame_start.elapsed() < frame_budget {
+        frame_clocks += frame_speed;
+      }
+    }
+
+    if let Some(speed) = speed_meter.measure(total_clocks) {
+      if !halted && speed_mode != SpeedMode::Paused {
+        status_line = speed_mode.status(unthrottled, speed, clock_speed);
+      }
+    }
+
+    render_display(&mut buffer, &display_model.intensities);
+    render_console(&mut buffer, &stdout, &font);
+    window.set_title(&match status_line.as_str() {
+      "" => "Atto‑8".to_string(),
+      status_line => format!("Atto‑8 — {}", status_line),
+    });
+    window
+      .update_with_buffer(&buffer, WIDTH, HEIGHT)
+      .map_err(|error| Error(format!("Unable to update window: {}", error)))?;
+  }
+
+  Ok(())
+}
+
+fn render_display(buffer: &mut [u32], intensities: &[u8; 0x100]) {
+  for y in 0..0x10 {
+    for x in 0..0x10 {
+      let color = intensities[y * 0x10 + x] as u32 * 0x010101;
+      fill(
+        buffer,
+        x * DISPLAY_SCALE,
+        y * DISPLAY_SCALE,
+        DISPLAY_SCALE,
+        color,
+      );
+    }
+  }
+}
+
+// renders the last lines of standard output, wrapping long lines and honoring backspaces
+fn render_console(buffer: &mut [u32], stdout: &VecDeque<u8>, font: &HashMap<char, [u8; 4]>) {
+  let mut lines: Vec<Vec<u8>> = vec![vec![]];
+  for byte in stdout.iter() {
+    let line = lines.last_mut().unwrap();
+    match byte {
+      0x0A => lines.push(vec![]),
+      0x08 => {
+        line.pop();
+      }
+      0x20..=0x7E if line.len() == CONSOLE_COLUMNS => lines.push(vec![*byte]),
+      0x20..=0x7E => line.push(*byte),
+      _ => {}
+    }
+  }
+
+  buffer[0x10 * DISPLAY_SCALE * WIDTH..].fill(CONSOLE_BACKGROUND);
+  let skipped = lines.len().saturating_sub(CONSOLE_ROWS);
+  for (row, line) in lines.iter().skip(skipped).enumerate() {
+    for (column, byte) in line.iter().enumerate() {
+      let glyph = font.get(&(*byte as char).to_ascii_uppercase());
+      for (y, bits) in glyph.unwrap_or(&[0x00; 4]).iter().enumerate() {
+        for x in 0..4 {
+          if bits >> (0x03 - x) & 0x01 != 0 {
+            let x = column * CELL_SIZE + x * FONT_SCALE;
+            let y = CONSOLE_TOP + row * CELL_SIZE + y * FONT_SCALE;
+            fill(buffer, x, y, FONT_SCALE, CONSOLE_FOREGROUND);
+          }
+        }
+      }
+    }
+  }
+}
+
+fn fill(buffer: &mut [u32], x: usize, y: usize, size: usize, color: u32) {
+  for y in y..y + size {
+    buffer[y * WIDTH + x..y * WIDTH + x + size].fill(color);
+  }
+}
+
+// fonts from /misc/fonts/ consist of a line with the character followed by two lines of block
+// elements, each of which encodes two rows of pixels. glyphs are returned as four rows of pixels,
+// most significant bit first
+fn parse_font(font: &str) -> HashMap<char, [u8; 4]> {
+  let lines: Vec<&str> = font.lines().collect();
+
+  lines
+    .chunks(3)
+    .filter_map(|chunk| match chunk {
+      [character, top, bottom] => {
+        let mut glyph = [0x00; 4];
+        for (row, line) in [top, bottom].iter().enumerate() {
+          for (x, block) in line.chars().take(4).enumerate() {
+            let (upper, lower) = match block {
+              '\u{2580}' => (1, 0),
+              '\u{2584}' => (0, 1),
+              '\u{2588}' => (1, 1),
+              _ => (0, 0),
+            };
+            glyph[row * 2] |= upper << (0x03 - x);
+            glyph[row * 2 + 1] |= lower << (0x03 - x);
+          }
+        }
+        Some((character.chars().next()?, glyph))
+      }
+      _ => None,
+    })
+    .collect()
+}
@@ -0,0 +1,198 @@
+use crate::*;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+// a microcomputer along with the peripherals it is attached to
+struct Side<MC: Tickable> {
+  mc: MC,
+  stdin: VecDeque<u8>,
+  stdout: VecDeque<u8>,
+  display: [u8; common::DISPLAY_BUFFER_LEN],
+  controller: u8,
+  clocks: u128,
+  pending: Option<TickTrap>, // tick trap hit while retiring an instruction
+}
+
+impl<MC: Tickable> Side<MC> {
+  fn new(mc: MC, input: &[u8]) -> Self {
+    let mut side = Side {
+      mc,
+      stdin: VecDeque::new(),
+      stdout: VecDeque::new(),
+      display: [0x00; common::DISPLAY_BUFFER_LEN],
+      controller: 0x00,
+      clocks: 0,
+      pending: None,
+    };
+    side.mc.reset(
+      &mut side.stdin,
+      &mut side.stdout,
+      &mut side.display,
+      &mut side.controller,
+    );
+    side.stdin.extend(input);
+    side
+  }
+
+  // ticks until an instruction is retired or a tick trap is hit. a tick trap hit on the very tick
+  // an instruction is retired, such as a program halt detected by Sim, is deferred to the next step
+  fn step(&mut self) -> (Option<TraceEntry>, Result<(), TickTrap>) {
+    if let Some(tick_trap) = self.pending.take() {
+      return (None, Err(tick_trap));
+    }
+    loop {
+      let result = self.mc.tick(
+        &mut self.stdin,
+        &mut self.stdout,
+        &mut self.display,
+        &mut self.controller,
+      );
+      if let Ok(clocks) = result {
+        self.clocks += clocks;
+      }
+      match (self.mc.retired(), result) {
+        (Some(entry), Ok(_)) => break (Some(entry), Ok(())),
+        (Some(entry), Err(tick_trap)) => {
+          self.pending = Some(tick_trap);
+          break (Some(entry), Ok(()));
+        }
+        (None, Err(tick_trap)) => break (None, Err(tick_trap)),
+        (None, Ok(_)) => continue,
+      }
+    }
+  }
+}
+
+// runs Emu and Sim side by side on the same memory image and the same standard input, comparing
+// architectural state at every instruction boundary. returns a process exit code
+pub fn lockstep(emu: emu::Microcomputer, sim: Microcomputer, clock_limit: Option<u128>) -> i32 {
+  // standard input is read in its entirety before execution begins
+  let mut input = vec![];
+  std::io::stdin().read_to_end(&mut input).unwrap();
+
+  let mut emu = Side::new(emu, &input);
+  let mut sim = Side::new(sim, &input);
+  let mut output = std::io::stdout().lock();
+
+  let mut steps: u128 = 0;
+  let (status, exit_code) = loop {
+    if clock_limit.is_some_and(|clock_limit| sim.clocks >= clock_limit) {
+      break (
+        format!(
+          "Clock limit reached after {} instructions in agreement",
+          steps
+        ),
+        2,
+      );
+    }
+
+    let (emu_entry, emu_result) = emu.step();
+    let (sim_entry, sim_result) = sim.step();
+
+    let emu_stdout: Vec<u8> = emu.stdout.drain(..).collect();
+    let sim_stdout: Vec<u8> = sim.stdout.drain(..).collect();
+    output.write_all(&emu_stdout).unwrap();
+
+    let emu_state = (emu.mc.mp.ip, emu.mc.mp.sp, emu.mc.mp.cf);
+    let sim_state = (sim.mc.mp.ip, sim.mc.mp.sp, sim.mc.mp.cf);
+
+    let mut differences: Vec<String> = vec![];
+    match (&emu_entry, &sim_entry) {
+      (Some(emu_entry), Some(sim_entry)) => {
+        if (emu_entry.ip, emu_entry.sp, emu_entry.cf) != (sim_entry.ip, sim_entry.sp, sim_entry.cf)
+        {
+          differences.push("state before instruction differs".to_string());
+        }
+        if emu_entry.instruction != sim_entry.instruction {
+          differences.push("fetched instruction differs".to_string());
+        }
+        if emu_entry.clocks != sim_entry.clocks {
+          differences.push(format!(
+            "clock count before instruction differs, Emu {} and Sim {}",
+            emu_entry.clocks, sim_entry.clocks
+          ));
+        }
+      }
+      (None, None) => {}
+      _ => differences.push("only one side retired the instruction".to_string()),
+    }
+    if emu_state != sim_state {
+      differences.push("state after instruction differs".to_string());
+    }
+    for address in 0..common::MEM_SIZE {
+      if emu.mc.mem[address] != sim.mc.mem[address] {
+        differences.push(format!(
+          "memory at {:02X} differs, Emu {:02X} and Sim {:02X}",
+          address, emu.mc.mem[address], sim.mc.mem[address]
+        ));
+      }
+    }
+    if emu_stdout != sim_stdout {
+      differences.push(format!(
+        "standard output differs, Emu {:02X?} and Sim {:02X?}",
+        emu_stdout, sim_stdout
+      ));
+    }
+    match (&emu_result, &sim_result) {
+      (Ok(()), Ok(())) => {}
+      (Err(emu_trap), Err(sim_trap)) if emu_trap.to_string() == sim_trap.to_string() => {}
+      _ => differences.push(format!(
+        "outcome differs, Emu {} and Sim {}",
+        describe_result(&emu_result),
+        describe_result(&sim_result)
+      )),
+    }
+
+    if !differences.is_empty() {
+      let entry = emu_entry.or(sim_entry);
+      let instruction = match &entry {
+        Some(entry) => format!(
+          "`{}` (opcode {:02X}) at {:02X} on clock {}",
+          common::instruction_to_token(entry.instruction.clone()),
+          common::instruction_to_opcode(entry.instruction.clone()),
+          entry.ip,
+          entry.clocks
+        ),
+        None => format!("at {:02X}", emu.mc.mp.ip),
+      };
+
+      let mut status = format!("Divergence at step {}, instruction {}", steps, instruction);
+      status += &format!(
+        "\n  Emu: IP {:02X} SP {:02X} CF {:01b}",
+        emu_state.0, emu_state.1, emu_state.2 as u8
+      );
+      status += &format!(
+        "\n  Sim: IP {:02X} SP {:02X} CF {:01b}",
+        sim_state.0, sim_state.1, sim_state.2 as u8
+      );
+      for difference in differences {
+        status += &format!("\n  {}", difference);
+      }
+      break (status, 1);
+    }
+
+    if let Err(tick_trap) = emu_result {
+      break (
+        format!(
+          "{} after {} instructions in agreement, {} clocks on Emu and {} clocks on Sim",
+          tick_trap, steps, emu.clocks, sim.clocks
+        ),
+        0,
+      );
+    }
+
+    steps += 1;
+  };
+
+  output.flush().unwrap();
+  eprintln!("{}", status);
+
+  exit_code
+}
+
+fn describe_result(result: &Result<(), TickTrap>) -> String {
+  match result {
+    Ok(()) => "retired instruction".to_string(),
+    Err(tick_trap) => tick_trap.to_string(),
+  }
+}
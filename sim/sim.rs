@@ -4,22 +4,237 @@ use std::collections::VecDeque;
 mod common;
 use common::*;
 
+#[cfg(feature = "gui")]
+#[path = "../misc/common/gui.rs"]
+mod gui;
+
+#[path = "../misc/common/export.rs"]
+mod export;
+use export::*;
+
+#[path = "../emu/microcomputer.rs"]
+#[allow(dead_code)]
+mod emu;
+mod lockstep;
+
 fn main() {
   let args: Vec<String> = std::env::args().collect();
-  if args.len() != 3 {
-    eprintln!("Usage: sim <memory image file> <microcode image file>");
+
+  let mut batch_mode = false;
+  let mut gui_mode = false;
+  let mut lockstep_mode = false;
+  let mut clock_limit: Option<u128> = None;
+  let mut exit_with_tos = false;
+  let mut clock_speed: Option<u128> = None;
+  let mut unthrottled = false;
+  let mut timer_rng = false;
+  let mut rng_seed: Option<u8> = None;
+  let mut frame_period: Option<u128> = None;
+  let mut symbol_file: Option<&String> = None;
+  let mut trace_file: Option<&String> = None;
+  let mut save_snapshot_file: Option<&String> = None;
+  let mut load_snapshot_file: Option<&String> = None;
+  let mut record_file: Option<&String> = None;
+  let mut replay_file: Option<&String> = None;
+  let mut frames_directory: Option<&String> = None;
+  let mut gif_file: Option<&String> = None;
+  let mut positional_args: Vec<&String> = vec![];
+
+  let mut args_iter = args.iter().skip(1);
+  while let Some(arg) = args_iter.next() {
+    match arg.as_str() {
+      "--batch" => batch_mode = true,
+      "--gui" => gui_mode = true,
+      "--lockstep" => lockstep_mode = true,
+      "--exit-with-tos" => exit_with_tos = true,
+      "--unthrottled" => unthrottled = true,
+      "--timer-rng" => timer_rng = true,
+      "--rng-seed" => {
+        rng_seed = Some(
+          args_iter
+            .next()
+            .and_then(|seed| u8::from_str_radix(seed, 16).ok())
+            .unwrap_or_else(|| {
+              eprintln!("Sim: Error: Option '--rng-seed' expects a byte in hexadecimal");
+              std::process::exit(1);
+            }),
+        )
+      }
+      "--clock-speed" => {
+        clock_speed = Some(
+          args_iter
+            .next()
+            .and_then(|hertz| hertz.parse().ok())
+            .filter(|hertz| *hertz > 0)
+            .unwrap_or_else(|| {
+              eprintln!("Sim: Error: Option '--clock-speed' expects a nonzero frequency in hertz");
+              std::process::exit(1);
+            }),
+        )
+      }
+      "--clock-limit" => {
+        clock_limit = Some(
+          args_iter
+            .next()
+            .and_then(|clocks| clocks.parse().ok())
+            .unwrap_or_else(|| {
+              eprintln!("Sim: Error: Option '--clock-limit' expects a clock count");
+              std::process::exit(1);
+            }),
+        )
+      }
+      "--frame-period" => {
+        frame_period = Some(
+          args_iter
+            .next()
+            .and_then(|clocks| clocks.parse().ok())
+            .filter(|clocks| *clocks > 0)
+            .unwrap_or_else(|| {
+              eprintln!("Sim: Error: Option '--frame-period' expects a nonzero clock count");
+              std::process::exit(1);
+            }),
+        )
+      }
+      "--trace" => {
+        trace_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Sim: Error: Option '--trace' expects a trace file");
+          std::process::exit(1);
+        }))
+      }
+      "--save-snapshot" => {
+        save_snapshot_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Sim: Error: Option '--save-snapshot' expects a snapshot file");
+          std::process::exit(1);
+        }))
+      }
+      "--load-snapshot" => {
+        load_snapshot_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Sim: Error: Option '--load-snapshot' expects a snapshot file");
+          std::process::exit(1);
+        }))
+      }
+      "--record" => {
+        record_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Sim: Error: Option '--record' expects a recording file");
+          std::process::exit(1);
+        }))
+      }
+      "--replay" => {
+        replay_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Sim: Error: Option '--replay' expects a recording file");
+          std::process::exit(1);
+        }))
+      }
+      "--frames" => {
+        frames_directory = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Sim: Error: Option '--frames' expects a frame directory");
+          std::process::exit(1);
+        }))
+      }
+      "--gif" => {
+        gif_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Sim: Error: Option '--gif' expects a GIF file");
+          std::process::exit(1);
+        }))
+      }
+      "--symbols" => {
+        symbol_file = Some(args_iter.next().unwrap_or_else(|| {
+          eprintln!("Sim: Error: Option '--symbols' expects a symbol file");
+          std::process::exit(1);
+        }))
+      }
+      _ if arg.starts_with("--") => {
+        eprintln!("Sim: Error: Unknown option '{}'", arg);
+        std::process::exit(1);
+      }
+      _ => positional_args.push(arg),
+    }
+  }
+
+  if positional_args.len() != 2 {
+    eprintln!("Usage: sim [--batch] [--gui] [--lockstep] [--clock-limit <clocks>] [--exit-with-tos] [--clock-speed <hertz>] [--unthrottled] [--frame-period <clocks>] [--timer-rng] [--rng-seed <seed>] [--symbols <symbol file>] [--trace <trace file>] [--save-snapshot <snapshot file>] [--load-snapshot <snapshot file>] [--record <recording file>] [--replay <recording file>] [--frames <frame directory>] [--gif <gif file>] <memory image file> <microcode image file>");
+    std::process::exit(1);
+  }
+
+  if batch_mode && lockstep_mode {
+    eprintln!("Sim: Error: Options '--batch' and '--lockstep' are mutually exclusive");
+    std::process::exit(1);
+  }
+
+  if gui_mode && !cfg!(feature = "gui") {
+    eprintln!("Sim: Error: Option '--gui' requires building with feature 'gui'");
+    std::process::exit(1);
+  }
+
+  if gui_mode && (batch_mode || lockstep_mode) {
+    eprintln!("Sim: Error: Option '--gui' cannot be combined with '--batch' or '--lockstep'");
     std::process::exit(1);
   }
 
-  let memory_image_file: &String = &args[1];
+  if timer_rng && lockstep_mode {
+    eprintln!("Sim: Error: Options '--timer-rng' and '--lockstep' are mutually exclusive");
+    std::process::exit(1);
+  }
 
-  let memory_image = std::fs::read(memory_image_file)
+  if rng_seed.is_some() && !timer_rng {
+    eprintln!("Sim: Error: Option '--rng-seed' requires option '--timer-rng'");
+    std::process::exit(1);
+  }
+
+  if (unthrottled || frame_period.is_some()) && (batch_mode || lockstep_mode) {
+    eprintln!(
+      "Sim: Error: Options '--unthrottled' and '--frame-period' cannot be combined with '--batch' or '--lockstep'"
+    );
+    std::process::exit(1);
+  }
+
+  if trace_file.is_some() && lockstep_mode {
+    eprintln!("Sim: Error: Options '--trace' and '--lockstep' are mutually exclusive");
+    std::process::exit(1);
+  }
+
+  if (frames_directory.is_some() || gif_file.is_some()) && lockstep_mode {
+    eprintln!("Sim: Error: Options '--frames' and '--gif' cannot be combined with '--lockstep'");
+    std::process::exit(1);
+  }
+
+  if gif_file.is_some() && !batch_mode && !gui_mode {
+    eprintln!("Sim: Error: Option '--gif' requires option '--batch' or '--gui'");
+    std::process::exit(1);
+  }
+
+  if clock_limit.is_some() && !batch_mode && !lockstep_mode {
+    eprintln!("Sim: Error: Option '--clock-limit' requires option '--batch' or '--lockstep'");
+    std::process::exit(1);
+  }
+
+  if exit_with_tos && !batch_mode {
+    eprintln!("Sim: Error: Option '--exit-with-tos' requires option '--batch'");
+    std::process::exit(1);
+  }
+
+  let session_options = [
+    save_snapshot_file,
+    load_snapshot_file,
+    record_file,
+    replay_file,
+  ];
+  if session_options.iter().any(Option::is_some) && lockstep_mode {
+    eprintln!(
+      "Sim: Error: Options '--save-snapshot', '--load-snapshot', '--record' and '--replay' cannot be combined with '--lockstep'"
+    );
+    std::process::exit(1);
+  }
+
+  let memory_image_file: &String = positional_args[0];
+
+  let (memory_image, banks) = std::fs::read(memory_image_file)
+    .map(common::split_memory_image)
     .unwrap_or_else(|_| {
       eprintln!("Sim: Error: Unable to read file '{}'", memory_image_file);
       std::process::exit(1);
     })
-    .try_into()
-    .unwrap_or_else(|_| {
+    .unwrap_or_else(|| {
       eprintln!(
         "Sim: Error: Memory image '{}' has incorrect size",
         memory_image_file
@@ -27,7 +242,7 @@ fn main() {
       std::process::exit(1);
     });
 
-  let microcode_image_file: &String = &args[2];
+  let microcode_image_file: &String = positional_args[1];
 
   let microcode_image = std::fs::read(microcode_image_file)
     .unwrap_or_else(|_| {
@@ -46,7 +261,26 @@ fn main() {
       std::process::exit(1);
     });
 
-  let mc = Microcomputer {
+  let symbols: Option<Symbols> = symbol_file.map(|symbol_file| {
+    let symbols = std::fs::read_to_string(symbol_file).unwrap_or_else(|_| {
+      eprintln!("Sim: Error: Unable to read file '{}'", symbol_file);
+      std::process::exit(1);
+    });
+    common::parse_symbols(&symbols).unwrap_or_else(|error| {
+      eprintln!("Sim: Error: Symbol file '{}': {}", symbol_file, error);
+      std::process::exit(1);
+    })
+  });
+
+  let mut peripherals = common::default_peripherals();
+  if timer_rng {
+    peripherals.push(Box::new(TimerRng::new(rng_seed.unwrap_or(0x00))));
+  }
+  if !banks.is_empty() {
+    peripherals.push(Box::new(BankSwitch::new(banks.clone())));
+  }
+
+  let mut mc = Microcomputer {
     mem: memory_image,
     mp: Microprocessor {
       ip: 0x00,
@@ -78,9 +312,165 @@ fn main() {
     data: 0x00,
     read: Signal::Inactive,
     wrt: Signal::Inactive,
+
+    halt: HaltDetector::default(),
+    clocks: 0,
+    current: None,
+    retired: None,
+    symbols: symbols.clone(),
+
+    peripherals,
+  };
+
+  let resume: Option<Snapshot> = load_snapshot_file.map(|snapshot_file| {
+    let snapshot = std::fs::read_to_string(snapshot_file).unwrap_or_else(|_| {
+      eprintln!("Sim: Error: Unable to read file '{}'", snapshot_file);
+      std::process::exit(1);
+    });
+    common::parse_snapshot(&snapshot)
+      .and_then(|snapshot| mc.load(&snapshot).map(|()| snapshot))
+      .unwrap_or_else(|error| {
+        eprintln!("Sim: Error: Snapshot file '{}': {}", snapshot_file, error);
+        std::process::exit(1);
+      })
+  });
+
+  let replay = replay_file
+    .map(|replay_file| {
+      let recording = std::fs::read_to_string(replay_file).unwrap_or_else(|_| {
+        eprintln!("Sim: Error: Unable to read file '{}'", replay_file);
+        std::process::exit(1);
+      });
+      common::parse_recording(&recording).unwrap_or_else(|error| {
+        eprintln!("Sim: Error: Recording file '{}': {}", replay_file, error);
+        std::process::exit(1);
+      })
+    })
+    .unwrap_or_default();
+
+  let record = record_file.map(|record_file| {
+    let mut record = std::fs::File::create(record_file)
+      .map(std::io::LineWriter::new)
+      .unwrap_or_else(|_| {
+        eprintln!("Sim: Error: Unable to write file '{}'", record_file);
+        std::process::exit(1);
+      });
+    use std::io::Write;
+    writeln!(record, "# Generated by Sim").unwrap();
+    Box::new(record) as Box<dyn std::io::Write>
+  });
+
+  let session = Session {
+    resume,
+    snapshot_file: save_snapshot_file,
+    replay,
+    record,
+  };
+
+  if lockstep_mode {
+    let mut peripherals = common::default_peripherals();
+    if !banks.is_empty() {
+      peripherals.push(Box::new(BankSwitch::new(banks)));
+    }
+    let emu = emu::Microcomputer {
+      mem: memory_image,
+      mp: emu::Microprocessor {
+        ip: 0x00,
+        sp: 0x00,
+        cf: false,
+      },
+      halt: HaltDetector::default(),
+      accesses: vec![],
+      clocks: 0,
+      timings: common::microcode_to_clocks(&microcode_image),
+      retired: None,
+      symbols,
+      peripherals,
+      stack_check: None,
+    };
+    std::process::exit(lockstep::lockstep(emu, mc, clock_limit));
+  }
+
+  let clock_speed = clock_speed.unwrap_or(1000000);
+  let timing = Timing {
+    clock_speed,
+    unthrottled,
+    frame_period: frame_period.unwrap_or(clock_speed / 60),
+  };
+
+  let mut exporter =
+    Exporter::new(mc, frames_directory, gif_file, clock_speed).unwrap_or_else(|error| {
+      eprintln!("Sim: Error: {}", error);
+      std::process::exit(1);
+    });
+  let exit_code = match trace_file {
+    Some(trace_file) => {
+      let mut trace = std::fs::File::create(trace_file)
+        .map(std::io::LineWriter::new)
+        .unwrap_or_else(|_| {
+          eprintln!("Sim: Error: Unable to write file '{}'", trace_file);
+          std::process::exit(1);
+        });
+      use std::io::Write;
+      writeln!(trace, "# Generated by Sim").unwrap();
+      let mc = &mut exporter;
+      run(
+        Tracer { mc, trace },
+        batch_mode,
+        gui_mode,
+        timing,
+        clock_limit,
+        exit_with_tos,
+        session,
+      )
+    }
+    None => run(
+      &mut exporter,
+      batch_mode,
+      gui_mode,
+      timing,
+      clock_limit,
+      exit_with_tos,
+      session,
+    ),
   };
+  exporter.finish().unwrap_or_else(|error| {
+    eprintln!("Sim: Error: {}", error);
+    std::process::exit(1);
+  });
+
+  std::process::exit(exit_code);
+
+  fn run<MC: std::fmt::Display + Tickable>(
+    mc: MC,
+    batch_mode: bool,
+    gui_mode: bool,
+    timing: Timing,
+    clock_limit: Option<u128>,
+    exit_with_tos: bool,
+    session: Session,
+  ) -> i32 {
+    if gui_mode {
+      #[cfg(feature = "gui")]
+      gui::execute(mc, timing, session).unwrap_or_else(|error| {
+        eprintln!("Sim: Error: {}", error);
+        std::process::exit(1);
+      });
+      return 0;
+    }
+
+    if batch_mode {
+      return common::execute_headless(mc, clock_limit, exit_with_tos, session).unwrap_or_else(
+        |error| {
+          eprintln!("Sim: Error: {}", error);
+          std::process::exit(1);
+        },
+      );
+    }
 
-  common::execute(mc, 1000000);
+    common::execute(mc, timing, session);
+    0
+  }
 }
 
 struct Microcomputer {
@@ -93,6 +483,14 @@ struct Microcomputer {
   data: u8,     // data bus
   read: Signal, // memory read
   wrt: Signal,  // memory write
+
+  halt: HaltDetector,          // halt detection
+  clocks: u128,                // clock count, for tracing
+  current: Option<TraceEntry>, // instruction being executed, for tracing
+  retired: Option<TraceEntry>, // last retired instruction, for tracing
+  symbols: Option<Symbols>,    // symbols, for display only
+
+  peripherals: Vec<Box<dyn Peripheral>>, // memory-mapped devices
 }
 
 struct Microprocessor {
@@ -259,6 +657,12 @@ impl Tickable for Microcomputer {
     }
 
     // address latch and memory
+    let mut io = Io {
+      stdin,
+      stdout,
+      display,
+      controller,
+    };
     self.addr = mp.al;
     self.read = mp.ctrl.mem_data;
     self.wrt = mp.ctrl.data_mem;
@@ -267,35 +671,66 @@ impl Tickable for Microcomputer {
         mp.al = self.data;
       }
       if let Signal::Active = self.wrt {
-        // stdout
-        if self.addr as usize == common::STDIO_BUFFER {
-          stdout.push_back(self.data);
+        if let Some(entry) = &mut self.current {
+          entry
+            .accesses
+            .push(MemoryAccess::Write(self.addr, self.data));
+        }
+        // peripherals
+        let mut consumed = false;
+        for peripheral in self.peripherals.iter_mut() {
+          if peripheral.claims(self.addr) {
+            consumed |= peripheral.write(self.addr, self.data, self.clocks, &mut io);
+          }
+        }
+        if consumed {
+          self.halt.mark_dirty();
         } else {
+          if self.mem[self.addr as usize] != self.data {
+            self.halt.mark_dirty();
+          }
           self.mem[self.addr as usize] = self.data;
-        }
-        // display
-        if self.addr as usize & common::DISPLAY_BUFFER == common::DISPLAY_BUFFER {
-          display[self.addr as usize & !common::DISPLAY_BUFFER] = self.data
+          for peripheral in self.peripherals.iter_mut() {
+            if peripheral.claims(self.addr) && peripheral.remap(self.addr, &mut self.mem) {
+              self.halt.mark_dirty();
+            }
+          }
         }
       }
     }
     if let Signal::Active = self.read {
-      // stdin and controller
-      if self.addr as usize == common::STDIO_BUFFER {
-        self.data = *stdin.front().unwrap_or(controller);
-        if let Clock::Rising = self.clk {
-          stdin.pop_front();
+      // peripherals drive the data bus throughout a read but only take effect on the rising edge
+      let (addr, clocks) = (self.addr, self.clocks);
+      let mut claimed = self
+        .peripherals
+        .iter_mut()
+        .filter(|peripheral| peripheral.claims(addr));
+      let value = match self.clk {
+        Clock::Rising => claimed.find_map(|peripheral| peripheral.read(addr, clocks, &mut io)),
+        _ => claimed.find_map(|peripheral| peripheral.peek(addr, clocks, &io)),
+      };
+      self.data = match value {
+        Some(value) => {
+          self.halt.mark_dirty();
+          value
         }
-      } else {
-        self.data = self.mem[self.addr as usize];
+        None => self.mem[self.addr as usize],
+      };
+      if let (Clock::Rising, Some(entry)) = (&self.clk, &mut self.current) {
+        entry
+          .accesses
+          .push(MemoryAccess::Read(self.addr, self.data));
       }
     }
     if let Reset::Asserted = self.rst {
+      self.halt = HaltDetector::default();
+      self.clocks = 0;
+      self.current = None;
+      self.retired = None;
       mp.al = 0x00;
-      stdin.push_back(self.mem[common::STDIO_BUFFER]);
-      display.copy_from_slice(
-        &self.mem[common::DISPLAY_BUFFER..common::DISPLAY_BUFFER + common::DISPLAY_BUFFER_LEN],
-      );
+      for peripheral in self.peripherals.iter_mut() {
+        peripheral.reset(&mut self.mem, &mut io);
+      }
     }
 
     // X latch and Y latch and Z latch
@@ -332,24 +767,158 @@ impl Tickable for Microcomputer {
       mp.zl = 0x00;
     }
 
+    // instruction boundary. the rising edge with a cleared step counter begins the fetch cycle of
+    // the next instruction, at which point the previous instruction has fully completed. sequences
+    // that loop, such as that of `rot`, also clear the step counter but do not begin with a fetch
+    let boundary = mp.sc == 0x00 && matches!(mp.ctrl.ip_data, Signal::Active);
+    if let (Clock::Rising, Reset::Deasserted) = (&self.clk, &self.rst) {
+      if boundary {
+        let entry = TraceEntry {
+          clocks: self.clocks,
+          ip: mp.ip,
+          sp: mp.sp,
+          cf: mp.cf,
+          instruction: Err(0x00), // decoded from the instruction fetch once retired
+          accesses: vec![],
+        };
+        self.retired = self.current.replace(entry).map(|entry| TraceEntry {
+          instruction: match entry.accesses.first() {
+            Some(MemoryAccess::Read(_, opcode)) => common::opcode_to_instruction(*opcode),
+            _ => entry.instruction.clone(),
+          },
+          ..entry
+        });
+      }
+      self.clocks += 1;
+
+      if boundary && self.halt.step(mp.ip, mp.sp, mp.cf) {
+        return Err(TickTrap::ProgramHalt(self.mem[mp.sp as usize]));
+      }
+    }
+
     Ok(match self.clk {
       Clock::Rising => 1,
       _ => 0,
     })
   }
+
+  fn retired(&mut self) -> Option<TraceEntry> {
+    self.retired.take()
+  }
+
+  fn save(&self, snapshot: &mut Snapshot) {
+    let mp = &self.mp;
+    let signal = |signal: Signal| matches!(signal, Signal::Active) as u8;
+
+    snapshot.put("machine", "Sim");
+    snapshot.put("clocks", self.clocks);
+    snapshot.put_bytes("mem", &self.mem);
+    snapshot.put_bytes("ip", &[mp.ip]);
+    snapshot.put_bytes("sp", &[mp.sp]);
+    snapshot.put("cf", mp.cf as u8);
+    snapshot.put_bytes("il", &[mp.il]);
+    snapshot.put_bytes("sc", &[mp.sc]);
+    snapshot.put_bytes("al", &[mp.al]);
+    snapshot.put_bytes("xl", &[mp.xl]);
+    snapshot.put_bytes("yl", &[mp.yl]);
+    snapshot.put_bytes("zl", &[mp.zl]);
+    snapshot.put_bytes("ctrl", &common::result_into_u16(Ok(mp.ctrl)).to_be_bytes());
+    snapshot.put("pull", signal(mp.pull));
+    snapshot.put_bytes("ones", &[mp.ones]);
+    snapshot.put_bytes("sum", &[mp.sum]);
+    snapshot.put_bytes("nand", &[mp.nand]);
+    snapshot.put("cin", mp.cin as u8);
+    snapshot.put("cout", mp.cout as u8);
+    snapshot.put("zero", mp.zero as u8);
+    snapshot.put(
+      "clk",
+      match self.clk {
+        Clock::Rising => "rising",
+        Clock::High => "high",
+        Clock::Falling => "falling",
+        Clock::Low => "low",
+      },
+    );
+    snapshot.put("rst", matches!(self.rst, Reset::Asserted) as u8);
+    snapshot.put_bytes("addr", &[self.addr]);
+    snapshot.put_bytes("data", &[self.data]);
+    snapshot.put("read", signal(self.read));
+    snapshot.put("wrt", signal(self.wrt));
+    self.halt.save(snapshot);
+    for peripheral in self.peripherals.iter() {
+      peripheral.save(snapshot);
+    }
+  }
+
+  fn load(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+    match snapshot.get::<String>("machine")?.as_str() {
+      "Sim" => {}
+      machine => Err(Error(format!("Snapshot was taken by {}, not Sim", machine)))?,
+    }
+    let signal = |field: &str| {
+      Ok(match snapshot.get_bit(field)? {
+        true => Signal::Active,
+        false => Signal::Inactive,
+      })
+    };
+    let byte = |field: &str| snapshot.get_bytes(field).map(|[byte]| byte);
+
+    let mp = &mut self.mp;
+    (mp.ip, mp.sp, mp.cf) = (byte("ip")?, byte("sp")?, snapshot.get_bit("cf")?);
+    (mp.il, mp.sc, mp.al) = (byte("il")?, byte("sc")?, byte("al")?);
+    (mp.xl, mp.yl, mp.zl) = (byte("xl")?, byte("yl")?, byte("zl")?);
+    mp.ctrl = common::u16_into_result(u16::from_be_bytes(snapshot.get_bytes("ctrl")?))
+      .map_err(|_| Error("Malformed snapshot field `ctrl`".to_string()))?;
+    mp.pull = signal("pull")?;
+    (mp.ones, mp.sum, mp.nand) = (byte("ones")?, byte("sum")?, byte("nand")?);
+    mp.cin = snapshot.get_bit("cin")?;
+    mp.cout = snapshot.get_bit("cout")?;
+    mp.zero = snapshot.get_bit("zero")?;
+
+    self.clocks = snapshot.get("clocks")?;
+    self.mem = snapshot.get_bytes("mem")?;
+    self.clk = match snapshot.get::<String>("clk")?.as_str() {
+      "rising" => Clock::Rising,
+      "high" => Clock::High,
+      "falling" => Clock::Falling,
+      "low" => Clock::Low,
+      _ => Err(Error("Malformed snapshot field `clk`".to_string()))?,
+    };
+    self.rst = match snapshot.get_bit("rst")? {
+      true => Reset::Asserted,
+      false => Reset::Deasserted,
+    };
+    (self.addr, self.data) = (byte("addr")?, byte("data")?);
+    (self.read, self.wrt) = (signal("read")?, signal("wrt")?);
+    self.halt = HaltDetector::load(snapshot)?;
+    for peripheral in self.peripherals.iter_mut() {
+      peripheral.load(snapshot)?;
+    }
+    self.current = None; // tracing resumes at the next instruction boundary
+    self.retired = None;
+    Ok(())
+  }
 }
 
 impl std::fmt::Display for Microcomputer {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     write!(
       f,
-      "{}\r\n{}\r\n{}",
+      "{}\r\nCLK  RST  ADDR  DATA  READ  WRT\r\n{}  {}  {:02X}    {:02X}    {}    {}\r\n\r\n{}",
       self.mp,
-      format!(
-        "CLK  RST  ADDR  DATA  READ  WRT\r\n{}  {}  {:02X}    {:02X}    {}    {}\r\n",
-        self.clk, self.rst, self.addr, self.data, self.read, self.wrt
+      self.clk,
+      self.rst,
+      self.addr,
+      self.data,
+      self.read,
+      self.wrt,
+      common::render_memory(
+        &self.mem,
+        self.mp.ip,
+        self.mp.sp,
+        self.mp.cf,
+        self.symbols.as_ref(),
       ),
-      common::render_memory(&self.mem, self.mp.ip, self.mp.sp, self.mp.cf),
     )
   }
 }
@@ -358,39 +927,35 @@ impl std::fmt::Display for Microprocessor {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     write!(
       f,
-      "{}\r\n{}\r\n{}\r\n{}",
-      format!(
-        "IP  SP  CF  IL  SC  AL  XL  YL  ZL\r\n{:02X}  {:02X}  {:01b}   {:02X}  {:02X}  {:02X}  {:02X}  {:02X}  {:02X}\r\n",
-        self.ip, self.sp, self.cf as u8, self.il, self.sc, self.al, self.xl, self.yl, self.zl
-      ),
-      format!(
-        "CTRL  {} {} {} {} {} {} {} {}\r\n      {} {} {} {} {} {} {} {}\r\n",
-        self.ctrl.data_ip,
-        self.ctrl.data_sp,
-        self.ctrl.data_cf,
-        self.ctrl.data_il,
-        self.ctrl.data_al,
-        self.ctrl.data_xl,
-        self.ctrl.data_yl,
-        self.ctrl.data_zl,
-        self.ctrl.mem_data,
-        self.ctrl.data_mem,
-        self.ctrl.clr_sc,
-        self.ctrl.set_cin,
-        self.ctrl.ip_data,
-        self.ctrl.sp_data,
-        self.ctrl.sum_data,
-        self.ctrl.nand_data,
-      ),
-      format!(
-        "PULL  ONES  SUM  NAND  CIN  COUT  ZERO\r\n{}    {:02X}    {:02X}   {:02X}    {:01b}    {:01b}     {:01b}\r\n",
-        self.pull, self.ones, self.sum, self.nand, self.cin as u8, self.cout as u8, self.zero as u8,
-      ),
-      format!(
-        "MIC  ({:#X} words)\r\n",
-        self.mic.len()
-      ),
-    )
+      "IP  SP  CF  IL  SC  AL  XL  YL  ZL\r\n{:02X}  {:02X}  {:01b}   {:02X}  {:02X}  {:02X}  {:02X}  {:02X}  {:02X}\r\n\r\n",
+      self.ip, self.sp, self.cf as u8, self.il, self.sc, self.al, self.xl, self.yl, self.zl
+    )?;
+    write!(
+      f,
+      "CTRL  {} {} {} {} {} {} {} {}\r\n      {} {} {} {} {} {} {} {}\r\n\r\n",
+      self.ctrl.data_ip,
+      self.ctrl.data_sp,
+      self.ctrl.data_cf,
+      self.ctrl.data_il,
+      self.ctrl.data_al,
+      self.ctrl.data_xl,
+      self.ctrl.data_yl,
+      self.ctrl.data_zl,
+      self.ctrl.mem_data,
+      self.ctrl.data_mem,
+      self.ctrl.clr_sc,
+      self.ctrl.set_cin,
+      self.ctrl.ip_data,
+      self.ctrl.sp_data,
+      self.ctrl.sum_data,
+      self.ctrl.nand_data,
+    )?;
+    write!(
+      f,
+      "PULL  ONES  SUM  NAND  CIN  COUT  ZERO\r\n{}    {:02X}    {:02X}   {:02X}    {:01b}    {:01b}     {:01b}\r\n\r\n",
+      self.pull, self.ones, self.sum, self.nand, self.cin as u8, self.cout as u8, self.zero as u8,
+    )?;
+    write!(f, "MIC  ({:#X} words)\r\n", self.mic.len())
   }
 }
 
@@ -0,0 +1,318 @@
+// runs the binaries in batch mode against the programs in `test/tests/`, which are copied into a
+// fresh directory alongside `lib/` just like `test.py` does
+
+#[path = "../misc/common/common.rs"]
+mod common;
+use common::*;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const INPUT: &[u8] = b"hello, atto-8";
+
+struct Run {
+  stdout: Vec<u8>,
+  stderr: String,
+  code: Option<i32>,
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+  std::fs::create_dir_all(to).unwrap();
+  for entry in std::fs::read_dir(from).unwrap() {
+    let path = entry.unwrap().path();
+    match path.is_dir() {
+      true => copy_dir(&path, &to.join(path.file_name().unwrap())),
+      false => drop(std::fs::copy(&path, to.join(path.file_name().unwrap())).unwrap()),
+    }
+  }
+}
+
+fn workspace(name: &str) -> PathBuf {
+  let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+  let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+  let _ = std::fs::remove_dir_all(&dir);
+  copy_dir(&root.join("lib"), &dir.join("lib"));
+  copy_dir(&root.join("test/tests"), &dir);
+  dir
+}
+
+fn run(bin: &str, args: &[&Path], options: &[&str], stdin: &[u8]) -> Run {
+  let mut child = Command::new(bin)
+    .args(args)
+    .args(options)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .unwrap();
+  child.stdin.take().unwrap().write_all(stdin).unwrap();
+  let output = child.wait_with_output().unwrap();
+  Run {
+    stdout: output.stdout,
+    stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    code: output.status.code(),
+  }
+}
+
+// assembles `<name>.asm` into `<name>.mem` and `<name>.sym`, by absolute path so that the symbol
+// file can be loaded from anywhere
+fn assemble(dir: &Path, name: &str) -> (PathBuf, PathBuf) {
+  let (image, symbols) = (
+    dir.join(format!("{}.mem", name)),
+    dir.join(format!("{}.sym", name)),
+  );
+  let source = dir.join(format!("{}.asm", name));
+  let symbol_file = symbols.to_str().unwrap();
+  let asm = run(
+    env!("CARGO_BIN_EXE_asm"),
+    &[&source, &image],
+    &["--symbols", symbol_file],
+    b"",
+  );
+  assert_eq!(asm.code, Some(0), "{}", asm.stderr);
+  (image, symbols)
+}
+
+// builds the microcode image once per directory, for `sim` to run memory images with
+fn microcode(dir: &Path) -> PathBuf {
+  let microcode = dir.join("microcode.mic");
+  if !microcode.exists() {
+    let mic = run(env!("CARGO_BIN_EXE_mic"), &[&microcode], &[], b"");
+    assert_eq!(mic.code, Some(0), "{}", mic.stderr);
+  }
+  microcode
+}
+
+// the `<status> after <clocks> clocks` line written to `stderr` once a batch run stops
+fn status(run: &Run) -> &str {
+  run.stderr.lines().last().unwrap_or_default()
+}
+
+#[test]
+fn symbols_resolve_labels_and_origins() {
+  let dir = workspace("symbols_resolve_labels_and_origins");
+  let (_, symbols) = assemble(&dir, "shout");
+  let symbols = std::fs::read_to_string(symbols).unwrap();
+  let symbols = parse_symbols(&symbols).unwrap_or_else(|Error(error)| panic!("{}", error));
+
+  let label = |name: &str| symbols.labels[&Label::Global(name.to_string())];
+  assert_eq!(label("loop"), 0x01);
+  assert_eq!(label("done"), 0x17);
+
+  // origins point back into the source, through the macros that expanded into every byte
+  let source = |address: u8| {
+    let (Pos(File(path), row, _), macros) = &symbols.origins[&address];
+    let line = symbols.sources[path].as_ref().unwrap()[*row]
+      .trim()
+      .to_string();
+    let macros: Vec<&str> = macros.iter().map(|Macro(name)| name.as_str()).collect();
+    (
+      path.file_name().unwrap().to_str().unwrap().to_string(),
+      line,
+      macros,
+    )
+  };
+  assert_eq!(
+    source(0x03),
+    (
+      "shout.asm".into(),
+      "ld0 !zr :done !bcs # stop at the end of input".into(),
+      vec!["main"]
+    )
+  );
+  assert_eq!(
+    source(0x15),
+    ("shout.asm".into(), ":loop !jmp".into(), vec!["main"])
+  );
+  assert_eq!(
+    source(label("done")),
+    (
+      "core.asm".into(),
+      "here! here. .here".into(),
+      vec!["main", "hlt", "here"]
+    )
+  );
+  assert!(symbols.code.contains(&label("loop")));
+}
+
+#[test]
+fn emu_and_sim_traces_agree() {
+  let dir = workspace("emu_and_sim_traces_agree");
+  let (image, _) = assemble(&dir, "shout");
+  let microcode = microcode(&dir);
+  let (emu_trace, sim_trace) = (dir.join("emu.trace"), dir.join("sim.trace"));
+
+  let emu_options = [
+    "--batch",
+    "--timer-rng",
+    "--trace",
+    emu_trace.to_str().unwrap(),
+  ];
+  let emu = run(env!("CARGO_BIN_EXE_emu"), &[&image], &emu_options, INPUT);
+  let sim_options = [
+    "--batch",
+    "--timer-rng",
+    "--trace",
+    sim_trace.to_str().unwrap(),
+  ];
+  let sim = run(
+    env!("CARGO_BIN_EXE_sim"),
+    &[&image, &microcode],
+    &sim_options,
+    INPUT,
+  );
+  assert_eq!(status(&emu), "Program halted after 3029 clocks");
+  assert_eq!(status(&sim), status(&emu));
+  assert_eq!(sim.stdout, emu.stdout);
+
+  // memory accesses are left out, as the emulator only models the accesses an instruction needs
+  // whereas the simulator reports every bus cycle, such as both reads of `iff`
+  let load = |trace: &Path| {
+    let trace = std::fs::read_to_string(trace).unwrap();
+    parse_trace(&trace)
+      .unwrap_or_else(|Error(error)| panic!("{}", error))
+      .into_iter()
+      .map(|entry| {
+        TraceEntry {
+          accesses: vec![],
+          ..entry
+        }
+        .to_string()
+      })
+      .collect::<Vec<String>>()
+  };
+  let (emu_trace, sim_trace) = (load(&emu_trace), load(&sim_trace));
+  assert!(emu_trace.len() > INPUT.len());
+  assert_eq!(emu_trace, sim_trace);
+}
+
+// stops a run halfway with a snapshot then resumes it, which must be indistinguishable from a
+// run that was never interrupted
+fn resume(bin: &str, args: &[&Path], snapshot: &Path) {
+  let snapshot = snapshot.to_str().unwrap();
+  let live = run(bin, args, &["--batch", "--timer-rng"], INPUT);
+  let options = ["--batch", "--timer-rng", "--clock-limit", "1500"];
+  let first = run(
+    bin,
+    args,
+    &[&options[..], &["--save-snapshot", snapshot]].concat(),
+    INPUT,
+  );
+  let options = ["--batch", "--timer-rng", "--load-snapshot", snapshot];
+  let second = run(bin, args, &options, b"");
+
+  assert_eq!(status(&live), "Program halted after 3029 clocks");
+  // the emulator only stops at instruction boundaries, so it may run a few clocks past the limit
+  assert_eq!(first.code, Some(2));
+  assert!(status(&first).starts_with("Clock limit reached after 15"));
+  assert!(first.stdout.len() < live.stdout.len());
+  assert_eq!(
+    [&first.stdout[..], &second.stdout[..]].concat(),
+    live.stdout
+  );
+  assert_eq!((second.code, status(&second)), (live.code, status(&live)));
+}
+
+#[test]
+fn emu_resumes_from_snapshot() {
+  let dir = workspace("emu_resumes_from_snapshot");
+  let (image, _) = assemble(&dir, "shout");
+  resume(env!("CARGO_BIN_EXE_emu"), &[&image], &dir.join("emu.snap"));
+}
+
+#[test]
+fn sim_resumes_from_snapshot() {
+  let dir = workspace("sim_resumes_from_snapshot");
+  let (image, _) = assemble(&dir, "shout");
+  let microcode = microcode(&dir);
+  resume(
+    env!("CARGO_BIN_EXE_sim"),
+    &[&image, &microcode],
+    &dir.join("sim.snap"),
+  );
+}
+
+// records a run then replays it without any input, which must reproduce the run exactly
+fn replay(bin: &str, args: &[&Path], recording: &Path) {
+  let recording = recording.to_str().unwrap();
+  let live = run(
+    bin,
+    args,
+    &["--batch", "--timer-rng", "--record", recording],
+    INPUT,
+  );
+  let replayed = run(
+    bin,
+    args,
+    &["--batch", "--timer-rng", "--replay", recording],
+    b"",
+  );
+
+  assert_eq!(status(&live), "Program halted after 3029 clocks");
+  assert_eq!(replayed.stdout, live.stdout);
+  assert_eq!(
+    (replayed.code, status(&replayed)),
+    (live.code, status(&live))
+  );
+}
+
+#[test]
+fn emu_replays_recording() {
+  let dir = workspace("emu_replays_recording");
+  let (image, _) = assemble(&dir, "shout");
+  replay(env!("CARGO_BIN_EXE_emu"), &[&image], &dir.join("emu.rec"));
+}
+
+#[test]
+fn sim_replays_recording() {
+  let dir = workspace("sim_replays_recording");
+  let (image, _) = assemble(&dir, "shout");
+  let microcode = microcode(&dir);
+  replay(
+    env!("CARGO_BIN_EXE_sim"),
+    &[&image, &microcode],
+    &dir.join("sim.rec"),
+  );
+}
+
+// runs a program that checks its own results and halts with the mismatches it found on top of
+// the stack, on both the emulator and the simulator
+fn check(name: &str) {
+  let dir = workspace(name);
+  let (image, _) = assemble(&dir, name);
+  let microcode = microcode(&dir);
+  let options = ["--batch", "--exit-with-tos"];
+  let emu = run(env!("CARGO_BIN_EXE_emu"), &[&image], &options, b"");
+  let sim = run(
+    env!("CARGO_BIN_EXE_sim"),
+    &[&image, &microcode],
+    &options,
+    b"",
+  );
+
+  for run in [emu, sim] {
+    assert!(status(&run).starts_with("Program halted"), "{}", run.stderr);
+    assert_eq!(run.code, Some(0), "mismatches in {}.asm", name);
+  }
+}
+
+#[test]
+fn macros() {
+  check("macros");
+}
+
+#[test]
+fn conditionals() {
+  check("conditionals");
+}
+
+#[test]
+fn constants() {
+  check("constants");
+}
+
+#[test]
+fn rotate() {
+  check("rotate");
+}
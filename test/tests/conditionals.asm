@@ -0,0 +1,18 @@
+@ lib/core.asm
+
+main!
+  x00 # mismatches are accumulated into the exit status
+  x01 @if x11 @else xEE @end !expect(x11)
+  x00 @if xEE @else x22 @end !expect(x22)
+  x01 @if x00 @if xEE @else x33 @end @else xEE @end !expect(x33)
+  x00 @if xEE x01 @if xEE @end @else x01 @if x44 @end @end !expect(x44)
+  ?!defined @if x55 @else xEE @end !expect(x55)
+  ?!undefined not @if x66 @else xEE @end !expect(x66)
+  ?:here ?!undefined orr @if x77 @else xEE @end !expect(x77)
+  ?:here ?!undefined and @if xEE @else x88 @end !expect(x88)
+  ?:skipped @if x99 @else xEE @end !expect(x99)
+  x00 @if skipped: @end
+  here: !hlt
+
+defined! nop
+expect(value)! $value xor orr # leaves the accumulator unchanged if the top of the stack matches
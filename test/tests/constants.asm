@@ -0,0 +1,24 @@
+@ lib/core.asm
+
+main!
+  x00 # mismatches are accumulated into the exit status
+  x10 @equ WIDTH
+  WIDTH 2 * @equ ROW_PAIR # constants may reference constants
+  42 !expect(x2A)
+  b101010 !expect(x2A)
+  '*' !expect(x2A)
+  '\n' !expect(x0A)
+  ROW_PAIR !expect(x20)
+  2 3 4 * add !expect(x0E)
+  2 3 add 4 * !expect(x14)
+  WIDTH 3 / 3 % !expect(x02)
+  WIDTH 0 / !expect(xFF)
+  WIDTH 0 % !expect(x10)
+  :str_hi x01 add lda !expect(x69)
+  :str_hi 2 add @equ STR_END # constants may reference labels
+  STR_END lda !expect(x00)
+  !hlt
+
+  str_hi: "Hi\0"
+
+expect(value)! $value xor orr # leaves the accumulator unchanged if the top of the stack matches
@@ -29,8 +29,19 @@ main!
   dyn: :dyn @dyn
   !self
   @error
+  !pair(x01)
+  !unbound
+  x01 @if x02 @else x03 @else x04 @end
+  @end
+  NOPE
+  x01 @equ TWICE x02 @equ TWICE
+  lda @equ LOAD
 
   !row !row !row !row !row !row !row !row !row !row !row !row !row !row !row !row
 row! @00 @00 @00 @00 @00 @00 @00 @00 @00 @00 @00 @00 @00 @00 @00 @00
 
 self! !main
+
+pair(a, b)! $a $b
+twin(a, a)! $a
+unbound! $nope
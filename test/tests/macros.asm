@@ -0,0 +1,21 @@
+@ lib/core.asm
+
+main!
+  x00 # mismatches are accumulated into the exit status
+  x02 x03 !add_then(x04 add) !expect(x09)
+  x02 !wrap(x03, ) !expect(x05)
+  x01 x02 x03 !wrap(, add) !expect(x06)
+  x10 !sat_add(x20) !expect(x30)
+  xF0 !sat_add(x20) !expect(xFF)
+  x00 !twice(!sat_add(x60)) !expect(xC0)
+  x80 !twice(!sat_add(x60)) !expect(xFF)
+  x01 !twice(!twice(shl)) !expect(x10)
+  !skip_to(.end) xEE end. x42 !expect(x42)
+  !hlt
+
+add_then(op)! add $op
+wrap(pre, post)! $pre add $post
+twice(op)! $op $op
+skip_to(label)! $label !jmp # `.end` is local to `!main`, not to `!skip_to`
+sat_add(n)! clc $n add @dyn .done !bcc pop xFF done. # `.done` is local to every expansion
+expect(value)! $value xor orr # leaves the accumulator unchanged if the top of the stack matches
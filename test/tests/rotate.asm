@@ -0,0 +1,9 @@
+@ lib/core.asm
+
+main!
+  x00 # mismatches are accumulated into the exit status
+  xFC x01 x03 rot @dyn swp lda swp # reads back the byte below the stack that held the count
+  !expect(x08) !expect(x00) # `rot` counts down in place, leaving that byte cleared
+  !hlt
+
+expect(value)! $value xor orr # leaves the accumulator unchanged if the top of the stack matches
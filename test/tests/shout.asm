@@ -0,0 +1,12 @@
+@ lib/core.asm
+@ lib/types.asm
+@ lib/stdio.asm
+
+main! !nop
+  loop:
+    !getc
+    ld0 !zr :done !bcs # stop at the end of input
+    x20 not and !putc # clear bit 5, making letters uppercase
+    xDF lda xE0 sta # copy the random number generator to the display
+  :loop !jmp
+  done: !hlt